@@ -0,0 +1,465 @@
+// typed decoding of the standard signed attributes; every accessor returns all the values of the SET,
+// None when the attribute is absent
+
+use bcder::decode::{self, BytesSource, Constructed, DecodeError};
+use bcder::{Mode, OctetString, Oid, Tag};
+use bytes::Bytes;
+
+use crate::crl::CertificateList;
+use crate::crypto::HashAlg;
+use crate::ocsp::{BasicOcspResponse, ResponderId};
+use crate::oid;
+use crate::{AlgorithmIdentifier, Attribute, Certificate, SignerInfo, Validity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigningTime {
+    pub time: i64,
+    pub generalized: bool, // GeneralizedTime, only used from 2050 on (RFC 5652 section 11.3)
+}
+
+// RFC 6211
+#[derive(Debug)]
+pub struct CmsAlgorithmProtection {
+    pub digest_algorithm: AlgorithmIdentifier,
+    pub signature_algorithm: Option<AlgorithmIdentifier>, // [1] IMPLICIT
+    pub mac_algorithm: Option<AlgorithmIdentifier>,       // [2] IMPLICIT
+}
+
+// capabilities in order of preference (RFC 8551 section 2.5.2)
+#[derive(Debug)]
+pub struct SmimeCapability {
+    pub capability: Oid,
+    pub parameters: Option<Vec<u8>>, // raw DER
+}
+
+// signingCertificate (ESSCertID, always SHA-1) and signingCertificateV2 (ESSCertIDv2)
+#[derive(Debug)]
+pub struct SigningCertificate {
+    pub v2: bool,
+    pub certs: Vec<EssCertId>, // the signer certificate first
+    pub policies: Vec<Oid>,
+}
+
+#[derive(Debug)]
+pub struct EssCertId {
+    pub hash_algorithm: Oid, // sha1 for v1, DEFAULT sha256 for v2
+    pub cert_hash: Vec<u8>,
+    pub issuer_serial: Option<IssuerSerial>,
+}
+
+#[derive(Debug)]
+pub struct IssuerSerial {
+    pub issuer: Vec<Bytes>, // directoryName entries of the GeneralNames, content of the Name SEQUENCE
+    pub serial_number: Vec<u8>,
+}
+
+// RFC 5126 section 5.11.1
+#[derive(Debug)]
+pub struct CommitmentTypeIndication {
+    pub commitment_type: Oid,
+    pub qualifiers: Vec<Vec<u8>>, // raw DER of each CommitmentTypeQualifier
+}
+
+// RFC 5126 section 6.3.4, the module uses EXPLICIT tags
+#[derive(Debug)]
+pub struct RevocationValues {
+    pub crls: Vec<CertificateList>,
+    pub ocsp_responses: Vec<BasicOcspResponse>,
+}
+
+// OtherHash: a bare OCTET STRING is a SHA-1 hash
+#[derive(Debug)]
+pub struct OtherHash {
+    pub hash_algorithm: Oid,
+    pub hash: Vec<u8>,
+}
+
+// an entry of complete-certificate-references (RFC 5126 section 6.2.1)
+#[derive(Debug)]
+pub struct OtherCertId {
+    pub cert_hash: OtherHash,
+    pub issuer_serial: Option<IssuerSerial>,
+}
+
+// an entry of complete-revocation-references (RFC 5126 section 6.2.2), one per certificate of the path
+#[derive(Debug)]
+pub struct CrlOcspRef {
+    pub crl_hashes: Vec<OtherHash>, // crlids [0]
+    pub ocsp_ids: Vec<OcspResponsesId>, // ocspids [1]
+}
+
+#[derive(Debug)]
+pub struct OcspResponsesId {
+    pub responder_id: ResponderId,
+    pub produced_at: i64,
+    pub response_hash: Option<OtherHash>,
+}
+
+impl Attribute {
+    // DER of each value of the SET
+    pub fn values(&self) -> Vec<Vec<u8>> {
+        let mut values = Vec::new();
+        let _ = Constructed::decode(self.value.as_slice(), Mode::Ber, |cons| {
+            while let Ok(value) = cons.capture_one() {
+                values.push(value.as_slice().to_vec());
+            }
+            Ok(())
+        });
+        values
+    }
+
+    // every value decoded with op, the first bad one fails the whole attribute
+    pub fn decode_values<T, F>(&self, op: F) -> Result<Vec<T>, String>
+    where
+        F: Fn(&mut Constructed<BytesSource>) -> Result<T, DecodeError<<BytesSource as decode::Source>::Error>>,
+    {
+        let values = self.values();
+        if values.is_empty() {
+            return Err(format!("{} attribute without values", oid::describe(&self.oid)));
+        }
+        values.iter()
+            .map(|value| Constructed::decode(Bytes::copy_from_slice(value), Mode::Der, &op)
+                .map_err(|e| format!("malformed {}: {}", oid::describe(&self.oid), e)))
+            .collect()
+    }
+}
+
+impl SignerInfo {
+    pub fn signed_attribute<T: AsRef<[u8]>>(&self, attribute_type: &Oid<T>) -> Option<&Attribute> {
+        self.auth_attributes.as_ref()?.iter().find(|a| a.oid.as_ref() == attribute_type.as_ref())
+    }
+
+    pub fn unsigned_attribute<T: AsRef<[u8]>>(&self, attribute_type: &Oid<T>) -> Option<&Attribute> {
+        self.unauth_attributes.as_ref()?.iter().find(|a| a.oid.as_ref() == attribute_type.as_ref())
+    }
+
+    pub fn content_type(&self) -> Option<Result<Vec<Oid>, String>> {
+        Some(self.signed_attribute(&oid::CONTENT_TYPE)?.decode_values(Oid::take_from))
+    }
+
+    pub fn message_digest(&self) -> Option<Result<Vec<Vec<u8>>, String>> {
+        Some(self.signed_attribute(&oid::MESSAGE_DIGEST)?.decode_values(|cons| {
+            Ok(OctetString::take_from(cons)?.to_bytes().to_vec())
+        }))
+    }
+
+    pub fn signing_time(&self) -> Option<Result<Vec<SigningTime>, String>> {
+        Some(self.signed_attribute(&oid::SIGNING_TIME)?.decode_values(|cons| {
+            let (time, tag) = Validity::take_time_from(cons)?;
+            Ok(SigningTime { time, generalized: tag == Tag::GENERALIZED_TIME })
+        }))
+    }
+
+    pub fn cms_algorithm_protection(&self) -> Option<Result<Vec<CmsAlgorithmProtection>, String>> {
+        Some(self.signed_attribute(&oid::CMS_ALGORITHM_PROTECTION)?.decode_values(CmsAlgorithmProtection::take_from))
+    }
+
+    // one SMIMECapabilities SEQUENCE per value
+    pub fn smime_capabilities(&self) -> Option<Result<Vec<Vec<SmimeCapability>>, String>> {
+        Some(self.signed_attribute(&oid::SMIME_CAPABILITIES)?.decode_values(|cons| {
+            cons.take_sequence(|cons| {
+                let mut capabilities = Vec::new();
+                while let Some(capability) = cons.take_opt_sequence(SmimeCapability::take_content_from)? {
+                    capabilities.push(capability);
+                }
+                Ok(capabilities)
+            })
+        }))
+    }
+
+    pub fn signing_certificate(&self) -> Option<Result<Vec<SigningCertificate>, String>> {
+        Some(self.signed_attribute(&oid::SIGNING_CERTIFICATE)?.decode_values(|cons| SigningCertificate::take_from(cons, false)))
+    }
+
+    pub fn signing_certificate_v2(&self) -> Option<Result<Vec<SigningCertificate>, String>> {
+        Some(self.signed_attribute(&oid::SIGNING_CERTIFICATE_V2)?.decode_values(|cons| SigningCertificate::take_from(cons, true)))
+    }
+
+    pub fn commitment_type_indication(&self) -> Option<Result<Vec<CommitmentTypeIndication>, String>> {
+        Some(self.signed_attribute(&oid::COMMITMENT_TYPE_INDICATION)?.decode_values(CommitmentTypeIndication::take_from))
+    }
+
+    // unsigned CAdES certificate-values: the path of the signer and of the revocation data issuers
+    pub fn certificate_values(&self) -> Option<Result<Vec<Vec<Certificate>>, String>> {
+        Some(self.unsigned_attribute(&oid::CERT_VALUES)?.decode_values(|cons| {
+            cons.take_sequence(|cons| {
+                let mut certs = Vec::new();
+                while let Ok(der) = cons.capture_one() {
+                    certs.push(Certificate::from_der(der.as_slice()).map_err(|e| {
+                        DecodeError::content(e.to_string(), decode::Pos::default())
+                    })?);
+                }
+                Ok(certs)
+            })
+        }))
+    }
+
+    pub fn revocation_values(&self) -> Option<Result<Vec<RevocationValues>, String>> {
+        Some(self.unsigned_attribute(&oid::REVOCATION_VALUES)?.decode_values(RevocationValues::take_from))
+    }
+
+    pub fn complete_certificate_references(&self) -> Option<Result<Vec<Vec<OtherCertId>>, String>> {
+        Some(self.unsigned_attribute(&oid::CERTIFICATE_REFS)?.decode_values(|cons| {
+            cons.take_sequence(|cons| {
+                let mut refs = Vec::new();
+                while let Some(cert_id) = cons.take_opt_sequence(|cons| {
+                    let cert_hash = OtherHash::take_from(cons)?;
+                    let issuer_serial = cons.take_opt_sequence(IssuerSerial::take_content_from)?;
+                    Ok(OtherCertId { cert_hash, issuer_serial })
+                })? {
+                    refs.push(cert_id);
+                }
+                Ok(refs)
+            })
+        }))
+    }
+
+    pub fn complete_revocation_references(&self) -> Option<Result<Vec<Vec<CrlOcspRef>>, String>> {
+        Some(self.unsigned_attribute(&oid::REVOCATION_REFS)?.decode_values(|cons| {
+            cons.take_sequence(|cons| {
+                let mut refs = Vec::new();
+                while let Some(crl_ocsp_ref) = cons.take_opt_sequence(CrlOcspRef::take_content_from)? {
+                    refs.push(crl_ocsp_ref);
+                }
+                Ok(refs)
+            })
+        }))
+    }
+}
+
+impl CmsAlgorithmProtection {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let digest_algorithm = AlgorithmIdentifier::take_from(cons)?;
+            let signature_algorithm = cons.take_opt_constructed_if(Tag::CTX_1, AlgorithmIdentifier::take_content_from)?;
+            let mac_algorithm = cons.take_opt_constructed_if(Tag::CTX_2, AlgorithmIdentifier::take_content_from)?;
+            // exactly one of them
+            if signature_algorithm.is_some() == mac_algorithm.is_some() {
+                return Err(DecodeError::content("CMSAlgorithmProtection needs one of signature or MAC algorithm", decode::Pos::default()));
+            }
+            Ok(CmsAlgorithmProtection { digest_algorithm, signature_algorithm, mac_algorithm })
+        })
+    }
+}
+
+impl SmimeCapability {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let algorithm = AlgorithmIdentifier::take_content_from(cons)?;
+        Ok(SmimeCapability { capability: algorithm.algorithm, parameters: algorithm.parameters })
+    }
+}
+
+impl SigningCertificate {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>, v2: bool) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let certs = cons.take_sequence(|cons| {
+                let mut certs = Vec::new();
+                while let Some(cert) = cons.take_opt_sequence(|cons| EssCertId::take_content_from(cons, v2))? {
+                    certs.push(cert);
+                }
+                Ok(certs)
+            })?;
+            let policies = cons.take_opt_sequence(|cons| {
+                let mut policies = Vec::new();
+                // PolicyInformation, the qualifiers are skipped
+                while let Some(policy) = cons.take_opt_sequence(|cons| {
+                    let policy = Oid::take_from(cons)?;
+                    cons.skip_all()?;
+                    Ok(policy)
+                })? {
+                    policies.push(policy);
+                }
+                Ok(policies)
+            })?.unwrap_or_default();
+            Ok(SigningCertificate { v2, certs, policies })
+        })
+    }
+}
+
+impl EssCertId {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>, v2: bool) -> Result<Self, DecodeError<S::Error>> {
+        let hash_algorithm = if v2 {
+            cons.take_opt_sequence(AlgorithmIdentifier::take_content_from)?
+                .map_or_else(|| oid::owned(oid::SHA256), |alg| alg.algorithm)
+        } else {
+            oid::owned(oid::SHA1)
+        };
+        let cert_hash = OctetString::take_from(cons)?.to_bytes().to_vec();
+        let issuer_serial = cons.take_opt_sequence(IssuerSerial::take_content_from)?;
+        Ok(EssCertId { hash_algorithm, cert_hash, issuer_serial })
+    }
+}
+
+impl IssuerSerial {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let issuer = cons.take_sequence(|cons| {
+            let mut names = Vec::new();
+            while let Some(name) = cons.take_opt_value(|tag, content| {
+                if tag == Tag::CTX_4 {
+                    // directoryName is EXPLICIT, Name is a CHOICE
+                    let name = content.as_constructed()?.take_sequence(|cons| Ok(cons.capture_all()?.into_bytes()))?;
+                    return Ok(Some(name));
+                }
+                match content {
+                    decode::Content::Primitive(content) => content.skip_all()?,
+                    decode::Content::Constructed(content) => content.skip_all()?,
+                }
+                Ok(None)
+            })? {
+                names.extend(name);
+            }
+            Ok(names)
+        })?;
+        let serial_number = cons.take_primitive_if(Tag::INTEGER, |content| Ok(content.take_all()?.to_vec()))?;
+        Ok(IssuerSerial { issuer, serial_number })
+    }
+}
+
+impl CommitmentTypeIndication {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let commitment_type = Oid::take_from(cons)?;
+            let qualifiers = cons.take_opt_sequence(|cons| {
+                let mut qualifiers = Vec::new();
+                while let Ok(qualifier) = cons.capture_one() {
+                    qualifiers.push(qualifier.as_slice().to_vec());
+                }
+                Ok(qualifiers)
+            })?.unwrap_or_default();
+            Ok(CommitmentTypeIndication { commitment_type, qualifiers })
+        })
+    }
+}
+
+impl RevocationValues {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let crls = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                cons.take_sequence(|cons| {
+                    let mut crls = Vec::new();
+                    while let Ok(der) = cons.capture_one() {
+                        crls.push(CertificateList::from_der(der.as_slice()).map_err(|e| {
+                            DecodeError::content(e.to_string(), decode::Pos::default())
+                        })?);
+                    }
+                    Ok(crls)
+                })
+            })?.unwrap_or_default();
+            let ocsp_responses = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+                cons.take_sequence(|cons| {
+                    let mut responses = Vec::new();
+                    while let Ok(der) = cons.capture_one() {
+                        responses.push(BasicOcspResponse::from_der(der.as_slice()).map_err(|e| {
+                            DecodeError::content(e.to_string(), decode::Pos::default())
+                        })?);
+                    }
+                    Ok(responses)
+                })
+            })?.unwrap_or_default();
+            cons.skip_all()?; // otherRevVals
+            Ok(RevocationValues { crls, ocsp_responses })
+        })
+    }
+}
+
+impl OtherHash {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        OtherHash::take_opt_from(cons)?.ok_or_else(|| DecodeError::content("missing OtherHash", decode::Pos::default()))
+    }
+
+    pub fn take_opt_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Option<Self>, DecodeError<S::Error>> {
+        if let Some(hash) = cons.take_opt_primitive_if(Tag::OCTET_STRING, |content| Ok(content.take_all()?.to_vec()))? {
+            return Ok(Some(OtherHash { hash_algorithm: oid::owned(oid::SHA1), hash }));
+        }
+        cons.take_opt_sequence(|cons| {
+            let hash_algorithm = AlgorithmIdentifier::take_from(cons)?.algorithm;
+            let hash = OctetString::take_from(cons)?.to_bytes().to_vec();
+            Ok(OtherHash { hash_algorithm, hash })
+        })
+    }
+
+    // false for hash algorithms we cannot compute
+    pub fn matches(&self, der: &[u8]) -> bool {
+        HashAlg::from_oid(&self.hash_algorithm).is_some_and(|hash| hash.digest(der) == self.hash)
+    }
+}
+
+impl CrlOcspRef {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        // CRLListID, a SEQUENCE OF CrlValidatedID; the crlIdentifier is only a hint
+        let crl_hashes = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+            cons.take_sequence(|cons| {
+                cons.take_sequence(|cons| {
+                    let mut hashes = Vec::new();
+                    while let Some(hash) = cons.take_opt_sequence(|cons| {
+                        let hash = OtherHash::take_from(cons)?;
+                        cons.skip_all()?;
+                        Ok(hash)
+                    })? {
+                        hashes.push(hash);
+                    }
+                    Ok(hashes)
+                })
+            })
+        })?.unwrap_or_default();
+        let ocsp_ids = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+            cons.take_sequence(|cons| {
+                cons.take_sequence(|cons| {
+                    let mut ids = Vec::new();
+                    while let Some(id) = cons.take_opt_sequence(OcspResponsesId::take_content_from)? {
+                        ids.push(id);
+                    }
+                    Ok(ids)
+                })
+            })
+        })?.unwrap_or_default();
+        cons.skip_all()?; // otherRev [2]
+        Ok(CrlOcspRef { crl_hashes, ocsp_ids })
+    }
+}
+
+impl OcspResponsesId {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let (responder_id, produced_at) = cons.take_sequence(|cons| {
+            let responder_id = ResponderId::take_from(cons)?;
+            let (produced_at, _) = Validity::take_time_from(cons)?;
+            Ok((responder_id, produced_at))
+        })?;
+        let response_hash = OtherHash::take_opt_from(cons)?;
+        Ok(OcspResponsesId { responder_id, produced_at, response_hash })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_pkcs7;
+    use crate::testdata::{MSG, SIGNED, TIME};
+
+    #[test]
+    fn openssl_signed_attributes() {
+        let pkcs7 = parse_pkcs7(SIGNED).unwrap();
+        let signer_info = &pkcs7.signed_data().unwrap().signer_infos[0];
+        assert_eq!(signer_info.content_type().unwrap().unwrap(), [oid::DATA]);
+        assert_eq!(signer_info.message_digest().unwrap().unwrap(), [HashAlg::Sha256.digest(MSG)]);
+        let signing_time = signer_info.signing_time().unwrap().unwrap();
+        assert!(signing_time[0].time > 1577836800 && signing_time[0].time < TIME);
+        assert!(!signing_time[0].generalized);
+        let capabilities = signer_info.smime_capabilities().unwrap().unwrap();
+        assert_eq!(capabilities[0][0].capability, oid::AES256_CBC);
+        assert!(signer_info.signing_certificate_v2().is_none());
+        assert!(signer_info.cms_algorithm_protection().is_none());
+    }
+
+    #[test]
+    fn every_value_decoded() {
+        let signing_time = |value: &[u8]| Attribute { oid: oid::owned(oid::SIGNING_TIME), value: value.to_vec() }
+            .decode_values(|cons| Validity::take_time_from(cons).map(|(time, _)| time));
+        // UTCTime 500101000000Z is 1950, GeneralizedTime 20500101000000Z is 2050
+        let utc = b"\x17\x0d500101000000Z";
+        let generalized = b"\x18\x0f20500101000000Z";
+        assert_eq!(signing_time(&[&utc[..], &generalized[..]].concat()).unwrap(), [-631152000, 2524608000]);
+        assert_eq!(signing_time(b"").unwrap_err(), "signingTime attribute without values");
+        assert!(signing_time(b"\x04\x01\x00").unwrap_err().starts_with("malformed signingTime"));
+    }
+}
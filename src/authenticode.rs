@@ -0,0 +1,297 @@
+// Authenticode (Windows Authenticode Portable Executable Signature Format): the WIN_CERTIFICATE entries
+// of the PE security directory and the image hash their SpcIndirectDataContent signs
+
+use std::ops::Range;
+
+use bcder::decode::{Constructed, DecodeError};
+use bcder::{Mode, OctetString, Oid};
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::crypto::HashAlg;
+use crate::report::VerificationReport;
+use crate::{dump, extensions, oid};
+use crate::{AlgorithmIdentifier, Certificate, SignedData};
+
+pub const WIN_CERT_REVISION_2_0: u16 = 0x0200;
+pub const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const SECURITY_DIRECTORY: usize = 4; // IMAGE_DIRECTORY_ENTRY_SECURITY
+
+#[derive(Debug)]
+pub struct PeImage {
+    pub pe32_plus: bool,
+    pub checksum_offset: usize, // of the optional header CheckSum field
+    pub security_directory_offset: usize, // of the data directory entry, offset and size
+    pub certificate_table: Range<usize>, // empty for an unsigned image
+    pub certificates: Vec<WinCertificate>,
+}
+
+#[derive(Debug)]
+pub struct WinCertificate {
+    pub offset: usize,
+    pub revision: u16,
+    pub certificate_type: u16,
+    pub certificate: Vec<u8>, // bCertificate, the PKCS#7 SignedData for WIN_CERT_TYPE_PKCS_SIGNED_DATA
+}
+
+#[derive(Debug)]
+pub struct SpcIndirectDataContent {
+    pub data_type: Oid,
+    pub digest_algorithm: AlgorithmIdentifier,
+    pub digest: Vec<u8>,
+}
+
+#[derive(Serialize)]
+pub struct AuthenticodeReport {
+    pub offset: usize,
+    pub revision: u16,
+    pub certificate_type: u16,
+    pub image_hash_error: Option<String>,
+    pub code_signing_errors: Vec<String>, // one per signer whose certificate is not for code signing
+    pub verification: VerificationReport,
+}
+
+impl PeImage {
+    // the headers Authenticode needs, and the certificate table when there is one
+    pub fn parse(pe: &[u8]) -> Result<Self, String> {
+        if pe.get(..2) != Some(b"MZ") {
+            return Err("not a PE file, no MZ header".to_string());
+        }
+        let pe_offset = read_u32(pe, 0x3c)? as usize;
+        if pe.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+            return Err("not a PE file, no PE signature".to_string());
+        }
+        // COFF file header, 20 bytes, then the optional header
+        let optional_header = pe_offset + 24;
+        let optional_header_size = read_u16(pe, pe_offset + 20)? as usize;
+        let pe32_plus = match read_u16(pe, optional_header)? {
+            PE32_MAGIC => false,
+            PE32_PLUS_MAGIC => true,
+            magic => return Err(format!("unknown optional header magic {:#x}", magic)),
+        };
+        let (directory_count_offset, directories) = match pe32_plus {
+            false => (optional_header + 92, optional_header + 96),
+            true => (optional_header + 108, optional_header + 112),
+        };
+        if (read_u32(pe, directory_count_offset)? as usize) <= SECURITY_DIRECTORY {
+            return Err("the optional header has no security directory".to_string());
+        }
+        let security_directory_offset = directories + SECURITY_DIRECTORY * 8;
+        if security_directory_offset + 8 > optional_header + optional_header_size {
+            return Err("the security directory is outside the optional header".to_string());
+        }
+
+        // a file offset, not an RVA
+        let table_offset = read_u32(pe, security_directory_offset)? as usize;
+        let table_size = read_u32(pe, security_directory_offset + 4)? as usize;
+        let certificate_table = table_offset..table_offset + table_size;
+        if table_size > 0 && (table_offset < security_directory_offset + 8 || certificate_table.end > pe.len()) {
+            return Err(format!("certificate table {:?} is outside the file", certificate_table));
+        }
+        let certificates = match table_size {
+            0 => Vec::new(),
+            _ => win_certificates(pe, certificate_table.clone())?,
+        };
+        Ok(PeImage {
+            pe32_plus,
+            checksum_offset: optional_header + 64,
+            security_directory_offset,
+            certificate_table,
+            certificates,
+        })
+    }
+
+    // the file without the CheckSum, the security directory entry and the certificate table
+    pub fn image_hash(&self, pe: &[u8], hash: HashAlg) -> Vec<u8> {
+        let end = match self.certificate_table.is_empty() {
+            true => pe.len(),
+            false => self.certificate_table.start,
+        };
+        let mut image = Vec::with_capacity(end);
+        image.extend_from_slice(&pe[..self.checksum_offset]);
+        image.extend_from_slice(&pe[self.checksum_offset + 4..self.security_directory_offset]);
+        image.extend_from_slice(&pe[self.security_directory_offset + 8..end]);
+        hash.digest(&image)
+    }
+
+    // bytes after the certificate table, which no signature covers
+    pub fn trailing_data(&self, pe: &[u8]) -> usize {
+        match self.certificate_table.is_empty() {
+            true => 0,
+            false => pe.len() - self.certificate_table.end,
+        }
+    }
+}
+
+impl WinCertificate {
+    pub fn type_name(&self) -> String {
+        match self.certificate_type {
+            0x0001 => "WIN_CERT_TYPE_X509".to_string(),
+            WIN_CERT_TYPE_PKCS_SIGNED_DATA => "WIN_CERT_TYPE_PKCS_SIGNED_DATA".to_string(),
+            0x0003 => "WIN_CERT_TYPE_RESERVED_1".to_string(),
+            0x0004 => "WIN_CERT_TYPE_TS_STACK_SIGNED".to_string(),
+            other => format!("certificate type {:#06x}", other),
+        }
+    }
+}
+
+impl SpcIndirectDataContent {
+    // SEQUENCE { data SpcAttributeTypeAndOptionalValue, messageDigest DigestInfo }, from the value of the
+    // SEQUENCE as the SignedData holds it
+    pub fn from_content(content: &[u8]) -> Result<Self, String> {
+        Constructed::decode(Bytes::copy_from_slice(content), Mode::Ber, |cons| {
+            let data_type = cons.take_sequence(|cons| {
+                let data_type = Oid::take_from(cons)?;
+                cons.skip_all()?; // SpcPeImageData, only flags and a legacy link
+                Ok(data_type)
+            })?;
+            let (digest_algorithm, digest) = cons.take_sequence(|cons| {
+                let digest_algorithm = AlgorithmIdentifier::take_from(cons)?;
+                let digest = OctetString::take_from(cons)?.into_bytes().to_vec();
+                Ok((digest_algorithm, digest))
+            })?;
+            Ok(SpcIndirectDataContent { data_type, digest_algorithm, digest })
+        }).map_err(|err: DecodeError<_>| format!("Error decoding SpcIndirectDataContent: {}", err))
+    }
+}
+
+impl AuthenticodeReport {
+    pub fn new(certificate: &WinCertificate, image_hash: &Result<(), String>, code_signing_errors: Vec<String>, verification: VerificationReport) -> Self {
+        AuthenticodeReport {
+            offset: certificate.offset,
+            revision: certificate.revision,
+            certificate_type: certificate.certificate_type,
+            image_hash_error: image_hash.clone().err(),
+            code_signing_errors,
+            verification,
+        }
+    }
+}
+
+// the signed SpcIndirectDataContent against the image hash of the file
+pub fn check_image_hash(signed_data: &SignedData, image: &PeImage, pe: &[u8]) -> Result<(), String> {
+    let content_info = &signed_data.content_info;
+    if content_info.content_type != oid::SPC_INDIRECT_DATA {
+        return Err(format!("content is {}, not SpcIndirectDataContent", oid::describe(&content_info.content_type)));
+    }
+    let content = content_info.content.as_deref().ok_or("SpcIndirectDataContent is missing")?;
+    let indirect = SpcIndirectDataContent::from_content(content)?;
+    if indirect.data_type != oid::SPC_PE_IMAGE_DATA {
+        return Err(format!("signed data is {}, not a PE image", oid::describe(&indirect.data_type)));
+    }
+    let hash = HashAlg::from_oid(&indirect.digest_algorithm.algorithm)
+        .ok_or_else(|| format!("unsupported image hash algorithm {}", oid::describe(&indirect.digest_algorithm.algorithm)))?;
+    if image.image_hash(pe, hash) != indirect.digest {
+        return Err("the image hash does not match the signed one".to_string());
+    }
+    Ok(())
+}
+
+// Windows only accepts signers whose certificate has the code signing purpose
+pub fn check_code_signing(cert: &Certificate) -> Result<(), String> {
+    let purposes = cert.tbs_certificate.find_extension(oid::EXT_KEY_USAGE)
+        .and_then(|extension| extensions::ext_key_usage(&extension.extn_value))
+        .ok_or("the signer certificate has no extended key usage")?;
+    if !purposes.iter().any(|purpose| *purpose == oid::KP_CODE_SIGNING) {
+        return Err("the signer certificate is not for code signing".to_string());
+    }
+    Ok(())
+}
+
+// WIN_CERTIFICATE { dwLength, wRevision, wCertificateType, bCertificate }, each one 8 byte aligned
+fn win_certificates(pe: &[u8], table: Range<usize>) -> Result<Vec<WinCertificate>, String> {
+    let mut certificates = Vec::new();
+    let mut offset = table.start;
+    while offset + 8 <= table.end {
+        let length = read_u32(pe, offset)? as usize;
+        if length < 8 || offset + length > table.end {
+            return Err(format!("WIN_CERTIFICATE at offset {} has a bad length {}", offset, length));
+        }
+        // signers pad bCertificate with zeros past the DER
+        let certificate = &pe[offset + 8..offset + length];
+        let der_len = dump::value_len(certificate).unwrap_or(certificate.len()).min(certificate.len());
+        certificates.push(WinCertificate {
+            offset,
+            revision: read_u16(pe, offset + 4)?,
+            certificate_type: read_u16(pe, offset + 6)?,
+            certificate: certificate[..der_len].to_vec(),
+        });
+        offset += length.next_multiple_of(8);
+    }
+    Ok(certificates)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| format!("PE file truncated at offset {}", offset))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| format!("PE file truncated at offset {}", offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_pkcs7;
+    use crate::testdata::{self, ALICE};
+
+    const SIGNED: &[u8] = include_bytes!("../testdata/signed.exe");
+
+    fn image_hash(pe: &[u8]) -> Result<(), String> {
+        let image = PeImage::parse(pe).unwrap();
+        let pkcs7 = parse_pkcs7(&image.certificates[0].certificate).unwrap();
+        check_image_hash(pkcs7.signed_data().unwrap(), &image, pe)
+    }
+
+    #[test]
+    fn certificate_table() {
+        let image = PeImage::parse(SIGNED).unwrap();
+        assert!(image.pe32_plus);
+        assert_eq!(image.certificate_table.start, 0x600);
+        assert_eq!(image.certificate_table.end, SIGNED.len());
+        assert_eq!(image.certificates.len(), 1);
+        assert_eq!(image.certificates[0].type_name(), "WIN_CERT_TYPE_PKCS_SIGNED_DATA");
+        assert_eq!(image.trailing_data(SIGNED), 0);
+        assert_eq!(image_hash(SIGNED), Ok(()));
+
+        let appended = [SIGNED, b"extra payload"].concat();
+        assert_eq!(PeImage::parse(&appended).unwrap().trailing_data(&appended), 13);
+    }
+
+    #[test]
+    fn sequence_econtent_round_trip() {
+        let image = PeImage::parse(SIGNED).unwrap();
+        let der = &image.certificates[0].certificate;
+        let pkcs7 = parse_pkcs7(der).unwrap();
+        assert_eq!(pkcs7.to_der(), *der);
+        let content = pkcs7.signed_data().unwrap().content_info.content.as_deref().unwrap();
+        let indirect = SpcIndirectDataContent::from_content(content).unwrap();
+        assert_eq!(indirect.data_type, oid::SPC_PE_IMAGE_DATA);
+        assert_eq!(indirect.digest_algorithm.algorithm, oid::SHA256);
+    }
+
+    #[test]
+    fn image_hash_excludes_checksum_and_signature() {
+        let image = PeImage::parse(SIGNED).unwrap();
+        let mut pe = SIGNED.to_vec();
+        pe[image.checksum_offset] ^= 1;
+        assert_eq!(image_hash(&pe), Ok(()));
+
+        let mut pe = SIGNED.to_vec();
+        pe[0x300] ^= 1;
+        assert_eq!(image_hash(&pe).unwrap_err(), "the image hash does not match the signed one");
+    }
+
+    #[test]
+    fn code_signing_purpose() {
+        assert_eq!(check_code_signing(&testdata::cert(include_bytes!("../testdata/codesign.crt"))), Ok(()));
+        assert_eq!(check_code_signing(&testdata::cert(ALICE)).unwrap_err(), "the signer certificate is not for code signing");
+    }
+}
@@ -0,0 +1,375 @@
+// CAdES baseline signatures (ETSI EN 319 122-1 section 6): the requirements of the B-B, B-T, B-LT
+// and B-LTA levels and the highest level a signer reaches
+
+use std::fmt;
+
+use bcder::decode::{self, Constructed, DecodeError};
+use bcder::encode::{self, PrimitiveContent};
+use bcder::{Mode, OctetString, Oid, Tag};
+use serde::Serialize;
+
+use crate::certs;
+use crate::crl::CertificateList;
+use crate::crypto::HashAlg;
+use crate::encode::{to_der, RawDer};
+use crate::oid;
+use crate::timestamp::TimeStampToken;
+use crate::verify::{SignerVerification, VerifyOptions};
+use crate::{AlgorithmIdentifier, Certificate, SignedData, SignerInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum BaselineLevel {
+    #[serde(rename = "B-B")]
+    B,
+    #[serde(rename = "B-T")]
+    T,
+    #[serde(rename = "B-LT")]
+    Lt,
+    #[serde(rename = "B-LTA")]
+    Lta,
+}
+
+const LEVELS: [BaselineLevel; 4] = [BaselineLevel::B, BaselineLevel::T, BaselineLevel::Lt, BaselineLevel::Lta];
+
+#[derive(Serialize)]
+pub struct Requirement {
+    pub level: BaselineLevel,
+    pub requirement: &'static str,
+    pub error: Option<String>, // None when met
+}
+
+#[derive(Serialize)]
+pub struct CadesValidation {
+    pub level: Option<BaselineLevel>, // None when not even B-B holds
+    pub requirements: Vec<Requirement>,
+}
+
+// ATSHashIndexV3 (EN 319 122-1 section 5.5.2), one hash per element of the SignedData at time-stamping
+pub struct AtsHashIndex {
+    pub hash_algorithm: Oid, // DEFAULT sha256
+    pub certificates: Vec<Vec<u8>>,
+    pub crls: Vec<Vec<u8>>,
+    pub unsigned_attr_values: Vec<Vec<u8>>,
+}
+
+impl fmt::Display for BaselineLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            BaselineLevel::B => "B-B",
+            BaselineLevel::T => "B-T",
+            BaselineLevel::Lt => "B-LT",
+            BaselineLevel::Lta => "B-LTA",
+        })
+    }
+}
+
+impl CadesValidation {
+    // content is the signed data: the eContent or the detached document; the time-stamps are trusted
+    // against the trust store of the options
+    pub fn new(signed_data: &SignedData, result: &SignerVerification, content: &[u8], options: &VerifyOptions) -> Self {
+        let signer_info = result.signer_info;
+        let mut requirements = Vec::new();
+        let mut require = |level, requirement, check: Result<(), String>| {
+            requirements.push(Requirement { level, requirement, error: check.err() });
+        };
+
+        require(BaselineLevel::B, "valid signature", result.signature.clone());
+        require(BaselineLevel::B, "signing-certificate-v2", check_signing_certificate_v2(signer_info));
+        require(BaselineLevel::B, "signing-time", check_signing_time(signer_info));
+        require(BaselineLevel::T, "signature-time-stamp", check_signature_time_stamp(result));
+        require(BaselineLevel::Lt, "certificate values", check_certificate_values(signed_data, result));
+        require(BaselineLevel::Lt, "revocation values", check_revocation_values(signed_data, result));
+        require(BaselineLevel::Lta, "archive-time-stamp-v3", check_archive_time_stamps(signed_data, result, content, options));
+
+        // a level needs its own requirements and all the lower ones
+        let level = LEVELS.iter()
+            .take_while(|level| requirements.iter().filter(|r| r.level == **level).all(|r| r.error.is_none()))
+            .last()
+            .copied();
+        CadesValidation { level, requirements }
+    }
+}
+
+impl AtsHashIndex {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let mut hash_algorithm = oid::owned(oid::SHA256);
+            let mut lists = Vec::new();
+            while let Some(()) = cons.take_opt_sequence(|cons| {
+                // the optional hashIndAlgorithm is the only SEQUENCE starting with an OID
+                if let Some(algorithm) = Oid::take_opt_from(cons)? {
+                    cons.skip_all()?;
+                    hash_algorithm = algorithm;
+                    return Ok(());
+                }
+                let mut hashes = Vec::new();
+                while let Some(hash) = OctetString::take_opt_from(cons)? {
+                    hashes.push(hash.to_bytes().to_vec());
+                }
+                lists.push(hashes);
+                Ok(())
+            })? {}
+            let [certificates, crls, unsigned_attr_values] = <[Vec<Vec<u8>>; 3]>::try_from(lists).map_err(|_| {
+                DecodeError::content("ATSHashIndexV3 needs three hash lists", decode::Pos::default())
+            })?;
+            Ok(AtsHashIndex { hash_algorithm, certificates, crls, unsigned_attr_values })
+        })
+    }
+
+    // the index of the SignedData as it is now
+    pub fn build(signed_data: &SignedData, signer_info: &SignerInfo, hash: HashAlg) -> Self {
+        let mut unsigned_attr_values = Vec::new();
+        for attribute in signer_info.unauth_attributes.iter().flatten() {
+            let attr_type = to_der(attribute.oid.encode_ref());
+            for value in attribute.values() {
+                unsigned_attr_values.push(hash.digest(&[attr_type.as_slice(), &value].concat()));
+            }
+        }
+        AtsHashIndex {
+            hash_algorithm: oid::owned(hash.oid()),
+            certificates: signed_data.certs.iter().map(|cert| hash.digest(&cert.raw)).collect(),
+            crls: certs::split_crls(&signed_data.crls).iter().map(|crl| hash.digest(crl)).collect(),
+            unsigned_attr_values,
+        }
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        fn hashes(hashes: &[Vec<u8>]) -> impl encode::Values + '_ {
+            encode::sequence(hashes.iter().map(OctetString::encode_slice).collect::<Vec<_>>())
+        }
+        let algorithm = AlgorithmIdentifier { algorithm: self.hash_algorithm.clone(), parameters: None };
+        to_der(encode::sequence((
+            (self.hash_algorithm != oid::SHA256).then(|| algorithm.encode_ref()), // DER leaves the DEFAULT out
+            hashes(&self.certificates),
+            hashes(&self.crls),
+            hashes(&self.unsigned_attr_values),
+        )))
+    }
+}
+
+// what the archive-time-stamp-v3 imprint is computed over (EN 319 122-1 section 5.5.3)
+pub fn archive_time_stamp_data(signed_data: &SignedData, signer_info: &SignerInfo, content: &[u8], hash_index: &[u8], hash: HashAlg) -> Vec<u8> {
+    let mut data = to_der(signed_data.content_info.content_type.encode_ref());
+    data.extend(hash.digest(content));
+    // the SignerInfo fields up to the signature, unsigned attributes left out
+    data.extend(to_der((
+        signer_info.version.encode(),
+        signer_info.signer_identifier.encode_ref(),
+        signer_info.digest_algorithm.encode_ref(),
+        signer_info.auth_attributes.as_ref().map(|_| encode::sequence_as(Tag::CTX_0, RawDer(&signer_info.auth_bytes))),
+        signer_info.signature_algorithm.encode_ref(),
+        OctetString::encode_slice(&signer_info.signature),
+    )));
+    data.extend(hash_index);
+    data
+}
+
+fn check_signing_certificate_v2(signer_info: &SignerInfo) -> Result<(), String> {
+    // the binding itself is part of the signature check
+    match signer_info.signing_certificate_v2() {
+        Some(values) => values.map(|_| ()),
+        None if signer_info.signing_certificate().is_some() => {
+            Err("only the SHA-1 signing-certificate, the baseline needs signing-certificate-v2".to_string())
+        },
+        None => Err("signing-certificate-v2 attribute missing".to_string()),
+    }
+}
+
+fn check_signing_time(signer_info: &SignerInfo) -> Result<(), String> {
+    let times = signer_info.signing_time().ok_or("signing-time attribute missing")??;
+    match times.len() {
+        1 => Ok(()),
+        _ => Err("signing-time attribute with several values".to_string()),
+    }
+}
+
+fn check_signature_time_stamp(result: &SignerVerification) -> Result<(), String> {
+    let check = result.timestamp.as_ref().ok_or("signature-time-stamp attribute missing")?.as_ref()?;
+    check.result.clone()?;
+    // B-T and up rest on the time-stamp, a TSA that is not trusted proves nothing
+    if !check.tsa_trusted {
+        return Err("the TSA certificate chain was not checked".to_string());
+    }
+    // the claimed signing time cannot be after the proof of existence
+    if let Some(Ok(times)) = result.signer_info.signing_time() {
+        if times.iter().any(|time| time.time > check.gen_time) {
+            return Err("signing-time is after the signature-time-stamp".to_string());
+        }
+    }
+    Ok(())
+}
+
+// the certification path without its trust anchor, which the relying party has anyway
+fn path<'a>(result: &'a SignerVerification) -> Result<Vec<&'a Certificate>, String> {
+    if result.chain.is_empty() {
+        return Err("no certification path, the chain was not built".to_string());
+    }
+    if !result.chain.last().is_some_and(|entry| entry.trust_anchor) {
+        return Err("the certification path does not reach a trust anchor".to_string());
+    }
+    Ok(result.chain.iter().filter(|entry| !entry.trust_anchor).map(|entry| entry.cert).collect())
+}
+
+fn check_certificate_values(signed_data: &SignedData, result: &SignerVerification) -> Result<(), String> {
+    let certificate_values = result.signer_info.certificate_values().transpose()?.unwrap_or_default();
+    for cert in path(result)? {
+        let embedded = signed_data.certs.iter().chain(certificate_values.iter().flatten()).any(|c| c.raw == cert.raw);
+        if !embedded {
+            return Err(format!("certificate {} is not in the signature", cert.tbs_certificate.serial_number));
+        }
+    }
+    Ok(())
+}
+
+fn check_revocation_values(signed_data: &SignedData, result: &SignerVerification) -> Result<(), String> {
+    let revocation_values = result.signer_info.revocation_values().transpose()?.unwrap_or_default();
+    let mut crls = Vec::new();
+    for der in certs::split_crls(&signed_data.crls) {
+        if let Ok(crl) = CertificateList::from_der(&der) {
+            crls.push(crl);
+        }
+    }
+    let embedded_crls: Vec<&CertificateList> = crls.iter().chain(revocation_values.iter().flat_map(|v| &v.crls)).collect();
+    path(result)?;
+    // every certificate below the anchor, with its issuer next in the chain
+    for pair in result.chain.windows(2).filter(|pair| !pair[0].trust_anchor) {
+        let (cert, issuer) = (pair[0].cert, pair[1].cert);
+        let has_crl = embedded_crls.iter().any(|crl| crl.issuer == cert.tbs_certificate.issuer);
+        let has_ocsp = revocation_values.iter().flat_map(|v| &v.ocsp_responses).any(|response| response.find(cert, issuer).is_some());
+        if !has_crl && !has_ocsp {
+            return Err(format!("no revocation data for certificate {} in the signature", cert.tbs_certificate.serial_number));
+        }
+    }
+    Ok(())
+}
+
+// every archive-time-stamp-v3: its hash index still resolves, its imprint and TSA signature hold,
+// and it is not older than the signature time-stamp
+fn check_archive_time_stamps(signed_data: &SignedData, result: &SignerVerification, content: &[u8], options: &VerifyOptions) -> Result<(), String> {
+    let signer_info = result.signer_info;
+    let tokens: Vec<Vec<u8>> = signer_info.unauth_attributes.iter().flatten()
+        .filter(|attribute| attribute.oid == oid::ARCHIVE_TIME_STAMP_V3)
+        .flat_map(|attribute| attribute.values())
+        .collect();
+    if tokens.is_empty() {
+        return Err("archive-time-stamp-v3 attribute missing".to_string());
+    }
+    let signature_time = match &result.timestamp {
+        Some(Ok(check)) => Some(check.gen_time),
+        _ => None,
+    };
+
+    for der in tokens {
+        let token = TimeStampToken::from_der(&der).map_err(|e| format!("archive time-stamp: {}", e))?;
+        let hash = HashAlg::from_oid(&token.tst_info.hash_algorithm.algorithm)
+            .ok_or_else(|| format!("unsupported archive time-stamp hash {}", oid::describe(&token.tst_info.hash_algorithm.algorithm)))?;
+        let tsa_signer = token.signed_data.signer_infos.first().ok_or("archive time-stamp without signer")?;
+        let index_values = tsa_signer.unsigned_attribute(&oid::ATS_HASH_INDEX_V3)
+            .ok_or("archive time-stamp without ats-hash-index-v3")?
+            .values();
+        let [index_der] = index_values.as_slice() else {
+            return Err("ats-hash-index-v3 attribute with several values".to_string());
+        };
+        let index = Constructed::decode(index_der.as_slice(), Mode::Der, AtsHashIndex::take_from)
+            .map_err(|e| format!("malformed ats-hash-index-v3: {}", e))?;
+
+        // elements can be added after time-stamping, never removed or changed
+        let index_hash = HashAlg::from_oid(&index.hash_algorithm)
+            .ok_or_else(|| format!("unsupported ats-hash-index-v3 hash {}", oid::describe(&index.hash_algorithm)))?;
+        let now = AtsHashIndex::build(signed_data, signer_info, index_hash);
+        if !index.certificates.iter().all(|hash| now.certificates.contains(hash)) {
+            return Err("a certificate covered by the archive time-stamp is missing".to_string());
+        }
+        if !index.crls.iter().all(|hash| now.crls.contains(hash)) {
+            return Err("a CRL covered by the archive time-stamp is missing".to_string());
+        }
+        if !index.unsigned_attr_values.iter().all(|hash| now.unsigned_attr_values.contains(hash)) {
+            return Err("an unsigned attribute covered by the archive time-stamp is missing or changed".to_string());
+        }
+
+        let data = archive_time_stamp_data(signed_data, signer_info, content, index_der, hash);
+        token.verify(&data).and_then(|()| token.check_trust(options)).map_err(|e| format!("archive time-stamp: {}", e))?;
+        if signature_time.is_some_and(|time| token.tst_info.gen_time < time) {
+            return Err("archive time-stamp is older than the signature time-stamp".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, CADES, MSG};
+    use crate::{parse_pkcs7, verify, Pkcs7Content};
+
+    const CADES_T: &[u8] = include_bytes!("../testdata/cades_t.p7m");
+    const CADES_LT: &[u8] = include_bytes!("../testdata/cades_lt.p7m");
+    const CADES_LTA: &[u8] = include_bytes!("../testdata/cades_lta.p7m");
+
+    fn signed_data(der: &[u8]) -> SignedData {
+        match parse_pkcs7(der).unwrap().content {
+            Pkcs7Content::SignedData(signed_data) => signed_data,
+            _ => unreachable!(),
+        }
+    }
+
+    fn validate(signed_data: &SignedData, options: &VerifyOptions) -> CadesValidation {
+        let results = verify::verify_signed_data(signed_data, options).unwrap();
+        CadesValidation::new(signed_data, &results[0], MSG, options)
+    }
+
+    fn error(validation: &CadesValidation, requirement: &str) -> Option<String> {
+        validation.requirements.iter().find(|r| r.requirement == requirement).unwrap().error.clone()
+    }
+
+    #[test]
+    fn levels() {
+        let options = testdata::options();
+        for (der, level) in [(CADES, BaselineLevel::B), (CADES_T, BaselineLevel::T), (CADES_LT, BaselineLevel::Lt),
+                             (CADES_LTA, BaselineLevel::Lta)] {
+            assert_eq!(validate(&signed_data(der), &options).level, Some(level));
+        }
+        // openssl without -cades has no signing-certificate-v2
+        let validation = validate(&signed_data(testdata::SIGNED), &options);
+        assert_eq!(validation.level, None);
+        assert_eq!(error(&validation, "signing-certificate-v2").unwrap(), "signing-certificate-v2 attribute missing");
+    }
+
+    #[test]
+    fn time_stamps_need_a_trusted_tsa() {
+        let validation = validate(&signed_data(include_bytes!("../testdata/cades_t_untrusted.p7m")), &testdata::options());
+        assert_eq!(validation.level, Some(BaselineLevel::B));
+        assert!(error(&validation, "signature-time-stamp").unwrap().starts_with("TSA certificate: chain does not end at a trust anchor"));
+
+        let options = VerifyOptions { check_chain: false, ..testdata::options() };
+        let validation = validate(&signed_data(CADES_LTA), &options);
+        assert_eq!(validation.level, Some(BaselineLevel::B));
+        assert_eq!(error(&validation, "signature-time-stamp").unwrap(), "the TSA certificate chain was not checked");
+        assert_eq!(error(&validation, "archive-time-stamp-v3").unwrap(), "archive time-stamp: the TSA certificate chain was not checked");
+    }
+
+    #[test]
+    fn archive_time_stamp_covers_the_unsigned_attributes() {
+        let mut signed_data = signed_data(CADES_LTA);
+        let attributes = signed_data.signer_infos[0].unauth_attributes.as_mut().unwrap();
+        attributes.retain(|attribute| attribute.oid != oid::REVOCATION_VALUES);
+        let validation = validate(&signed_data, &testdata::options());
+        assert_eq!(validation.level, Some(BaselineLevel::T));
+        assert_eq!(error(&validation, "archive-time-stamp-v3").unwrap(),
+                   "an unsigned attribute covered by the archive time-stamp is missing or changed");
+    }
+
+    #[test]
+    fn hash_index_round_trip() {
+        let signed_data = signed_data(CADES_LT);
+        for hash in [HashAlg::Sha256, HashAlg::Sha384] {
+            let index = AtsHashIndex::build(&signed_data, &signed_data.signer_infos[0], hash);
+            let der = index.to_der();
+            let decoded = Constructed::decode(der.as_slice(), Mode::Der, AtsHashIndex::take_from).unwrap();
+            assert_eq!(decoded.hash_algorithm, hash.oid());
+            assert_eq!(decoded.certificates, index.certificates);
+            assert_eq!(decoded.unsigned_attr_values.len(), 2);
+            assert_eq!(decoded.to_der(), der);
+        }
+    }
+}
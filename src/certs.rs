@@ -0,0 +1,152 @@
+// certs-only ("degenerate") SignedData: certificate chains shipped as .p7b
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bcder::decode::Constructed;
+use bcder::Mode;
+
+use crate::oid;
+use crate::{name, Certificate, ContentInfo, SignedData};
+
+// what a bundle carries, CRLs as DER CertificateList
+#[derive(Default)]
+pub struct CertBundle {
+    pub certs: Vec<Certificate>,
+    pub crls: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertFormat {
+    Pem,
+    Der,
+}
+
+// a SignedData without content and signers, version 1 (RFC 5652 section 5.2)
+pub fn certs_only(bundle: CertBundle) -> SignedData {
+    SignedData {
+        version: 1,
+        digest_algorithms: Vec::new(),
+        content_info: ContentInfo {
+            content_type: oid::owned(oid::DATA),
+            content: None,
+            content_raw: None,
+        },
+        certs: bundle.certs,
+        crls: bundle.crls.concat(),
+        signer_infos: Vec::new(),
+    }
+}
+
+// DER ContentInfo of a certs-only bundle
+pub fn encode_bundle(bundle: CertBundle) -> Vec<u8> {
+    certs_only(bundle).to_content_info_der()
+}
+
+// <subject CN>_<serial>.pem, characters unsafe in file names replaced
+pub fn file_name(cert: &Certificate, format: CertFormat) -> String {
+    let tbs = &cert.tbs_certificate;
+    let common_name = name::common_name(&tbs.subject).unwrap_or_else(|| "cert".to_string());
+    let stem: String = format!("{}_{}", common_name, tbs.serial_number)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    match format {
+        CertFormat::Pem => format!("{}.pem", stem),
+        CertFormat::Der => format!("{}.der", stem),
+    }
+}
+
+pub fn encode_certificate(cert: &Certificate, format: CertFormat) -> Vec<u8> {
+    let der = cert.raw.to_vec();
+    match format {
+        CertFormat::Pem => pem::encode(&pem::Pem::new("CERTIFICATE", der)).into_bytes(),
+        CertFormat::Der => der,
+    }
+}
+
+// writes every certificate of the bundle into dir, returns the paths written
+pub fn extract(signed_data: &SignedData, dir: &Path, format: CertFormat) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    for cert in &signed_data.certs {
+        let path = dir.join(file_name(cert, format));
+        fs::write(&path, encode_certificate(cert, format))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+impl CertBundle {
+    // certificates and CRLs from a PEM bundle, a DER file or a p7b, added to what is there
+    pub fn load(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let data = fs::read(path)?;
+
+        if crate::keys::is_pem(&data) {
+            for block in pem::parse_many(&data)? {
+                match block.tag() {
+                    "CERTIFICATE" => self.certs.push(Certificate::from_der(block.contents())?),
+                    "TRUSTED CERTIFICATE" => self.certs.push(crate::trust::trusted_certificate(block.contents())?.0),
+                    "X509 CRL" => self.crls.push(block.contents().to_vec()),
+                    "PKCS7" | "CMS" => self.add_pkcs7(block.contents())?,
+                    _ => {},
+                }
+            }
+        } else if path.ends_with(".crl") {
+            self.crls.push(data);
+        } else if let Ok(cert) = Certificate::from_der(&data) {
+            self.certs.push(cert);
+        } else {
+            self.add_pkcs7(&data).map_err(|_| format!("{}: not a certificate, CRL or p7b", path))?;
+        }
+        Ok(())
+    }
+
+    fn add_pkcs7(&mut self, der: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let pkcs7 = crate::parse_pkcs7(der)?;
+        let mut signed_data = match pkcs7.content {
+            crate::Pkcs7Content::SignedData(signed_data) => signed_data,
+            _ => return Err("not a SignedData".into()),
+        };
+        self.certs.append(&mut signed_data.certs);
+        self.crls.extend(split_crls(&signed_data.crls));
+        Ok(())
+    }
+}
+
+// RevocationInfoChoices content back into single DER CRLs
+pub fn split_crls(crls: &[u8]) -> Vec<Vec<u8>> {
+    let mut split = Vec::new();
+    let _ = Constructed::decode(crls, Mode::Ber, |cons| {
+        while let Ok(crl) = cons.capture_one() {
+            split.push(crl.as_slice().to_vec());
+        }
+        Ok(())
+    });
+    split
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_pkcs7;
+    use crate::testdata::{self, ALICE, INT, ROOT};
+
+    #[test]
+    fn bundle_round_trip() {
+        let bundle = CertBundle { certs: vec![testdata::cert(ALICE), testdata::cert(INT), testdata::cert(ROOT)], crls: Vec::new() };
+        let pkcs7 = parse_pkcs7(&encode_bundle(bundle)).unwrap();
+        let signed_data = pkcs7.signed_data().unwrap();
+        assert!(signed_data.signer_infos.is_empty());
+        let certs: Vec<Vec<u8>> = signed_data.certs.iter().map(|cert| cert.raw.to_vec()).collect();
+        assert_eq!(certs, [testdata::der(ALICE), testdata::der(INT), testdata::der(ROOT)]);
+    }
+
+    #[test]
+    fn extracted_certificate_is_the_received_one() {
+        let alice = testdata::cert(ALICE);
+        assert_eq!(encode_certificate(&alice, CertFormat::Der), testdata::der(ALICE));
+        assert_eq!(testdata::der(&encode_certificate(&alice, CertFormat::Pem)), testdata::der(ALICE));
+        assert_eq!(file_name(&alice, CertFormat::Pem), format!("Alice_{}.pem", alice.tbs_certificate.serial_number));
+    }
+}
@@ -0,0 +1,163 @@
+// X.509 CRLs (RFC 5280 section 5): revoked serials and the issuer's signature
+
+use bcder::decode::{self, Constructed, DecodeError};
+use bcder::{BitString, Mode, Oid, Tag};
+use bytes::Bytes;
+
+use crate::signature::{self, SignatureError};
+use crate::{AlgorithmIdentifier, Certificate, Extension, Validity};
+
+#[derive(Debug)]
+pub struct CertificateList {
+    pub version: Option<u8>, // v2 when present
+    pub signature_algorithm: AlgorithmIdentifier,
+    pub issuer: Bytes, // Name, content of the SEQUENCE
+    pub this_update: i64,
+    pub next_update: Option<i64>,
+    pub revoked_certificates: Vec<RevokedCertificate>,
+    pub extensions: Option<Vec<Extension>>,
+    pub tbs_bytes: Bytes, // exact DER the issuer signed
+    pub outer_algorithm: AlgorithmIdentifier,
+    pub signature_value: Vec<u8>,
+    pub raw: Bytes,
+}
+
+#[derive(Debug)]
+pub struct RevokedCertificate {
+    pub serial_number: String, // hex, as in TbsCertificate
+    pub revocation_date: i64,
+    pub extensions: Option<Vec<Extension>>, // reasonCode, invalidityDate, ...
+}
+
+impl CertificateList {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let mut crl = None;
+        let raw = cons.capture(|cons| {
+            crl = Some(cons.take_sequence(|cons| {
+                let mut tbs = None;
+                let tbs_bytes = cons.capture(|cons| {
+                    tbs = Some(CertificateList::take_tbs_from(cons)?);
+                    Ok(())
+                })?.into_bytes();
+                let outer_algorithm = AlgorithmIdentifier::take_from(cons)?;
+                let signature_value = BitString::take_from(cons)?.octet_bytes().to_vec();
+                Ok((tbs, tbs_bytes, outer_algorithm, signature_value))
+            })?);
+            Ok(())
+        })?.into_bytes();
+
+        let (tbs, tbs_bytes, outer_algorithm, signature_value) = crl.ok_or_else(|| {
+            DecodeError::content("missing CertificateList", decode::Pos::default())
+        })?;
+        let mut crl = tbs.ok_or_else(|| {
+            DecodeError::content("missing tbsCertList", decode::Pos::default())
+        })?;
+        crl.tbs_bytes = tbs_bytes;
+        crl.outer_algorithm = outer_algorithm;
+        crl.signature_value = signature_value;
+        crl.raw = raw;
+        Ok(crl)
+    }
+
+    fn take_tbs_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let version = cons.take_opt_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+            let signature_algorithm = AlgorithmIdentifier::take_from(cons)?;
+            let issuer = cons.take_sequence(|cons| Ok(cons.capture_all()?.into_bytes()))?;
+            let (this_update, _) = Validity::take_time_from(cons)?;
+
+            // nextUpdate is optional but not tagged, it's there when a time follows
+            let next_update = CertificateList::take_opt_time_from(cons)?;
+
+            let revoked_certificates = cons.take_opt_sequence(|cons| {
+                let mut revoked = Vec::new();
+                while let Some(entry) = cons.take_opt_sequence(|cons| {
+                    let serial_number = cons.take_primitive_if(Tag::INTEGER, |content| {
+                        Ok(hex::encode(content.take_all()?))
+                    })?;
+                    let (revocation_date, _) = Validity::take_time_from(cons)?;
+                    let extensions = cons.take_opt_sequence(take_extensions_from)?;
+                    Ok(RevokedCertificate {
+                        serial_number,
+                        revocation_date,
+                        extensions,
+                    })
+                })? {
+                    revoked.push(entry);
+                }
+                Ok(revoked)
+            })?.unwrap_or_default();
+
+            let extensions = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                cons.take_sequence(take_extensions_from)
+            })?;
+
+            Ok(CertificateList {
+                version,
+                signature_algorithm,
+                issuer,
+                this_update,
+                next_update,
+                revoked_certificates,
+                extensions,
+                tbs_bytes: Bytes::new(),
+                outer_algorithm: AlgorithmIdentifier {
+                    algorithm: Oid(Bytes::new()),
+                    parameters: None,
+                },
+                signature_value: Vec::new(),
+                raw: Bytes::new(),
+            })
+        })
+    }
+
+    fn take_opt_time_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Option<i64>, DecodeError<S::Error>> {
+        let time = cons.take_opt_value_if(Tag::UTC_TIME, |content| {
+            Ok(String::from_utf8_lossy(&content.as_primitive()?.take_all()?).into_owned())
+        })?.map(|time| (time, Tag::UTC_TIME));
+        let time = match time {
+            Some(time) => Some(time),
+            None => cons.take_opt_value_if(Tag::GENERALIZED_TIME, |content| {
+                Ok(String::from_utf8_lossy(&content.as_primitive()?.take_all()?).into_owned())
+            })?.map(|time| (time, Tag::GENERALIZED_TIME)),
+        };
+        match time {
+            Some((time, tag)) => Validity::parse_asn1_to_timestamp(&time, tag).map(Some).map_err(|_| {
+                DecodeError::content(format!("Failed to parse time {}", time), decode::Pos::default())
+            }),
+            None => Ok(None),
+        }
+    }
+
+    pub fn from_der(der: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let crl = Constructed::decode(Bytes::copy_from_slice(der), Mode::Ber, |cons| {
+            CertificateList::take_from(cons)
+        }).map_err(|err| format!("Error decoding CRL: {}", err))?;
+        Ok(crl)
+    }
+
+    pub fn verify_signed_by(&self, issuer: &Certificate) -> Result<(), SignatureError> {
+        if self.signature_algorithm.algorithm != self.outer_algorithm.algorithm {
+            return Err(SignatureError::Malformed("signature algorithm differs from the TBS one".into()));
+        }
+        signature::verify_signature(
+            &issuer.tbs_certificate.subject_public_key_info,
+            &self.outer_algorithm,
+            None,
+            &self.tbs_bytes,
+            &self.signature_value,
+        )
+    }
+
+    pub fn find(&self, serial_number: &str) -> Option<&RevokedCertificate> {
+        self.revoked_certificates.iter().find(|r| r.serial_number == serial_number)
+    }
+}
+
+fn take_extensions_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Vec<Extension>, DecodeError<S::Error>> {
+    let mut extensions = Vec::new();
+    while let Ok(extension) = Extension::take_from(cons) {
+        extensions.push(extension);
+    }
+    Ok(extensions)
+}
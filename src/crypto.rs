@@ -0,0 +1,127 @@
+// hash helpers shared by decryption, signing and verification
+
+use bcder::{ConstOid, Oid};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+
+use crate::oid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlg {
+    Sha1,
+    Sha224,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlg {
+    pub fn from_oid<T: AsRef<[u8]>>(alg: &Oid<T>) -> Option<Self> {
+        if *alg == oid::SHA1 { Some(HashAlg::Sha1) }
+        else if *alg == oid::SHA224 { Some(HashAlg::Sha224) }
+        else if *alg == oid::SHA256 { Some(HashAlg::Sha256) }
+        else if *alg == oid::SHA384 { Some(HashAlg::Sha384) }
+        else if *alg == oid::SHA512 { Some(HashAlg::Sha512) }
+        else { None }
+    }
+
+    // prf of PBKDF2 and friends
+    pub fn from_hmac_oid<T: AsRef<[u8]>>(alg: &Oid<T>) -> Option<Self> {
+        if *alg == oid::HMAC_WITH_SHA1 { Some(HashAlg::Sha1) }
+        else if *alg == oid::HMAC_WITH_SHA224 { Some(HashAlg::Sha224) }
+        else if *alg == oid::HMAC_WITH_SHA256 { Some(HashAlg::Sha256) }
+        else if *alg == oid::HMAC_WITH_SHA384 { Some(HashAlg::Sha384) }
+        else if *alg == oid::HMAC_WITH_SHA512 { Some(HashAlg::Sha512) }
+        else { None }
+    }
+
+    pub fn oid(&self) -> ConstOid {
+        match self {
+            HashAlg::Sha1 => oid::SHA1,
+            HashAlg::Sha224 => oid::SHA224,
+            HashAlg::Sha256 => oid::SHA256,
+            HashAlg::Sha384 => oid::SHA384,
+            HashAlg::Sha512 => oid::SHA512,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('-', "").as_str() {
+            "sha1" => Some(HashAlg::Sha1),
+            "sha224" => Some(HashAlg::Sha224),
+            "sha256" => Some(HashAlg::Sha256),
+            "sha384" => Some(HashAlg::Sha384),
+            "sha512" => Some(HashAlg::Sha512),
+            _ => None,
+        }
+    }
+
+    pub fn output_len(&self) -> usize {
+        match self {
+            HashAlg::Sha1 => 20,
+            HashAlg::Sha224 => 28,
+            HashAlg::Sha256 => 32,
+            HashAlg::Sha384 => 48,
+            HashAlg::Sha512 => 64,
+        }
+    }
+
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlg::Sha1 => Sha1::digest(data).to_vec(),
+            HashAlg::Sha224 => Sha224::digest(data).to_vec(),
+            HashAlg::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlg::Sha384 => Sha384::digest(data).to_vec(),
+            HashAlg::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    // input block size, the v of the PKCS#12 key derivation
+    pub fn block_len(&self) -> usize {
+        match self {
+            HashAlg::Sha1 | HashAlg::Sha224 | HashAlg::Sha256 => 64,
+            HashAlg::Sha384 | HashAlg::Sha512 => 128,
+        }
+    }
+
+    pub fn hmac(&self, key: &[u8], data: &[u8]) -> Vec<u8> {
+        fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], data: &[u8]) -> Vec<u8> {
+            let mut mac = <M as hmac::digest::KeyInit>::new_from_slice(key).expect("HMAC takes any key length");
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+        match self {
+            HashAlg::Sha1 => mac::<Hmac<Sha1>>(key, data),
+            HashAlg::Sha224 => mac::<Hmac<Sha224>>(key, data),
+            HashAlg::Sha256 => mac::<Hmac<Sha256>>(key, data),
+            HashAlg::Sha384 => mac::<Hmac<Sha384>>(key, data),
+            HashAlg::Sha512 => mac::<Hmac<Sha512>>(key, data),
+        }
+    }
+
+    pub fn pbkdf2(&self, password: &[u8], salt: &[u8], rounds: u32, out: &mut [u8]) {
+        match self {
+            HashAlg::Sha1 => pbkdf2::pbkdf2_hmac::<Sha1>(password, salt, rounds, out),
+            HashAlg::Sha224 => pbkdf2::pbkdf2_hmac::<Sha224>(password, salt, rounds, out),
+            HashAlg::Sha256 => pbkdf2::pbkdf2_hmac::<Sha256>(password, salt, rounds, out),
+            HashAlg::Sha384 => pbkdf2::pbkdf2_hmac::<Sha384>(password, salt, rounds, out),
+            HashAlg::Sha512 => pbkdf2::pbkdf2_hmac::<Sha512>(password, salt, rounds, out),
+        }
+    }
+}
+
+// ANSI X9.63 key derivation, as used by ECDH key agreement in CMS (RFC 5753)
+pub fn x963_kdf(hash: HashAlg, secret: &[u8], shared_info: &[u8], key_len: usize) -> Vec<u8> {
+    let mut key = Vec::with_capacity(key_len + hash.output_len());
+    let mut counter: u32 = 1;
+    while key.len() < key_len {
+        let mut input = secret.to_vec();
+        input.extend_from_slice(&counter.to_be_bytes());
+        input.extend_from_slice(shared_info);
+        key.extend(hash.digest(&input));
+        counter += 1;
+    }
+    key.truncate(key_len);
+    key
+}
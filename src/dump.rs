@@ -0,0 +1,188 @@
+// ASN.1 structure dump, one line per value like `openssl asn1parse`
+
+use bcder::Oid;
+use bytes::Bytes;
+
+use crate::oid;
+
+const MAX_HEX: usize = 32; // longer primitive values are cut
+
+struct Header {
+    class: u8, // the two top bits of the identifier
+    constructed: bool,
+    number: u32,
+    header_len: usize,
+    content_len: Option<usize>, // None for BER indefinite length
+}
+
+pub fn dump(data: &[u8]) -> Result<String, String> {
+    let mut out = String::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        offset += dump_value(data, offset, 0, &mut out)?;
+    }
+    Ok(out)
+}
+
+// the encoded length of the first value, what follows it (padding) is not counted
+pub fn value_len(data: &[u8]) -> Result<usize, String> {
+    dump_value(data, 0, 0, &mut String::new())
+}
+
+// writes the value at offset and everything inside it, returns its encoded length
+fn dump_value(data: &[u8], offset: usize, depth: usize, out: &mut String) -> Result<usize, String> {
+    let header = parse_header(&data[offset..]).ok_or_else(|| format!("bad header at offset {}", offset))?;
+    let start = offset + header.header_len;
+    let length = header.content_len.map_or("inf".to_string(), |len| len.to_string());
+
+    out.push_str(&format!(
+        "{:>5}:d={:<2} hl={} l={:>4} {}: {}{}",
+        offset,
+        depth,
+        header.header_len,
+        length,
+        if header.constructed { "cons" } else { "prim" },
+        " ".repeat(depth),
+        tag_name(&header),
+    ));
+
+    if header.constructed {
+        out.push('\n');
+        let mut pos = start;
+        match header.content_len {
+            Some(len) => {
+                let end = start.checked_add(len).filter(|end| *end <= data.len())
+                    .ok_or_else(|| format!("length past the end at offset {}", offset))?;
+                while pos < end {
+                    pos += dump_value(&data[..end], pos, depth + 1, out)?;
+                }
+                Ok(end - offset)
+            },
+            None => {
+                // until the end-of-contents octets
+                while data.get(pos..pos + 2) != Some(&[0, 0]) {
+                    if pos >= data.len() {
+                        return Err(format!("missing end-of-contents for offset {}", offset));
+                    }
+                    pos += dump_value(data, pos, depth + 1, out)?;
+                }
+                Ok(pos + 2 - offset)
+            },
+        }
+    } else {
+        let len = header.content_len.ok_or_else(|| format!("indefinite primitive at offset {}", offset))?;
+        let content = data.get(start..start + len).ok_or_else(|| format!("length past the end at offset {}", offset))?;
+        let value = describe_primitive(&header, content);
+        if !value.is_empty() {
+            out.push_str(&format!(" :{}", value));
+        }
+        out.push('\n');
+        Ok(header.header_len + len)
+    }
+}
+
+fn parse_header(data: &[u8]) -> Option<Header> {
+    let first = *data.first()?;
+    let mut pos = 1;
+    let mut number = u32::from(first & 0x1f);
+    if number == 0x1f {
+        // high tag number form, base 128
+        number = 0;
+        loop {
+            let byte = *data.get(pos)?;
+            pos += 1;
+            number = number.checked_mul(128)? | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+
+    let len_byte = *data.get(pos)?;
+    pos += 1;
+    let content_len = if len_byte == 0x80 {
+        None
+    } else if len_byte & 0x80 == 0 {
+        Some(usize::from(len_byte))
+    } else {
+        let count = usize::from(len_byte & 0x7f);
+        if count > std::mem::size_of::<usize>() {
+            return None;
+        }
+        let mut len = 0usize;
+        for byte in data.get(pos..pos + count)? {
+            len = (len << 8) | usize::from(*byte);
+        }
+        pos += count;
+        Some(len)
+    };
+
+    Some(Header {
+        class: first >> 6,
+        constructed: first & 0x20 != 0,
+        number,
+        header_len: pos,
+        content_len,
+    })
+}
+
+fn tag_name(header: &Header) -> String {
+    match header.class {
+        1 => return format!("appl [ {} ]", header.number),
+        2 => return format!("cont [ {} ]", header.number),
+        3 => return format!("priv [ {} ]", header.number),
+        _ => {},
+    }
+    let name = match header.number {
+        0 => "EOC",
+        1 => "BOOLEAN",
+        2 => "INTEGER",
+        3 => "BIT STRING",
+        4 => "OCTET STRING",
+        5 => "NULL",
+        6 => "OBJECT",
+        10 => "ENUMERATED",
+        12 => "UTF8STRING",
+        16 => "SEQUENCE",
+        17 => "SET",
+        18 => "NUMERICSTRING",
+        19 => "PRINTABLESTRING",
+        20 => "T61STRING",
+        22 => "IA5STRING",
+        23 => "UTCTIME",
+        24 => "GENERALIZEDTIME",
+        26 => "VISIBLESTRING",
+        28 => "UNIVERSALSTRING",
+        30 => "BMPSTRING",
+        number => return format!("univ [ {} ]", number),
+    };
+    name.to_string()
+}
+
+fn describe_primitive(header: &Header, content: &[u8]) -> String {
+    if header.class != 0 {
+        return hex_value(content);
+    }
+    match header.number {
+        1 => (if content.first().is_some_and(|b| *b != 0) { "TRUE" } else { "FALSE" }).to_string(),
+        2 | 10 if content.len() <= 8 => {
+            let mut value: i64 = if content.first().is_some_and(|b| b & 0x80 != 0) { -1 } else { 0 };
+            for byte in content {
+                value = (value << 8) | i64::from(*byte);
+            }
+            value.to_string()
+        },
+        6 => oid::describe(&Oid(Bytes::copy_from_slice(content))),
+        12 | 18 | 19 | 20 | 22 | 23 | 24 | 26 => String::from_utf8_lossy(content).into_owned(),
+        5 => String::new(),
+        _ => hex_value(content),
+    }
+}
+
+fn hex_value(content: &[u8]) -> String {
+    if content.len() > MAX_HEX {
+        format!("{}... ({} bytes)", hex::encode_upper(&content[..MAX_HEX]), content.len())
+    } else {
+        hex::encode_upper(content)
+    }
+}
@@ -0,0 +1,413 @@
+// DER encoding of the parsed model (bcder encode::Values)
+//
+// every structure parsed from DER encodes back to the same bytes: fields the
+// model does not interpret are kept as raw DER and written out unchanged
+
+use std::io;
+
+use bcder::encode::{self, Choice2, Choice3, PrimitiveContent, Values};
+use bcder::{BitString, Captured, Mode, OctetString, Tag};
+
+use crate::attributes::CmsAlgorithmProtection;
+use crate::enveloped::{
+    EncryptedContentInfo, EnvelopedData, KekRecipientInfo, KeyAgreeRecipientInfo, KeyTransRecipientInfo,
+    OriginatorIdentifierOrKey, PasswordRecipientInfo, RecipientEncryptedKey, RecipientIdentifier, RecipientInfo,
+};
+use crate::oid;
+use crate::{
+    AlgorithmIdentifier, Attribute, Certificate, ContentInfo, Extension, Pkcs7, Pkcs7Content, SignedData,
+    SignerIdentifier, SignerInfo, SubjectPublicKeyInfo, TbsCertificate, Validity,
+};
+
+// already encoded bytes written as they are
+pub struct RawDer<'a>(pub &'a [u8]);
+
+impl Values for RawDer<'_> {
+    fn encoded_len(&self, _: Mode) -> usize {
+        self.0.len()
+    }
+
+    fn write_encoded<W: io::Write>(&self, _: Mode, target: &mut W) -> Result<(), io::Error> {
+        target.write_all(self.0)
+    }
+}
+
+pub fn to_der<V: Values>(values: V) -> Vec<u8> {
+    values.to_captured(Mode::Der).as_slice().to_vec()
+}
+
+impl Pkcs7 {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        let content = match &self.content {
+            Pkcs7Content::SignedData(signed_data) => Choice2::One(signed_data.encode_ref()),
+            Pkcs7Content::EnvelopedData(enveloped) | Pkcs7Content::AuthEnvelopedData(enveloped) => {
+                Choice2::Two(enveloped.encode_ref())
+            },
+        };
+        encode::sequence((
+            self.content_type.encode_ref(),
+            encode::sequence_as(Tag::CTX_0, content),
+        ))
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        to_der(self.encode_ref())
+    }
+}
+
+impl SignedData {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.version.encode(),
+            encode::set(self.digest_algorithms.iter().map(|a| a.encode_ref()).collect::<Vec<_>>()),
+            self.content_info.encode_ref(),
+            (!self.certs.is_empty()).then(|| {
+                encode::sequence_as(Tag::CTX_0, self.certs.iter().map(|c| c.encode_ref()).collect::<Vec<_>>())
+            }),
+            (!self.crls.is_empty()).then(|| encode::sequence_as(Tag::CTX_1, RawDer(&self.crls))),
+            encode::set(self.signer_infos.iter().map(|s| s.encode_ref()).collect::<Vec<_>>()),
+        ))
+    }
+
+    // wrapped in the outer ContentInfo, what .p7b/.p7m files hold
+    pub fn to_content_info_der(&self) -> Vec<u8> {
+        to_der(encode::sequence((
+            oid::SIGNED_DATA.encode(),
+            encode::sequence_as(Tag::CTX_0, self.encode_ref()),
+        )))
+    }
+}
+
+impl ContentInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.content_type.encode_ref(),
+            self.content.as_ref().map(|c| encode::sequence_as(Tag::CTX_0, match &self.content_raw {
+                Some(raw) => Choice2::One(RawDer(raw)),
+                None => Choice2::Two(OctetString::encode_slice(c)),
+            })),
+        ))
+    }
+}
+
+impl Certificate {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.tbs_certificate.encode_ref(),
+            self.signature_algorithm.encode_ref(),
+            BitString::encode_slice(&self.signature_value, 0),
+        ))
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        to_der(self.encode_ref())
+    }
+}
+
+impl TbsCertificate {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        // serial is kept as hex of the INTEGER content
+        let serial = hex::decode(&self.serial_number).unwrap_or_default();
+        encode::sequence((
+            self.version.map(|v| encode::sequence_as(Tag::CTX_0, v.encode())),
+            OctetString::encode_slice_as(serial, Tag::INTEGER),
+            self.signature_algorithm.encode_ref(),
+            encode::sequence(RawDer(&self.issuer)),
+            self.validity.encode_ref(),
+            encode::sequence(RawDer(&self.subject)),
+            self.subject_public_key_info.encode_ref(),
+            self.issuer_unique_id.as_ref().map(|id| OctetString::encode_slice_as(id, Tag::CTX_1)),
+            self.subject_unique_id.as_ref().map(|id| OctetString::encode_slice_as(id, Tag::CTX_2)),
+            self.extensions.as_ref().map(|extensions| {
+                encode::sequence_as(Tag::CTX_3, encode::sequence(
+                    extensions.iter().map(|e| e.encode_ref()).collect::<Vec<_>>(),
+                ))
+            }),
+        ))
+    }
+}
+
+impl Extension {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.extn_id.encode_ref(),
+            self.critical.then(|| true.encode()), // DER leaves out the DEFAULT
+            OctetString::encode_slice(&self.extn_value),
+        ))
+    }
+}
+
+impl Validity {
+    pub fn encode_ref(&self) -> impl Values {
+        let not_before = Validity::format_asn1_time(self.not_before, self.not_before_tag);
+        let not_after = Validity::format_asn1_time(self.not_after, self.not_after_tag);
+        encode::sequence((
+            OctetString::encode_slice_as(not_before.into_bytes(), self.not_before_tag),
+            OctetString::encode_slice_as(not_after.into_bytes(), self.not_after_tag),
+        ))
+    }
+}
+
+impl SubjectPublicKeyInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.algorithm.encode_ref(),
+            BitString::encode_slice(&self.subject_public_key, 0),
+        ))
+    }
+}
+
+impl AlgorithmIdentifier {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence(self.encode_content())
+    }
+
+    // for the places where the SEQUENCE tag is replaced by an IMPLICIT one
+    pub fn encode_content(&self) -> impl Values + '_ {
+        (
+            self.algorithm.encode_ref(),
+            self.parameters.as_deref().map(RawDer),
+        )
+    }
+}
+
+impl Attribute {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.oid.encode_ref(),
+            encode::set(RawDer(&self.value)),
+        ))
+    }
+}
+
+impl CmsAlgorithmProtection {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.digest_algorithm.encode_ref(),
+            self.signature_algorithm.as_ref().map(|alg| encode::sequence_as(Tag::CTX_1, alg.encode_content())),
+            self.mac_algorithm.as_ref().map(|alg| encode::sequence_as(Tag::CTX_2, alg.encode_content())),
+        ))
+    }
+}
+
+impl SignerIdentifier {
+    // IssuerAndSerialNumber, both fields are kept as content bytes
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            encode::sequence(RawDer(&self.issuer)),
+            OctetString::encode_slice_as(&self.serial_number, Tag::INTEGER),
+        ))
+    }
+}
+
+impl SignerInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.version.encode(),
+            self.signer_identifier.encode_ref(),
+            self.digest_algorithm.encode_ref(),
+            self.auth_attributes.as_ref().map(|_| encode::sequence_as(Tag::CTX_0, RawDer(&self.auth_bytes))),
+            self.signature_algorithm.encode_ref(),
+            OctetString::encode_slice(&self.signature),
+            self.unauth_attributes.as_ref().map(|attributes| {
+                encode::sequence_as(Tag::CTX_1, attributes.iter().map(|a| a.encode_ref()).collect::<Vec<_>>())
+            }),
+        ))
+    }
+
+    // what the signature is computed over: the signed attributes as a SET OF
+    pub fn signed_attrs_der(&self) -> Vec<u8> {
+        to_der(encode::set(RawDer(&self.auth_bytes)))
+    }
+}
+
+impl EnvelopedData {
+    // AuthEnvelopedData when there is a MAC
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        let authenticated = self.is_authenticated();
+        let auth_attributes = self.auth_attributes.as_ref().map(|_| {
+            encode::sequence_as(Tag::CTX_1, RawDer(&self.auth_bytes))
+        });
+        let unprotected_tag = if authenticated { Tag::CTX_2 } else { Tag::CTX_1 };
+        encode::sequence((
+            self.version.encode(),
+            self.originator_info.as_deref().map(|info| encode::sequence_as(Tag::CTX_0, RawDer(info))),
+            encode::set(self.recipient_infos.iter().map(|r| r.encode_ref()).collect::<Vec<_>>()),
+            self.encrypted_content_info.encode_ref(),
+            auth_attributes,
+            self.mac.as_ref().map(OctetString::encode_slice),
+            self.unprotected_attributes.as_ref().map(|attributes| {
+                encode::sequence_as(unprotected_tag, attributes.iter().map(|a| a.encode_ref()).collect::<Vec<_>>())
+            }),
+        ))
+    }
+
+    pub fn to_der(&self) -> Vec<u8> {
+        to_der(self.encode_ref())
+    }
+
+    // additional data of AES-GCM: the authenticated attributes as a SET OF
+    pub fn auth_attrs_der(&self) -> Vec<u8> {
+        to_der(encode::set(RawDer(&self.auth_bytes)))
+    }
+}
+
+impl EncryptedContentInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.content_type.encode_ref(),
+            self.content_encryption_algorithm.encode_ref(),
+            self.encrypted_content.as_ref().map(|c| OctetString::encode_slice_as(c, Tag::CTX_0)),
+        ))
+    }
+}
+
+impl RecipientInfo {
+    // the alternatives are distinct types, so they are encoded up front
+    pub fn encode_ref(&self) -> Captured {
+        match self {
+            RecipientInfo::KeyTrans(ktri) => ktri.encode_ref().to_captured(Mode::Der),
+            RecipientInfo::KeyAgree(kari) => kari.encode_ref().to_captured(Mode::Der),
+            RecipientInfo::Kek(kekri) => kekri.encode_ref().to_captured(Mode::Der),
+            RecipientInfo::Password(pwri) => pwri.encode_ref().to_captured(Mode::Der),
+            RecipientInfo::Other { ori_type, ori_value } => encode::sequence_as(Tag::CTX_4, (
+                ori_type.encode_ref(),
+                RawDer(ori_value),
+            )).to_captured(Mode::Der),
+        }
+    }
+}
+
+impl RecipientIdentifier {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        match self {
+            RecipientIdentifier::IssuerAndSerial(sid) => Choice2::One(sid.encode_ref()),
+            RecipientIdentifier::SubjectKeyId(ski) => Choice2::Two(OctetString::encode_slice_as(ski, Tag::CTX_0)),
+        }
+    }
+}
+
+impl KeyTransRecipientInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence((
+            self.version.encode(),
+            self.rid.encode_ref(),
+            self.key_encryption_algorithm.encode_ref(),
+            OctetString::encode_slice(&self.encrypted_key),
+        ))
+    }
+}
+
+impl OriginatorIdentifierOrKey {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        match self {
+            OriginatorIdentifierOrKey::IssuerAndSerial(sid) => Choice3::One(sid.encode_ref()),
+            OriginatorIdentifierOrKey::SubjectKeyId(ski) => Choice3::Two(OctetString::encode_slice_as(ski, Tag::CTX_0)),
+            OriginatorIdentifierOrKey::OriginatorKey { algorithm, public_key } => Choice3::Three(
+                encode::sequence_as(Tag::CTX_1, (
+                    algorithm.encode_ref(),
+                    BitString::encode_slice(public_key, 0),
+                )),
+            ),
+        }
+    }
+}
+
+impl KeyAgreeRecipientInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence_as(Tag::CTX_1, (
+            self.version.encode(),
+            encode::sequence_as(Tag::CTX_0, self.originator.encode_ref()),
+            self.ukm.as_ref().map(|ukm| encode::sequence_as(Tag::CTX_1, OctetString::encode_slice(ukm))),
+            self.key_encryption_algorithm.encode_ref(),
+            encode::sequence(self.recipient_encrypted_keys.iter().map(|k| k.encode_ref()).collect::<Vec<_>>()),
+        ))
+    }
+}
+
+impl RecipientEncryptedKey {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        let rid = match &self.rid {
+            RecipientIdentifier::IssuerAndSerial(sid) => Choice2::One(sid.encode_ref()),
+            // rKeyId [0] IMPLICIT RecipientKeyIdentifier
+            RecipientIdentifier::SubjectKeyId(ski) => Choice2::Two(encode::sequence_as(Tag::CTX_0, (
+                OctetString::encode_slice(ski),
+                RawDer(&self.r_key_id_extra),
+            ))),
+        };
+        encode::sequence((
+            rid,
+            OctetString::encode_slice(&self.encrypted_key),
+        ))
+    }
+}
+
+impl KekRecipientInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence_as(Tag::CTX_2, (
+            self.version.encode(),
+            encode::sequence((
+                OctetString::encode_slice(&self.key_identifier),
+                RawDer(&self.kekid_extra),
+            )),
+            self.key_encryption_algorithm.encode_ref(),
+            OctetString::encode_slice(&self.encrypted_key),
+        ))
+    }
+}
+
+impl PasswordRecipientInfo {
+    pub fn encode_ref(&self) -> impl Values + '_ {
+        encode::sequence_as(Tag::CTX_3, (
+            self.version.encode(),
+            self.key_derivation_algorithm.as_ref().map(|alg| encode::sequence_as(Tag::CTX_0, alg.encode_content())),
+            self.key_encryption_algorithm.encode_ref(),
+            OctetString::encode_slice(&self.encrypted_key),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bcder::decode::Constructed;
+    use bcder::{Mode, OctetString};
+
+    use super::to_der;
+    use crate::testdata::{self, ALICE, EC, INT, ROOT};
+    use crate::{oid, parse_pkcs7, Attribute};
+
+    #[test]
+    fn pkcs7_round_trip() {
+        let fixtures: [&[u8]; 6] = [
+            include_bytes!("../testdata/signed.p7m"),
+            include_bytes!("../testdata/signed_ec.p7m"),
+            include_bytes!("../testdata/ktri.p7m"),
+            include_bytes!("../testdata/kari.p7m"),
+            include_bytes!("../testdata/kekri.p7m"),
+            include_bytes!("../testdata/pwri.p7m"),
+        ];
+        for der in fixtures {
+            assert_eq!(parse_pkcs7(der).unwrap().to_der(), der);
+        }
+    }
+
+    #[test]
+    fn certificate_round_trip() {
+        for pem in [ROOT, INT, ALICE, EC] {
+            assert_eq!(testdata::cert(pem).to_der(), testdata::der(pem));
+        }
+    }
+
+    #[test]
+    fn attribute_round_trip() {
+        // a value over 127 bytes has a long form length
+        let attribute = Attribute {
+            oid: oid::owned(oid::MESSAGE_DIGEST),
+            value: to_der(OctetString::encode_slice(&[0x5a; 200])),
+        };
+        let der = to_der(attribute.encode_ref());
+        let parsed = Constructed::decode(der.as_slice(), Mode::Der, Attribute::take_from).unwrap();
+        assert_eq!(parsed.value, attribute.value);
+        assert_eq!(to_der(parsed.encode_ref()), der);
+    }
+}
@@ -0,0 +1,856 @@
+/*
+CMS EnvelopedData (RFC 5652) and AuthEnvelopedData (RFC 5083)
+
+recipient infos: key transport (RSA, RSA-OAEP), key agreement (ECDH P-256/P-384),
+previously distributed KEK and password (PBKDF2 + PWRI-KEK)
+content: AES-CBC and AES-GCM
+*/
+
+use aes::cipher::{generic_array::GenericArray, BlockDecrypt, BlockDecryptMut, KeyInit, KeyIvInit};
+use aes::{Aes128, Aes192, Aes256};
+use aes_gcm::aead::consts::{U12, U16};
+use aes_gcm::aead::AeadInPlace;
+use aes_gcm::AesGcm;
+use bcder::decode::{self, Constructed, DecodeError, IntoSource};
+use bcder::encode::{self, PrimitiveContent, Values};
+use bcder::{BitString, Mode, Oid, OctetString, Tag};
+use rsa::{Oaep, Pkcs1v15Encrypt, RsaPrivateKey};
+use sha1::Sha1;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+
+use crate::crypto::{self, HashAlg};
+use crate::keys::PrivateKey;
+use crate::oid;
+use crate::{AlgorithmIdentifier, Attribute, SignerIdentifier};
+
+#[derive(Debug)]
+pub struct EnvelopedData {
+    pub version: u8,
+    pub originator_info: Option<Vec<u8>>, // DER of the [0] content, certs and crls of the originator
+    pub recipient_infos: Vec<RecipientInfo>,
+    pub encrypted_content_info: EncryptedContentInfo,
+    pub auth_attributes: Option<Vec<Attribute>>, // AuthEnvelopedData only
+    pub auth_bytes: Vec<u8>, // content of the implicit [1], the MAC covers SET OF + these bytes
+    pub mac: Option<Vec<u8>>, // AuthEnvelopedData only
+    pub unprotected_attributes: Option<Vec<Attribute>>,
+}
+
+#[derive(Debug)]
+pub struct EncryptedContentInfo {
+    pub content_type: Oid,
+    pub content_encryption_algorithm: AlgorithmIdentifier,
+    pub encrypted_content: Option<Vec<u8>>, // absent when detached
+}
+
+#[derive(Debug)]
+pub enum RecipientInfo {
+    KeyTrans(KeyTransRecipientInfo),
+    KeyAgree(KeyAgreeRecipientInfo),
+    Kek(KekRecipientInfo),
+    Password(PasswordRecipientInfo),
+    Other { ori_type: Oid, ori_value: Vec<u8> },
+}
+
+#[derive(Debug)]
+pub enum RecipientIdentifier {
+    IssuerAndSerial(SignerIdentifier),
+    SubjectKeyId(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub struct KeyTransRecipientInfo {
+    pub version: u8,
+    pub rid: RecipientIdentifier,
+    pub key_encryption_algorithm: AlgorithmIdentifier,
+    pub encrypted_key: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum OriginatorIdentifierOrKey {
+    IssuerAndSerial(SignerIdentifier),
+    SubjectKeyId(Vec<u8>),
+    OriginatorKey {
+        algorithm: AlgorithmIdentifier,
+        public_key: Vec<u8>,
+    },
+}
+
+#[derive(Debug)]
+pub struct KeyAgreeRecipientInfo {
+    pub version: u8,
+    pub originator: OriginatorIdentifierOrKey,
+    pub ukm: Option<Vec<u8>>,
+    pub key_encryption_algorithm: AlgorithmIdentifier,
+    pub recipient_encrypted_keys: Vec<RecipientEncryptedKey>,
+}
+
+#[derive(Debug)]
+pub struct RecipientEncryptedKey {
+    pub rid: RecipientIdentifier,
+    pub r_key_id_extra: Vec<u8>, // DER of rKeyId date and other, empty when absent
+    pub encrypted_key: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct KekRecipientInfo {
+    pub version: u8,
+    pub key_identifier: Vec<u8>,
+    pub kekid_extra: Vec<u8>, // DER of KEKIdentifier date and other, empty when absent
+    pub key_encryption_algorithm: AlgorithmIdentifier,
+    pub encrypted_key: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct PasswordRecipientInfo {
+    pub version: u8,
+    pub key_derivation_algorithm: Option<AlgorithmIdentifier>,
+    pub key_encryption_algorithm: AlgorithmIdentifier,
+    pub encrypted_key: Vec<u8>,
+}
+
+// what the caller holds to open the envelope
+pub enum DecryptionKey {
+    Private(PrivateKey),
+    Kek { key_id: Option<Vec<u8>>, key: Vec<u8> },
+    Password(Vec<u8>),
+}
+
+#[derive(Debug)]
+pub enum DecryptError {
+    NoMatchingRecipient,
+    UnsupportedAlgorithm(String),
+    KeyDecryption(String),
+    ContentDecryption(String),
+    Malformed(String),
+}
+
+impl std::fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DecryptError::NoMatchingRecipient => write!(f, "no recipient info matches the given key"),
+            DecryptError::UnsupportedAlgorithm(alg) => write!(f, "unsupported algorithm {}", alg),
+            DecryptError::KeyDecryption(e) => write!(f, "failed to decrypt content-encryption key: {}", e),
+            DecryptError::ContentDecryption(e) => write!(f, "failed to decrypt content: {}", e),
+            DecryptError::Malformed(e) => write!(f, "malformed enveloped data: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+impl EnvelopedData {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        EnvelopedData::take_from_with(cons, false)
+    }
+
+    pub fn take_auth_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        EnvelopedData::take_from_with(cons, true)
+    }
+
+    // both structures share everything up to the encrypted content
+    fn take_from_with<S: decode::Source>(cons: &mut Constructed<S>, authenticated: bool) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let version = cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+
+            // originatorInfo: certs and crls of the originator, not needed to decrypt
+            let originator_info = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                Ok(cons.capture_all()?.as_slice().to_vec())
+            })?;
+
+            let recipient_infos = cons.take_set(|cons| {
+                let mut recipients = Vec::new();
+                while let Ok(recipient) = RecipientInfo::take_from(cons) {
+                    recipients.push(recipient);
+                }
+                Ok(recipients)
+            })?;
+
+            let encrypted_content_info = EncryptedContentInfo::take_from(cons)?;
+
+            let mut auth_attributes = None;
+            let mut auth_bytes = Vec::new();
+            let mut mac = None;
+            if authenticated {
+                let auth_captured = cons.take_opt_constructed_if(Tag::CTX_1, |cons| cons.capture_all())?;
+                if let Some(captured) = auth_captured {
+                    auth_bytes = captured.as_slice().to_vec();
+                    auth_attributes = Some(take_attributes_from(captured)?);
+                }
+                mac = Some(OctetString::take_from(cons)?.into_bytes().to_vec());
+            }
+
+            let unprotected_tag = if authenticated { Tag::CTX_2 } else { Tag::CTX_1 };
+            let unprotected_attributes = cons.take_opt_constructed_if(unprotected_tag, |cons| {
+                let mut attributes = Vec::new();
+                while let Ok(attr) = Attribute::take_from(cons) {
+                    attributes.push(attr);
+                }
+                Ok(attributes)
+            })?;
+
+            Ok(EnvelopedData {
+                version,
+                originator_info,
+                recipient_infos,
+                encrypted_content_info,
+                auth_attributes,
+                auth_bytes,
+                mac,
+                unprotected_attributes,
+            })
+        })
+    }
+
+    // the eContent of an encrypted-then-signed SignedData is a bare EnvelopedData
+    pub fn from_der(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let enveloped = Constructed::decode(bytes, Mode::Ber, |cons| {
+            EnvelopedData::take_from(cons)
+        }).map_err(|err| format!("Error decoding EnvelopedData: {}", err))?;
+        Ok(enveloped)
+    }
+
+    pub fn is_authenticated(&self) -> bool {
+        self.mac.is_some()
+    }
+
+    pub fn decrypt(&self, key: &DecryptionKey) -> Result<Vec<u8>, DecryptError> {
+        let cek = self.recover_content_key(key)?;
+        // the MAC covers the DER of the attributes with the SET OF tag
+        let aad = if self.auth_attributes.is_some() { self.auth_attrs_der() } else { Vec::new() };
+        self.encrypted_content_info.decrypt(&cek, &aad, self.mac.as_deref())
+    }
+
+    // tries every recipient info the key could open, the first that unwraps wins
+    pub fn recover_content_key(&self, key: &DecryptionKey) -> Result<Vec<u8>, DecryptError> {
+        let mut last_err = DecryptError::NoMatchingRecipient;
+        for recipient in &self.recipient_infos {
+            let result = match (recipient, key) {
+                (RecipientInfo::KeyTrans(ktri), DecryptionKey::Private(PrivateKey::Rsa(rsa_key))) => {
+                    ktri.decrypt_key(rsa_key)
+                },
+                (RecipientInfo::KeyAgree(kari), DecryptionKey::Private(private_key)) => {
+                    kari.decrypt_key(private_key)
+                },
+                (RecipientInfo::Kek(kekri), DecryptionKey::Kek { key_id, key }) => {
+                    if key_id.as_ref().is_some_and(|id| *id != kekri.key_identifier) {
+                        continue;
+                    }
+                    kekri.decrypt_key(key)
+                },
+                (RecipientInfo::Password(pwri), DecryptionKey::Password(password)) => {
+                    pwri.decrypt_key(password)
+                },
+                _ => continue,
+            };
+            match result {
+                Ok(cek) => return Ok(cek),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+}
+
+impl EncryptedContentInfo {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let content_type = Oid::take_from(cons)?;
+            let content_encryption_algorithm = AlgorithmIdentifier::take_from(cons)?;
+            // [0] IMPLICIT OCTET STRING, constructed when streamed
+            let encrypted_content = cons.take_opt_value_if(Tag::CTX_0, |content| {
+                OctetString::from_content(content)
+            })?.map(|octets| octets.into_bytes().to_vec());
+
+            Ok(EncryptedContentInfo {
+                content_type,
+                content_encryption_algorithm,
+                encrypted_content,
+            })
+        })
+    }
+
+    pub fn decrypt(&self, cek: &[u8], aad: &[u8], mac: Option<&[u8]>) -> Result<Vec<u8>, DecryptError> {
+        let ciphertext = self.encrypted_content.as_deref().ok_or_else(|| {
+            DecryptError::Malformed("encrypted content is detached".into())
+        })?;
+        let alg = &self.content_encryption_algorithm;
+        let params = alg.parameters.as_deref().unwrap_or_default();
+        let gcm = alg.algorithm == oid::AES128_GCM || alg.algorithm == oid::AES192_GCM || alg.algorithm == oid::AES256_GCM;
+
+        // the mac only means something to an AEAD, a CBC content would come out unauthenticated
+        if mac.is_some() && !gcm {
+            Err(DecryptError::Malformed(format!(
+                "AuthEnvelopedData with the non-AEAD content algorithm {}", oid::describe(&alg.algorithm),
+            )))
+        } else if alg.algorithm == oid::AES128_CBC || alg.algorithm == oid::AES192_CBC || alg.algorithm == oid::AES256_CBC {
+            let iv = decode_octet_string(params)?;
+            aes_cbc_decrypt(cek, &iv, ciphertext)
+        } else if gcm {
+            let tag = mac.ok_or_else(|| {
+                DecryptError::Malformed("AES-GCM content outside of AuthEnvelopedData".into())
+            })?;
+            let nonce = decode_gcm_nonce(params)?;
+            aes_gcm_decrypt(cek, &nonce, aad, ciphertext, tag)
+        } else {
+            Err(DecryptError::UnsupportedAlgorithm(oid::describe(&alg.algorithm)))
+        }
+    }
+}
+
+impl RecipientInfo {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_value(|tag, content| {
+            let cons = content.as_constructed()?;
+            if tag == Tag::SEQUENCE {
+                Ok(RecipientInfo::KeyTrans(KeyTransRecipientInfo::take_content_from(cons)?))
+            } else if tag == Tag::CTX_1 {
+                Ok(RecipientInfo::KeyAgree(KeyAgreeRecipientInfo::take_content_from(cons)?))
+            } else if tag == Tag::CTX_2 {
+                Ok(RecipientInfo::Kek(KekRecipientInfo::take_content_from(cons)?))
+            } else if tag == Tag::CTX_3 {
+                Ok(RecipientInfo::Password(PasswordRecipientInfo::take_content_from(cons)?))
+            } else {
+                // OtherRecipientInfo: oriType and a value we cannot use
+                let ori_type = Oid::take_from(cons)?;
+                let ori_value = cons.capture_all()?.as_slice().to_vec();
+                Ok(RecipientInfo::Other { ori_type, ori_value })
+            }
+        })
+    }
+}
+
+impl RecipientIdentifier {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        // subjectKeyIdentifier [0] IMPLICIT OCTET STRING, otherwise issuerAndSerialNumber
+        let ski = cons.take_opt_value_if(Tag::CTX_0, |content| OctetString::from_content(content))?;
+        match ski {
+            Some(ski) => Ok(RecipientIdentifier::SubjectKeyId(ski.into_bytes().to_vec())),
+            None => Ok(RecipientIdentifier::IssuerAndSerial(SignerIdentifier::take_from(cons)?)),
+        }
+    }
+}
+
+impl KeyTransRecipientInfo {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let version = cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+        let rid = RecipientIdentifier::take_from(cons)?;
+        let key_encryption_algorithm = AlgorithmIdentifier::take_from(cons)?;
+        let encrypted_key = OctetString::take_from(cons)?.into_bytes().to_vec();
+
+        Ok(KeyTransRecipientInfo {
+            version,
+            rid,
+            key_encryption_algorithm,
+            encrypted_key,
+        })
+    }
+
+    pub fn decrypt_key(&self, key: &RsaPrivateKey) -> Result<Vec<u8>, DecryptError> {
+        let alg = &self.key_encryption_algorithm;
+        let result = if alg.algorithm == oid::RSA_ENCRYPTION {
+            key.decrypt(Pkcs1v15Encrypt, &self.encrypted_key)
+        } else if alg.algorithm == oid::RSAES_OAEP {
+            let (hash, mgf_hash) = decode_oaep_params(alg.parameters.as_deref())?;
+            key.decrypt(oaep_padding(hash, mgf_hash), &self.encrypted_key)
+        } else {
+            return Err(DecryptError::UnsupportedAlgorithm(oid::describe(&alg.algorithm)));
+        };
+        result.map_err(|e| DecryptError::KeyDecryption(e.to_string()))
+    }
+}
+
+impl KeyAgreeRecipientInfo {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let version = cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+
+        let originator = cons.take_constructed_if(Tag::CTX_0, |cons| {
+            if let Some(ski) = cons.take_opt_value_if(Tag::CTX_0, |content| OctetString::from_content(content))? {
+                return Ok(OriginatorIdentifierOrKey::SubjectKeyId(ski.into_bytes().to_vec()));
+            }
+            let originator_key = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+                let algorithm = AlgorithmIdentifier::take_from(cons)?;
+                let public_key = BitString::take_from(cons)?.octet_bytes().to_vec();
+                Ok(OriginatorIdentifierOrKey::OriginatorKey { algorithm, public_key })
+            })?;
+            match originator_key {
+                Some(key) => Ok(key),
+                None => Ok(OriginatorIdentifierOrKey::IssuerAndSerial(SignerIdentifier::take_from(cons)?)),
+            }
+        })?;
+
+        let ukm = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+            Ok(OctetString::take_from(cons)?.into_bytes().to_vec())
+        })?;
+
+        let key_encryption_algorithm = AlgorithmIdentifier::take_from(cons)?;
+
+        let recipient_encrypted_keys = cons.take_sequence(|cons| {
+            let mut keys = Vec::new();
+            while let Ok(key) = RecipientEncryptedKey::take_from(cons) {
+                keys.push(key);
+            }
+            Ok(keys)
+        })?;
+
+        Ok(KeyAgreeRecipientInfo {
+            version,
+            originator,
+            ukm,
+            key_encryption_algorithm,
+            recipient_encrypted_keys,
+        })
+    }
+
+    pub fn decrypt_key(&self, key: &PrivateKey) -> Result<Vec<u8>, DecryptError> {
+        let originator_key = match &self.originator {
+            OriginatorIdentifierOrKey::OriginatorKey { public_key, .. } => public_key,
+            _ => return Err(DecryptError::UnsupportedAlgorithm("static-static ECDH".into())),
+        };
+        let kdf_hash = ecdh_kdf_hash(&self.key_encryption_algorithm.algorithm)?;
+
+        // parameters of the agreement algorithm name the key wrap algorithm
+        let wrap_params = self.key_encryption_algorithm.parameters.as_deref().ok_or_else(|| {
+            DecryptError::Malformed("missing key wrap algorithm".into())
+        })?;
+        let wrap_algorithm = Constructed::decode(wrap_params, Mode::Der, |cons| {
+            AlgorithmIdentifier::take_from(cons)
+        }).map_err(|e| DecryptError::Malformed(e.to_string()))?;
+        let kek_len = aes_wrap_key_len(&wrap_algorithm.algorithm)?;
+
+        let secret = ecdh_shared_secret(key, originator_key)?;
+        let shared_info = ecc_cms_shared_info(&wrap_algorithm.algorithm, self.ukm.as_deref(), kek_len);
+        let kek = crypto::x963_kdf(kdf_hash, &secret, &shared_info, kek_len);
+
+        // we don't know which rid is ours, the wrap integrity check tells
+        let mut last_err = DecryptError::NoMatchingRecipient;
+        for recipient_key in &self.recipient_encrypted_keys {
+            match aes_key_unwrap(&kek, &recipient_key.encrypted_key) {
+                Ok(cek) => return Ok(cek),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+}
+
+impl RecipientEncryptedKey {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            // rKeyId [0] IMPLICIT RecipientKeyIdentifier, otherwise issuerAndSerialNumber
+            let r_key_id = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                let ski = OctetString::take_from(cons)?.into_bytes().to_vec();
+                let extra = cons.capture_all()?.as_slice().to_vec(); // date, other
+                Ok((ski, extra))
+            })?;
+            let (rid, r_key_id_extra) = match r_key_id {
+                Some((ski, extra)) => (RecipientIdentifier::SubjectKeyId(ski), extra),
+                None => (RecipientIdentifier::IssuerAndSerial(SignerIdentifier::take_from(cons)?), Vec::new()),
+            };
+            let encrypted_key = OctetString::take_from(cons)?.into_bytes().to_vec();
+
+            Ok(RecipientEncryptedKey {
+                rid,
+                r_key_id_extra,
+                encrypted_key,
+            })
+        })
+    }
+}
+
+impl KekRecipientInfo {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let version = cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+        let (key_identifier, kekid_extra) = cons.take_sequence(|cons| {
+            let id = OctetString::take_from(cons)?.into_bytes().to_vec();
+            let extra = cons.capture_all()?.as_slice().to_vec(); // date, other
+            Ok((id, extra))
+        })?;
+        let key_encryption_algorithm = AlgorithmIdentifier::take_from(cons)?;
+        let encrypted_key = OctetString::take_from(cons)?.into_bytes().to_vec();
+
+        Ok(KekRecipientInfo {
+            version,
+            key_identifier,
+            kekid_extra,
+            key_encryption_algorithm,
+            encrypted_key,
+        })
+    }
+
+    pub fn decrypt_key(&self, kek: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        let expected_len = aes_wrap_key_len(&self.key_encryption_algorithm.algorithm)?;
+        if kek.len() != expected_len {
+            return Err(DecryptError::KeyDecryption(format!("KEK must be {} bytes", expected_len)));
+        }
+        aes_key_unwrap(kek, &self.encrypted_key)
+    }
+}
+
+impl PasswordRecipientInfo {
+    fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let version = cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+        let key_derivation_algorithm = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+            AlgorithmIdentifier::take_content_from(cons)
+        })?;
+        let key_encryption_algorithm = AlgorithmIdentifier::take_from(cons)?;
+        let encrypted_key = OctetString::take_from(cons)?.into_bytes().to_vec();
+
+        Ok(PasswordRecipientInfo {
+            version,
+            key_derivation_algorithm,
+            key_encryption_algorithm,
+            encrypted_key,
+        })
+    }
+
+    pub fn decrypt_key(&self, password: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        if self.key_encryption_algorithm.algorithm != oid::PWRI_KEK {
+            return Err(DecryptError::UnsupportedAlgorithm(oid::describe(&self.key_encryption_algorithm.algorithm)));
+        }
+        // id-alg-PWRI-KEK parameters are the inner block cipher, AES-CBC with its IV
+        let inner_params = self.key_encryption_algorithm.parameters.as_deref().ok_or_else(|| {
+            DecryptError::Malformed("missing PWRI-KEK cipher".into())
+        })?;
+        let inner = Constructed::decode(inner_params, Mode::Der, |cons| {
+            AlgorithmIdentifier::take_from(cons)
+        }).map_err(|e| DecryptError::Malformed(e.to_string()))?;
+        let kek_len = aes_cbc_key_len(&inner.algorithm)?;
+        let iv = decode_octet_string(inner.parameters.as_deref().unwrap_or_default())?;
+
+        let derivation = self.key_derivation_algorithm.as_ref().ok_or_else(|| {
+            DecryptError::Malformed("missing key derivation algorithm".into())
+        })?;
+        let kek = derive_pbkdf2_key(derivation, password, kek_len)?;
+
+        pwri_unwrap(&kek, &iv, &self.encrypted_key)
+    }
+}
+
+// attributes captured from an IMPLICIT [n] SET OF Attribute
+fn take_attributes_from<E>(captured: bcder::Captured) -> Result<Vec<Attribute>, DecodeError<E>> {
+    Constructed::decode(captured.into_source(), Mode::Ber, |cons| {
+        let mut attributes = Vec::new();
+        while let Ok(attr) = Attribute::take_from(cons) {
+            attributes.push(attr);
+        }
+        Ok(attributes)
+    }).map_err(|e| DecodeError::content(format!("failed to parse attributes: {}", e), decode::Pos::default()))
+}
+
+// RSAES-OAEP-params, all fields default to SHA-1
+fn decode_oaep_params(params: Option<&[u8]>) -> Result<(HashAlg, HashAlg), DecryptError> {
+    let params = match params {
+        Some(params) if params != [0x05, 0x00] => params,
+        _ => return Ok((HashAlg::Sha1, HashAlg::Sha1)),
+    };
+    let (hash, mgf) = Constructed::decode(params, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let hash = cons.take_opt_constructed_if(Tag::CTX_0, AlgorithmIdentifier::take_from)?;
+            let mgf = cons.take_opt_constructed_if(Tag::CTX_1, AlgorithmIdentifier::take_from)?;
+            cons.skip_all()?; // pSourceAlgorithm, only the empty label is used in CMS
+            Ok((hash, mgf))
+        })
+    }).map_err(|e| DecryptError::Malformed(e.to_string()))?;
+
+    let hash = match hash {
+        Some(alg) => HashAlg::from_oid(&alg.algorithm)
+            .ok_or_else(|| DecryptError::UnsupportedAlgorithm(oid::describe(&alg.algorithm)))?,
+        None => HashAlg::Sha1,
+    };
+    let mgf_hash = match mgf {
+        Some(mgf) => {
+            if mgf.algorithm != oid::MGF1 {
+                return Err(DecryptError::UnsupportedAlgorithm(oid::describe(&mgf.algorithm)));
+            }
+            let mgf_params = mgf.parameters.as_deref().unwrap_or_default();
+            let mgf_hash = Constructed::decode(mgf_params, Mode::Der, |cons| {
+                AlgorithmIdentifier::take_from(cons)
+            }).map_err(|e| DecryptError::Malformed(e.to_string()))?;
+            HashAlg::from_oid(&mgf_hash.algorithm)
+                .ok_or_else(|| DecryptError::UnsupportedAlgorithm(oid::describe(&mgf_hash.algorithm)))?
+        },
+        None => HashAlg::Sha1,
+    };
+    Ok((hash, mgf_hash))
+}
+
+fn oaep_padding(hash: HashAlg, mgf_hash: HashAlg) -> Oaep {
+    fn with_mgf<D>(mgf_hash: HashAlg) -> Oaep
+    where D: 'static + sha2::Digest + sha2::digest::DynDigest + Send + Sync {
+        match mgf_hash {
+            HashAlg::Sha1 => Oaep::new_with_mgf_hash::<D, Sha1>(),
+            HashAlg::Sha224 => Oaep::new_with_mgf_hash::<D, Sha224>(),
+            HashAlg::Sha256 => Oaep::new_with_mgf_hash::<D, Sha256>(),
+            HashAlg::Sha384 => Oaep::new_with_mgf_hash::<D, Sha384>(),
+            HashAlg::Sha512 => Oaep::new_with_mgf_hash::<D, Sha512>(),
+        }
+    }
+    match hash {
+        HashAlg::Sha1 => with_mgf::<Sha1>(mgf_hash),
+        HashAlg::Sha224 => with_mgf::<Sha224>(mgf_hash),
+        HashAlg::Sha256 => with_mgf::<Sha256>(mgf_hash),
+        HashAlg::Sha384 => with_mgf::<Sha384>(mgf_hash),
+        HashAlg::Sha512 => with_mgf::<Sha512>(mgf_hash),
+    }
+}
+
+fn ecdh_kdf_hash<T: AsRef<[u8]>>(scheme: &Oid<T>) -> Result<HashAlg, DecryptError> {
+    // cofactor variants are the same thing on the NIST prime curves (h = 1)
+    if *scheme == oid::DH_SINGLE_PASS_STD_DH_SHA1KDF || *scheme == oid::DH_SINGLE_PASS_COFACTOR_DH_SHA1KDF {
+        Ok(HashAlg::Sha1)
+    } else if *scheme == oid::DH_SINGLE_PASS_STD_DH_SHA224KDF || *scheme == oid::DH_SINGLE_PASS_COFACTOR_DH_SHA224KDF {
+        Ok(HashAlg::Sha224)
+    } else if *scheme == oid::DH_SINGLE_PASS_STD_DH_SHA256KDF || *scheme == oid::DH_SINGLE_PASS_COFACTOR_DH_SHA256KDF {
+        Ok(HashAlg::Sha256)
+    } else if *scheme == oid::DH_SINGLE_PASS_STD_DH_SHA384KDF || *scheme == oid::DH_SINGLE_PASS_COFACTOR_DH_SHA384KDF {
+        Ok(HashAlg::Sha384)
+    } else if *scheme == oid::DH_SINGLE_PASS_STD_DH_SHA512KDF || *scheme == oid::DH_SINGLE_PASS_COFACTOR_DH_SHA512KDF {
+        Ok(HashAlg::Sha512)
+    } else {
+        Err(DecryptError::UnsupportedAlgorithm(oid::describe(scheme)))
+    }
+}
+
+fn ecdh_shared_secret(key: &PrivateKey, originator_key: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    let bad_point = |_| DecryptError::Malformed("invalid originator public key".into());
+    match key {
+        PrivateKey::P256(secret) => {
+            let public = p256::PublicKey::from_sec1_bytes(originator_key).map_err(bad_point)?;
+            let shared = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), public.as_affine());
+            Ok(shared.raw_secret_bytes().to_vec())
+        },
+        PrivateKey::P384(secret) => {
+            let public = p384::PublicKey::from_sec1_bytes(originator_key).map_err(bad_point)?;
+            let shared = p384::ecdh::diffie_hellman(secret.to_nonzero_scalar(), public.as_affine());
+            Ok(shared.raw_secret_bytes().to_vec())
+        },
+        PrivateKey::Rsa(_) => Err(DecryptError::NoMatchingRecipient),
+    }
+}
+
+// DER of ECC-CMS-SharedInfo (RFC 5753 section 7.2)
+fn ecc_cms_shared_info<T: AsRef<[u8]>>(wrap_algorithm: &Oid<T>, ukm: Option<&[u8]>, kek_len: usize) -> Vec<u8> {
+    let supp_pub_info = ((kek_len * 8) as u32).to_be_bytes();
+    encode::sequence((
+        // AES key wrap identifiers have absent parameters
+        encode::sequence(wrap_algorithm.encode_ref()),
+        ukm.map(|ukm| encode::sequence_as(Tag::CTX_0, OctetString::encode_slice(ukm))),
+        encode::sequence_as(Tag::CTX_2, OctetString::encode_slice(supp_pub_info)),
+    )).to_captured(Mode::Der).as_slice().to_vec()
+}
+
+pub fn derive_pbkdf2_key(derivation: &AlgorithmIdentifier, password: &[u8], key_len: usize) -> Result<Vec<u8>, DecryptError> {
+    if derivation.algorithm != oid::PBKDF2 {
+        return Err(DecryptError::UnsupportedAlgorithm(oid::describe(&derivation.algorithm)));
+    }
+    let params = derivation.parameters.as_deref().unwrap_or_default();
+    let (salt, rounds, prf) = Constructed::decode(params, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let salt = OctetString::take_from(cons)?.into_bytes().to_vec();
+            let rounds = cons.take_u32()?;
+            let _key_length = cons.take_opt_u32()?;
+            let prf = cons.take_opt_sequence(AlgorithmIdentifier::take_content_from)?;
+            Ok((salt, rounds, prf))
+        })
+    }).map_err(|e| DecryptError::Malformed(e.to_string()))?;
+
+    let prf_hash = match prf {
+        Some(prf) => HashAlg::from_hmac_oid(&prf.algorithm)
+            .ok_or_else(|| DecryptError::UnsupportedAlgorithm(oid::describe(&prf.algorithm)))?,
+        None => HashAlg::Sha1,
+    };
+    let mut key = vec![0u8; key_len];
+    prf_hash.pbkdf2(password, &salt, rounds, &mut key);
+    Ok(key)
+}
+
+pub fn decode_octet_string(der: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    Constructed::decode(der, Mode::Der, |cons| {
+        Ok(OctetString::take_from(cons)?.into_bytes().to_vec())
+    }).map_err(|e| DecryptError::Malformed(e.to_string()))
+}
+
+// GCMParameters ::= SEQUENCE { aes-nonce OCTET STRING, aes-ICVlen INTEGER DEFAULT 12 }
+fn decode_gcm_nonce(der: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    Constructed::decode(der, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let nonce = OctetString::take_from(cons)?.into_bytes().to_vec();
+            cons.skip_all()?;
+            Ok(nonce)
+        })
+    }).map_err(|e| DecryptError::Malformed(e.to_string()))
+}
+
+fn aes_wrap_key_len<T: AsRef<[u8]>>(alg: &Oid<T>) -> Result<usize, DecryptError> {
+    if *alg == oid::AES128_WRAP { Ok(16) }
+    else if *alg == oid::AES192_WRAP { Ok(24) }
+    else if *alg == oid::AES256_WRAP { Ok(32) }
+    else { Err(DecryptError::UnsupportedAlgorithm(oid::describe(alg))) }
+}
+
+pub fn aes_cbc_key_len<T: AsRef<[u8]>>(alg: &Oid<T>) -> Result<usize, DecryptError> {
+    if *alg == oid::AES128_CBC { Ok(16) }
+    else if *alg == oid::AES192_CBC { Ok(24) }
+    else if *alg == oid::AES256_CBC { Ok(32) }
+    else { Err(DecryptError::UnsupportedAlgorithm(oid::describe(alg))) }
+}
+
+// RFC 3394, the key size picks the AES variant
+fn aes_key_unwrap(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    let result = match kek.len() {
+        16 => aes_kw::KekAes128::new(GenericArray::from_slice(kek)).unwrap_vec(wrapped),
+        24 => aes_kw::KekAes192::new(GenericArray::from_slice(kek)).unwrap_vec(wrapped),
+        32 => aes_kw::KekAes256::new(GenericArray::from_slice(kek)).unwrap_vec(wrapped),
+        _ => return Err(DecryptError::KeyDecryption("invalid KEK length".into())),
+    };
+    result.map_err(|e| DecryptError::KeyDecryption(e.to_string()))
+}
+
+pub fn aes_cbc_decrypt(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    use aes::cipher::block_padding::Pkcs7;
+
+    let bad_key = |_| DecryptError::ContentDecryption("invalid key or IV length".into());
+    let result = match key.len() {
+        16 => cbc::Decryptor::<Aes128>::new_from_slices(key, iv).map_err(bad_key)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        24 => cbc::Decryptor::<Aes192>::new_from_slices(key, iv).map_err(bad_key)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        32 => cbc::Decryptor::<Aes256>::new_from_slices(key, iv).map_err(bad_key)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        _ => return Err(DecryptError::ContentDecryption("invalid key length".into())),
+    };
+    result.map_err(|_| DecryptError::ContentDecryption("bad padding, wrong key?".into()))
+}
+
+fn aes_gcm_decrypt(key: &[u8], nonce: &[u8], aad: &[u8], ciphertext: &[u8], tag: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    fn open<C: KeyInit + AeadInPlace>(key: &[u8], nonce: &[u8], aad: &[u8], ciphertext: &[u8], tag: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        let cipher = C::new_from_slice(key)
+            .map_err(|_| DecryptError::ContentDecryption("invalid key length".into()))?;
+        let mut buffer = ciphertext.to_vec();
+        cipher.decrypt_in_place_detached(GenericArray::from_slice(nonce), aad, &mut buffer, GenericArray::from_slice(tag))
+            .map_err(|_| DecryptError::ContentDecryption("authentication failed".into()))?;
+        Ok(buffer)
+    }
+
+    if nonce.len() != 12 {
+        return Err(DecryptError::UnsupportedAlgorithm(format!("AES-GCM with {} byte nonce", nonce.len())));
+    }
+    match (key.len(), tag.len()) {
+        (16, 16) => open::<AesGcm<Aes128, U12, U16>>(key, nonce, aad, ciphertext, tag),
+        (24, 16) => open::<AesGcm<Aes192, U12, U16>>(key, nonce, aad, ciphertext, tag),
+        (32, 16) => open::<AesGcm<Aes256, U12, U16>>(key, nonce, aad, ciphertext, tag),
+        (16, 12) => open::<AesGcm<Aes128, U12, U12>>(key, nonce, aad, ciphertext, tag),
+        (24, 12) => open::<AesGcm<Aes192, U12, U12>>(key, nonce, aad, ciphertext, tag),
+        (32, 12) => open::<AesGcm<Aes256, U12, U12>>(key, nonce, aad, ciphertext, tag),
+        _ => Err(DecryptError::ContentDecryption("invalid key or tag length".into())),
+    }
+}
+
+/*
+RFC 3211 key unwrap: the wrapped key was CBC encrypted twice, so
+1. decrypt the last block using the one before it as IV
+2. decrypt the other blocks using that result as IV (strips the outer layer)
+3. CBC decrypt everything again with the original IV
+the result is: key length, 3 check bytes (complement of the key's first 3), key, padding
+*/
+fn pwri_unwrap(kek: &[u8], iv: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    match kek.len() {
+        16 => pwri_unwrap_with(Aes128::new(GenericArray::from_slice(kek)), iv, wrapped),
+        24 => pwri_unwrap_with(Aes192::new(GenericArray::from_slice(kek)), iv, wrapped),
+        32 => pwri_unwrap_with(Aes256::new(GenericArray::from_slice(kek)), iv, wrapped),
+        _ => Err(DecryptError::KeyDecryption("invalid KEK length".into())),
+    }
+}
+
+fn pwri_unwrap_with<C: BlockDecrypt>(cipher: C, iv: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    let decrypt_block = |b: &mut [u8]| cipher.decrypt_block(GenericArray::from_mut_slice(b));
+    let bl = 16;
+    if wrapped.len() < 2 * bl || !wrapped.len().is_multiple_of(bl) || iv.len() != bl {
+        return Err(DecryptError::KeyDecryption("invalid wrapped key length".into()));
+    }
+    let n = wrapped.len() / bl;
+    let block = |i: usize| &wrapped[i * bl..(i + 1) * bl];
+    let xor = |a: &mut [u8], b: &[u8]| a.iter_mut().zip(b).for_each(|(x, y)| *x ^= y);
+
+    let mut tmp = wrapped.to_vec();
+    // last block, chained to the one before it
+    decrypt_block(&mut tmp[(n - 1) * bl..]);
+    xor(&mut tmp[(n - 1) * bl..], block(n - 2));
+    // the other blocks, the first one chained to the decrypted last block
+    let last = tmp[(n - 1) * bl..].to_vec();
+    for i in 0..n - 1 {
+        decrypt_block(&mut tmp[i * bl..(i + 1) * bl]);
+        xor(&mut tmp[i * bl..(i + 1) * bl], if i == 0 { &last } else { block(i - 1) });
+    }
+    // inner layer with the real IV
+    let outer = tmp.clone();
+    for i in 0..n {
+        decrypt_block(&mut tmp[i * bl..(i + 1) * bl]);
+        xor(&mut tmp[i * bl..(i + 1) * bl], if i == 0 { iv } else { &outer[(i - 1) * bl..i * bl] });
+    }
+
+    let key_len = tmp[0] as usize;
+    if (tmp[1] ^ tmp[4]) & (tmp[2] ^ tmp[5]) & (tmp[3] ^ tmp[6]) != 0xff || key_len + 4 > tmp.len() {
+        return Err(DecryptError::KeyDecryption("wrong password".into()));
+    }
+    Ok(tmp[4..4 + key_len].to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_pkcs7, Pkcs7Content};
+    use crate::testdata::{self, ALICE_KEY, EC_KEY, MSG};
+
+    fn decrypt(der: &[u8], key: &DecryptionKey) -> Result<Vec<u8>, DecryptError> {
+        parse_pkcs7(der).unwrap().enveloped_data().unwrap().decrypt(key)
+    }
+
+    #[test]
+    fn key_transport() {
+        let content = decrypt(include_bytes!("../testdata/ktri.p7m"), &testdata::private_key(ALICE_KEY)).unwrap();
+        assert_eq!(content, MSG);
+    }
+
+    #[test]
+    fn key_agreement() {
+        let content = decrypt(include_bytes!("../testdata/kari.p7m"), &testdata::private_key(EC_KEY)).unwrap();
+        assert_eq!(content, MSG);
+    }
+
+    #[test]
+    fn kek() {
+        let key = DecryptionKey::Kek { key_id: Some(b"kek".to_vec()), key: (0..16).collect() };
+        assert_eq!(decrypt(include_bytes!("../testdata/kekri.p7m"), &key).unwrap(), MSG);
+        let key = DecryptionKey::Kek { key_id: Some(b"other".to_vec()), key: (0..16).collect() };
+        assert!(matches!(decrypt(include_bytes!("../testdata/kekri.p7m"), &key), Err(DecryptError::NoMatchingRecipient)));
+    }
+
+    #[test]
+    fn password() {
+        let pwri = include_bytes!("../testdata/pwri.p7m");
+        assert_eq!(decrypt(pwri, &DecryptionKey::Password(b"secret".to_vec())).unwrap(), MSG);
+        assert!(decrypt(pwri, &DecryptionKey::Password(b"wrong".to_vec())).is_err());
+    }
+
+    #[test]
+    fn authenticated() {
+        let der = include_bytes!("../testdata/auth.p7m");
+        assert_eq!(decrypt(der, &testdata::private_key(ALICE_KEY)).unwrap(), MSG);
+
+        // the same envelope claiming AES-CBC content (that of ktri.p7m) must not decrypt without its authentication
+        let Pkcs7Content::AuthEnvelopedData(mut enveloped) = parse_pkcs7(der).unwrap().content else {
+            panic!("not an AuthEnvelopedData");
+        };
+        let cbc = parse_pkcs7(include_bytes!("../testdata/ktri.p7m")).unwrap();
+        enveloped.encrypted_content_info.content_encryption_algorithm.algorithm =
+            cbc.enveloped_data().unwrap().encrypted_content_info.content_encryption_algorithm.algorithm.clone();
+        let error = enveloped.decrypt(&testdata::private_key(ALICE_KEY)).unwrap_err();
+        assert!(matches!(error, DecryptError::Malformed(_)), "{}", error);
+    }
+
+    #[test]
+    fn other_recipient_key() {
+        assert!(decrypt(include_bytes!("../testdata/ktri.p7m"), &testdata::private_key(EC_KEY)).is_err());
+    }
+}
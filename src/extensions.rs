@@ -0,0 +1,406 @@
+// decoding of the X.509 v3 extension values (extn_value, without the OCTET STRING)
+
+use bcder::decode::{self, Constructed, Content, DecodeError};
+use bcder::{BitString, Integer, Mode, OctetString, Oid, Tag};
+use serde::Serialize;
+
+use crate::path::NameSubtree;
+use crate::{name, oid};
+
+pub struct BasicConstraints {
+    pub ca: bool,
+    pub path_len: Option<u32>,
+}
+
+// KeyUsage bits in RFC 5280 order
+pub const KEY_USAGE_NAMES: [&str; 9] = [
+    "digitalSignature",
+    "nonRepudiation",
+    "keyEncipherment",
+    "dataEncipherment",
+    "keyAgreement",
+    "keyCertSign",
+    "cRLSign",
+    "encipherOnly",
+    "decipherOnly",
+];
+
+// requireExplicitPolicy [0] and inhibitPolicyMapping [1], certificates to skip
+pub struct PolicyConstraints {
+    pub require_explicit_policy: Option<u32>,
+    pub inhibit_policy_mapping: Option<u32>,
+}
+
+// qcStatements (RFC 3739, ETSI EN 319 412-5)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QcStatements {
+    pub compliance: bool, // QcCompliance, an EU qualified certificate
+    pub sscd: bool, // QcSSCD, the private key is in a qualified signature creation device
+    pub types: Vec<String>, // QcType: esign, eseal or web
+    pub pds: Vec<PdsLocation>,
+    pub retention_period: Option<u32>, // years
+    pub limit_value: Option<MonetaryValue>,
+    pub legislation: Vec<String>, // QcCClegislation country codes, for non-EU qualified certificates
+    pub other: Vec<String>, // statement OIDs not decoded
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PdsLocation {
+    pub url: String,
+    pub language: String,
+}
+
+// amount * 10^exponent in the currency
+#[derive(Debug, Clone, Serialize)]
+pub struct MonetaryValue {
+    pub currency: String, // ISO 4217 alphabetic, or the numeric code as text
+    pub amount: i64,
+    pub exponent: i64,
+}
+
+pub fn basic_constraints(value: &[u8]) -> Option<BasicConstraints> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let ca = cons.take_opt_bool()?.unwrap_or(false); // DEFAULT FALSE
+            let path_len = cons.take_opt_u32()?;
+            Ok(BasicConstraints { ca, path_len })
+        })
+    }).ok()
+}
+
+// names of the bits that are set
+pub fn key_usage(value: &[u8]) -> Option<Vec<&'static str>> {
+    let bits = Constructed::decode(value, Mode::Der, BitString::take_from).ok()?;
+    Some(KEY_USAGE_NAMES.iter().enumerate().filter(|(i, _)| bits.bit(*i)).map(|(_, name)| *name).collect())
+}
+
+pub fn ext_key_usage(value: &[u8]) -> Option<Vec<Oid>> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let mut purposes = Vec::new();
+            while let Some(purpose) = cons.take_opt_primitive_if(Tag::OID, |content| {
+                Ok(Oid(content.take_all()?))
+            })? {
+                purposes.push(purpose);
+            }
+            Ok(purposes)
+        })
+    }).ok()
+}
+
+pub fn subject_key_identifier(value: &[u8]) -> Option<Vec<u8>> {
+    Constructed::decode(value, Mode::Der, OctetString::take_from).ok().map(|id| id.to_bytes().to_vec())
+}
+
+// keyIdentifier [0], issuer and serial are ignored
+pub fn authority_key_identifier(value: &[u8]) -> Option<Vec<u8>> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let key_id = cons.take_opt_primitive_if(Tag::CTX_0, |content| Ok(content.take_all()?.to_vec()))?;
+            cons.skip_all()?;
+            Ok(key_id)
+        })
+    }).ok().flatten()
+}
+
+// the policyIdentifiers, qualifiers are ignored
+pub fn certificate_policies(value: &[u8]) -> Option<Vec<Oid>> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let mut policies = Vec::new();
+            while let Some(policy) = cons.take_opt_sequence(|cons| {
+                let policy = Oid::take_from(cons)?;
+                cons.skip_all()?;
+                Ok(policy)
+            })? {
+                policies.push(policy);
+            }
+            Ok(policies)
+        })
+    }).ok()
+}
+
+// (issuerDomainPolicy, subjectDomainPolicy) pairs
+pub fn policy_mappings(value: &[u8]) -> Option<Vec<(Oid, Oid)>> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let mut mappings = Vec::new();
+            while let Some(mapping) = cons.take_opt_sequence(|cons| Ok((Oid::take_from(cons)?, Oid::take_from(cons)?)))? {
+                mappings.push(mapping);
+            }
+            Ok(mappings)
+        })
+    }).ok()
+}
+
+pub fn policy_constraints(value: &[u8]) -> Option<PolicyConstraints> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let require_explicit_policy = cons.take_opt_primitive_if(Tag::CTX_0, |prim| prim.to_u32())?;
+            let inhibit_policy_mapping = cons.take_opt_primitive_if(Tag::CTX_1, |prim| prim.to_u32())?;
+            Ok(PolicyConstraints { require_explicit_policy, inhibit_policy_mapping })
+        })
+    }).ok()
+}
+
+// SkipCerts
+pub fn inhibit_any_policy(value: &[u8]) -> Option<u32> {
+    Constructed::decode(value, Mode::Der, |cons| cons.take_u32()).ok()
+}
+
+// permittedSubtrees [0] and excludedSubtrees [1]; minimum and maximum are always 0 and absent (RFC 5280
+// section 4.2.1.10) and are skipped
+pub fn name_constraints(value: &[u8]) -> Option<(Vec<NameSubtree>, Vec<NameSubtree>)> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let permitted = cons.take_opt_constructed_if(Tag::CTX_0, take_subtrees)?.unwrap_or_default();
+            let excluded = cons.take_opt_constructed_if(Tag::CTX_1, take_subtrees)?.unwrap_or_default();
+            Ok((permitted, excluded))
+        })
+    }).ok()
+}
+
+fn take_subtrees<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Vec<NameSubtree>, DecodeError<S::Error>> {
+    let mut subtrees = Vec::new();
+    while let Some(subtree) = cons.take_opt_sequence(|cons| {
+        let base = cons.take_value(|tag, content| {
+            if tag == Tag::CTX_4 {
+                let dir_name = content.as_constructed()?.take_sequence(|cons| Ok(cons.capture_all()?.into_bytes()))?;
+                return Ok(NameSubtree::Directory(dir_name.to_vec()));
+            }
+            let subtree = match content {
+                Content::Primitive(content) => {
+                    let bytes = content.take_all()?;
+                    let text = String::from_utf8_lossy(&bytes).into_owned();
+                    if tag == Tag::CTX_1 {
+                        NameSubtree::Email(text)
+                    } else if tag == Tag::CTX_2 {
+                        NameSubtree::Dns(text)
+                    } else if tag == Tag::CTX_6 {
+                        NameSubtree::Uri(text)
+                    } else if tag == Tag::ctx(7) {
+                        NameSubtree::Ip(bytes.to_vec())
+                    } else {
+                        NameSubtree::Other(tag.to_string())
+                    }
+                },
+                Content::Constructed(content) => {
+                    content.skip_all()?;
+                    NameSubtree::Other(tag.to_string())
+                },
+            };
+            Ok(subtree)
+        })?;
+        cons.skip_all()?;
+        Ok(base)
+    })? {
+        subtrees.push(subtree);
+    }
+    Ok(subtrees)
+}
+
+// GeneralNames as openssl prints them: email:, DNS:, URI:, IP Address:, DirName:
+pub fn general_names(value: &[u8]) -> Option<Vec<String>> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let mut names = Vec::new();
+            while let Some(name) = cons.take_opt_value(|tag, content| {
+                if tag == Tag::CTX_4 {
+                    let dir_name = content.as_constructed()?.take_sequence(|cons| Ok(cons.capture_all()?.into_bytes()))?;
+                    return Ok(format!("DirName:{}", name::to_string(&dir_name)));
+                }
+                let described = match content {
+                    Content::Primitive(content) => {
+                        let bytes = content.take_all()?;
+                        let text = String::from_utf8_lossy(&bytes).into_owned();
+                        if tag == Tag::CTX_1 {
+                            format!("email:{}", text)
+                        } else if tag == Tag::CTX_2 {
+                            format!("DNS:{}", text)
+                        } else if tag == Tag::CTX_6 {
+                            format!("URI:{}", text)
+                        } else if tag == Tag::ctx(7) {
+                            format!("IP Address:{}", ip_address(&bytes))
+                        } else {
+                            format!("{}:{}", tag, hex::encode(&bytes))
+                        }
+                    },
+                    Content::Constructed(content) => {
+                        content.skip_all()?;
+                        format!("othername:{}", tag)
+                    },
+                };
+                Ok(described)
+            })? {
+                names.push(name);
+            }
+            Ok(names)
+        })
+    }).ok()
+}
+
+// SEQUENCE OF QCStatement { statementId, statementInfo ANY OPTIONAL }
+pub fn qc_statements(value: &[u8]) -> Option<QcStatements> {
+    Constructed::decode(value, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let mut statements = QcStatements::default();
+            while let Some(()) = cons.take_opt_sequence(|cons| {
+                let statement_id = Oid::take_from(cons)?;
+                if statement_id == oid::QCS_COMPLIANCE {
+                    statements.compliance = true;
+                } else if statement_id == oid::QCS_SSCD {
+                    statements.sscd = true;
+                } else if statement_id == oid::QCS_TYPE {
+                    cons.take_sequence(|cons| {
+                        while let Some(qc_type) = Oid::take_opt_from(cons)? {
+                            statements.types.push(oid::name(&qc_type).unwrap_or_else(|| qc_type.to_string()));
+                        }
+                        Ok(())
+                    })?;
+                } else if statement_id == oid::QCS_PDS {
+                    cons.take_sequence(|cons| {
+                        while let Some(location) = cons.take_opt_sequence(|cons| {
+                            let url = take_string(cons)?;
+                            let language = take_string(cons)?;
+                            Ok(PdsLocation { url, language })
+                        })? {
+                            statements.pds.push(location);
+                        }
+                        Ok(())
+                    })?;
+                } else if statement_id == oid::QCS_RETENTION_PERIOD {
+                    statements.retention_period = Some(cons.take_u32()?);
+                } else if statement_id == oid::QCS_LIMIT_VALUE {
+                    statements.limit_value = Some(cons.take_sequence(|cons| {
+                        // Iso4217CurrencyCode ::= CHOICE { alphabetic PrintableString, numeric INTEGER }
+                        let currency = cons.take_value(|tag, content| {
+                            let bytes = content.as_primitive()?.take_all()?;
+                            Ok(match tag {
+                                Tag::INTEGER => bytes.iter().fold(0u32, |code, byte| code << 8 | u32::from(*byte)).to_string(),
+                                _ => String::from_utf8_lossy(&bytes).into_owned(),
+                            })
+                        })?;
+                        let amount = cons.take_primitive_if(Tag::INTEGER, Integer::i64_from_primitive)?;
+                        let exponent = cons.take_primitive_if(Tag::INTEGER, Integer::i64_from_primitive)?;
+                        Ok(MonetaryValue { currency, amount, exponent })
+                    })?);
+                } else if statement_id == oid::QCS_CC_LEGISLATION {
+                    cons.take_sequence(|cons| {
+                        while let Some(country) = cons.take_opt_value_if(Tag::PRINTABLE_STRING, |content| {
+                            Ok(String::from_utf8_lossy(&content.as_primitive()?.take_all()?).into_owned())
+                        })? {
+                            statements.legislation.push(country);
+                        }
+                        Ok(())
+                    })?;
+                } else {
+                    statements.other.push(statement_id.to_string());
+                }
+                // statementInfo past what is understood, e.g. a NULL after QcCompliance
+                cons.skip_all()
+            })? {}
+            Ok(statements)
+        })
+    }).ok()
+}
+
+impl QcStatements {
+    // one line: QcCompliance, QcSSCD, QcType esign, ...
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.compliance {
+            parts.push("QcCompliance".to_string());
+        }
+        if self.sscd {
+            parts.push("QcSSCD".to_string());
+        }
+        if !self.types.is_empty() {
+            parts.push(format!("QcType {}", self.types.join(" ")));
+        }
+        for pds in &self.pds {
+            parts.push(format!("QcPDS {} ({})", pds.url, pds.language));
+        }
+        if let Some(years) = self.retention_period {
+            parts.push(format!("QcRetentionPeriod {} years", years));
+        }
+        if let Some(limit) = &self.limit_value {
+            parts.push(format!("QcLimitValue {}e{} {}", limit.amount, limit.exponent, limit.currency));
+        }
+        if !self.legislation.is_empty() {
+            parts.push(format!("QcCClegislation {}", self.legislation.join(" ")));
+        }
+        parts.extend(self.other.iter().cloned());
+        parts.join(", ")
+    }
+}
+
+// any string type as text, IA5String and PrintableString here
+fn take_string<S: decode::Source>(cons: &mut Constructed<S>) -> Result<String, DecodeError<S::Error>> {
+    cons.take_value(|_, content| Ok(String::from_utf8_lossy(&content.as_primitive()?.take_all()?).into_owned()))
+}
+
+fn ip_address(bytes: &[u8]) -> String {
+    match bytes.len() {
+        4 => std::net::Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string(),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            std::net::Ipv6Addr::from(octets).to_string()
+        },
+        _ => hex::encode(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata;
+
+    #[test]
+    fn qc_statements_of_qualified_certificate() {
+        let cert = testdata::cert(include_bytes!("../testdata/qualified.crt"));
+        let statements = cert.tbs_certificate.qc_statements().unwrap().unwrap();
+        assert!(statements.compliance);
+        assert!(statements.sscd);
+        assert_eq!(statements.types, ["esign"]);
+        assert_eq!(statements.pds.len(), 1);
+        assert_eq!((statements.pds[0].url.as_str(), statements.pds[0].language.as_str()), ("https://example.com/pds", "en"));
+        assert_eq!(statements.retention_period, Some(15));
+        let limit = statements.limit_value.as_ref().unwrap();
+        assert_eq!((limit.currency.as_str(), limit.amount, limit.exponent), ("EUR", 5, 4));
+        assert!(statements.legislation.is_empty());
+        assert_eq!(statements.other, ["1.3.6.1.5.5.7.11.2"]);
+        assert_eq!(statements.describe(), "QcCompliance, QcSSCD, QcType esign, QcPDS https://example.com/pds (en), \
+            QcRetentionPeriod 15 years, QcLimitValue 5e4 EUR, 1.3.6.1.5.5.7.11.2");
+    }
+
+    #[test]
+    fn qc_statements_numeric_currency_and_legislation() {
+        // QcLimitValue 1000e0 in currency 978, QcCClegislation CH
+        let value = hex::decode("30273015060604008e460102300b020203d2020203e8020100300e060604008e460107300413024348").unwrap();
+        let statements = qc_statements(&value).unwrap();
+        let limit = statements.limit_value.unwrap();
+        assert_eq!((limit.currency.as_str(), limit.amount, limit.exponent), ("978", 1000, 0));
+        assert_eq!(statements.legislation, ["CH"]);
+        assert!(testdata::cert(testdata::ALICE).tbs_certificate.qc_statements().is_none());
+    }
+
+    #[test]
+    fn qc_statements_skip_unexpected_statement_info() {
+        // QcCompliance with a NULL statementInfo, then QcSSCD
+        let value = hex::decode("3016300a060604008e46010105003008060604008e460104").unwrap();
+        let statements = qc_statements(&value).unwrap();
+        assert!(statements.compliance);
+        assert!(statements.sscd);
+    }
+
+    #[test]
+    fn qc_statements_malformed() {
+        let mut cert = testdata::cert(include_bytes!("../testdata/qualified.crt"));
+        let extension = cert.tbs_certificate.extensions.as_mut().unwrap().iter_mut()
+            .find(|extension| extension.extn_id == oid::QC_STATEMENTS).unwrap();
+        // an INTEGER where a QCStatement SEQUENCE belongs
+        extension.extn_value = hex::decode("3003020101").unwrap();
+        assert!(cert.tbs_certificate.qc_statements().unwrap().is_err());
+    }
+}
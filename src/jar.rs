@@ -0,0 +1,350 @@
+// JAR and APK v1 signatures (JAR File Specification, "Signed JAR File"): META-INF/<name>.RSA, .EC or .DSA
+// is a SignedData detached over META-INF/<name>.SF, whose digests cover the sections of MANIFEST.MF,
+// whose digests cover the archive entries. No ZIP64, archives that need it are not JARs in practice.
+
+use std::ops::Range;
+
+use base64::Engine;
+use serde::Serialize;
+
+use crate::crypto::HashAlg;
+use crate::oid;
+use crate::report::VerificationReport;
+
+pub const MANIFEST: &str = "META-INF/MANIFEST.MF";
+pub const SIGNATURE_BLOCK_EXTENSIONS: [&str; 3] = [".RSA", ".EC", ".DSA"];
+
+const LOCAL_HEADER: u32 = 0x04034b50;
+const CENTRAL_HEADER: u32 = 0x02014b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x06054b50;
+
+#[derive(Debug)]
+pub struct Archive<'a> {
+    data: &'a [u8],
+    pub entries: Vec<ZipEntry>,
+}
+
+#[derive(Debug)]
+pub struct ZipEntry {
+    pub name: String,
+    pub method: u16, // 0 stored, 8 deflated
+    pub compressed_size: usize,
+    pub size: usize,
+    pub local_header_offset: usize,
+}
+
+// MANIFEST.MF or a .SF file: the main section, then one section per entry
+#[derive(Debug)]
+pub struct Manifest {
+    pub main: Section,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Debug)]
+pub struct Section {
+    pub attributes: Vec<(String, String)>, // continuation lines joined
+    pub raw: Range<usize>, // the bytes of the section, its blank line included, what the .SF digests
+}
+
+#[derive(Serialize)]
+pub struct JarReport {
+    pub signatures: Vec<JarSignatureReport>,
+    pub entry_errors: Vec<String>, // manifest digests that do not match the archive
+    pub unsigned_entries: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct JarSignatureReport {
+    pub block: String,
+    pub signature_file: String,
+    pub manifest_errors: Vec<String>, // .SF digests that do not match the manifest
+    pub verification: VerificationReport,
+}
+
+impl<'a> Archive<'a> {
+    // the central directory, from the end of central directory record
+    pub fn parse(data: &'a [u8]) -> Result<Self, String> {
+        // the record is 22 bytes and a comment of at most 64 KiB
+        let search_start = data.len().saturating_sub(22 + 0xffff);
+        let end = (search_start..data.len().saturating_sub(21)).rev()
+            .find(|offset| read_u32(data, *offset) == Ok(END_OF_CENTRAL_DIRECTORY))
+            .ok_or("not a ZIP archive, no end of central directory")?;
+        let count = read_u16(data, end + 10)? as usize;
+        let mut offset = read_u32(data, end + 16)? as usize;
+        if count == 0xffff || offset == 0xffffffff {
+            return Err("ZIP64 archives are not supported".to_string());
+        }
+
+        let mut entries = Vec::with_capacity(count);
+        for _ in 0..count {
+            if read_u32(data, offset)? != CENTRAL_HEADER {
+                return Err(format!("bad central directory header at offset {}", offset));
+            }
+            let name_len = read_u16(data, offset + 28)? as usize;
+            let extra_len = read_u16(data, offset + 30)? as usize;
+            let comment_len = read_u16(data, offset + 32)? as usize;
+            let name = data.get(offset + 46..offset + 46 + name_len).ok_or("ZIP archive truncated")?;
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: read_u16(data, offset + 10)?,
+                compressed_size: read_u32(data, offset + 20)? as usize,
+                size: read_u32(data, offset + 24)? as usize,
+                local_header_offset: read_u32(data, offset + 42)? as usize,
+            });
+            offset += 46 + name_len + extra_len + comment_len;
+        }
+        Ok(Archive { data, entries })
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&ZipEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    // the uncompressed content of an entry
+    pub fn read(&self, entry: &ZipEntry) -> Result<Vec<u8>, String> {
+        let offset = entry.local_header_offset;
+        if read_u32(self.data, offset)? != LOCAL_HEADER {
+            return Err(format!("{}: bad local header", entry.name));
+        }
+        // the local extra field can differ from the central one
+        let start = offset + 30 + read_u16(self.data, offset + 26)? as usize + read_u16(self.data, offset + 28)? as usize;
+        let compressed = self.data.get(start..start + entry.compressed_size)
+            .ok_or_else(|| format!("{}: truncated", entry.name))?;
+        let content = match entry.method {
+            0 => compressed.to_vec(),
+            8 => miniz_oxide::inflate::decompress_to_vec_with_limit(compressed, entry.size)
+                .map_err(|e| format!("{}: bad deflate data, {:?}", entry.name, e.status))?,
+            method => return Err(format!("{}: compression method {} is not supported", entry.name, method)),
+        };
+        if content.len() != entry.size {
+            return Err(format!("{}: {} bytes instead of {}", entry.name, content.len(), entry.size));
+        }
+        Ok(content)
+    }
+
+    // META-INF/<name>.RSA, .EC and .DSA with their META-INF/<name>.SF
+    pub fn signature_blocks(&self) -> Vec<(&ZipEntry, String)> {
+        self.entries.iter().filter_map(|entry| {
+            let upper = entry.name.to_ascii_uppercase();
+            let base = upper.strip_prefix("META-INF/")?;
+            let extension = SIGNATURE_BLOCK_EXTENSIONS.iter().find(|extension| base.ends_with(*extension))?;
+            if base.contains('/') {
+                return None;
+            }
+            let stem = &entry.name[..entry.name.len() - extension.len()];
+            Some((entry, format!("{}.SF", stem)))
+        }).collect()
+    }
+}
+
+impl Manifest {
+    // CRLF, LF or CR line ends, 72 byte lines continued by a leading space
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut sections = Vec::new();
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        let mut section_start = 0;
+        let mut pos = 0;
+        while pos < data.len() {
+            let line_end = data[pos..].iter().position(|b| *b == b'\r' || *b == b'\n').map_or(data.len(), |i| pos + i);
+            let next = match data.get(line_end..line_end + 2) {
+                Some(b"\r\n") => line_end + 2,
+                _ => (line_end + 1).min(data.len()),
+            };
+            let line = &data[pos..line_end];
+            if line.is_empty() {
+                // a blank line ends the section, several in a row are one separator
+                if !attributes.is_empty() {
+                    sections.push(Section { attributes: std::mem::take(&mut attributes), raw: section_start..next });
+                }
+                section_start = next;
+            } else if let Some(continuation) = line.strip_prefix(b" ") {
+                let (_, value) = attributes.last_mut().ok_or_else(|| format!("continuation line at offset {} without an attribute", pos))?;
+                value.push_str(&String::from_utf8_lossy(continuation));
+            } else {
+                let line = String::from_utf8_lossy(line);
+                let (key, value) = line.split_once(": ").ok_or_else(|| format!("bad manifest line at offset {}: {}", pos, line))?;
+                attributes.push((key.to_string(), value.to_string()));
+            }
+            pos = next;
+        }
+        if !attributes.is_empty() {
+            sections.push(Section { attributes, raw: section_start..data.len() });
+        }
+
+        let mut sections = sections.into_iter();
+        let main = sections.next().ok_or("empty manifest")?;
+        Ok(Manifest { main, sections: sections.collect() })
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|section| section.name() == Some(name))
+    }
+}
+
+impl Section {
+    // attribute names are case insensitive
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, value)| value.as_str())
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.get("Name")
+    }
+
+    // every "<alg><suffix>" attribute, as SHA-256-Digest or SHA1-Digest-Manifest; unknown algorithms are skipped
+    pub fn digests(&self, suffix: &str) -> Vec<(HashAlg, Result<Vec<u8>, String>)> {
+        self.attributes.iter().filter_map(|(key, value)| {
+            let alg = key.len().checked_sub(suffix.len())
+                .filter(|split| key.is_char_boundary(*split) && key[*split..].eq_ignore_ascii_case(suffix))
+                .map(|split| &key[..split])?;
+            let digest = base64::engine::general_purpose::STANDARD.decode(value.trim())
+                .map_err(|e| format!("{}: bad base64, {}", key, e));
+            Some((HashAlg::from_name(alg)?, digest))
+        }).collect()
+    }
+}
+
+// every digest with the suffix has to match, and there has to be at least one known
+pub fn check_digests(section: &Section, suffix: &str, data: &[u8]) -> Option<Result<(), String>> {
+    let digests = section.digests(suffix);
+    if digests.is_empty() {
+        return None;
+    }
+    for (alg, digest) in digests {
+        let digest = match digest {
+            Ok(digest) => digest,
+            Err(e) => return Some(Err(e)),
+        };
+        if digest != alg.digest(data) {
+            return Some(Err(format!("{} digest does not match", oid::describe(&alg.oid()))));
+        }
+    }
+    Some(Ok(()))
+}
+
+// the .SF against the manifest: the whole manifest digest, otherwise the main attributes and each section
+// on its own (JAR File Specification, "Signature Validation"); returns the errors and the signed names
+pub fn check_signature_file(signature_file: &Manifest, manifest: &Manifest, manifest_bytes: &[u8]) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let names: Vec<String> = signature_file.sections.iter().filter_map(|section| section.name().map(str::to_string)).collect();
+    if check_digests(&signature_file.main, "-Digest-Manifest", manifest_bytes) == Some(Ok(())) {
+        return (errors, names);
+    }
+
+    let main_attributes = &manifest_bytes[manifest.main.raw.clone()];
+    if let Some(Err(e)) = check_digests(&signature_file.main, "-Digest-Manifest-Main-Attributes", main_attributes) {
+        errors.push(format!("main attributes: {}", e));
+    }
+    let mut signed = Vec::new();
+    for name in names {
+        let Some(section) = manifest.section(&name) else {
+            errors.push(format!("{}: signed but not in the manifest", name));
+            continue;
+        };
+        let entry = signature_file.section(&name).expect("names come from the sections");
+        match check_digests(entry, "-Digest", &manifest_bytes[section.raw.clone()]) {
+            Some(Ok(())) => signed.push(name),
+            Some(Err(e)) => errors.push(format!("{}: {}", name, e)),
+            None => errors.push(format!("{}: no digest of a known algorithm", name)),
+        }
+    }
+    (errors, signed)
+}
+
+// META-INF/MANIFEST.MF, the signature files and blocks and SIG-* are outside what is signed; so are directories
+pub fn is_signature_related(name: &str) -> bool {
+    let upper = name.to_ascii_uppercase();
+    let Some(base) = upper.strip_prefix("META-INF/") else {
+        return name.ends_with('/');
+    };
+    name.ends_with('/')
+        || (!base.contains('/')
+            && (base == "MANIFEST.MF"
+                || base.starts_with("SIG-")
+                || base.ends_with(".SF")
+                || SIGNATURE_BLOCK_EXTENSIONS.iter().any(|extension| base.ends_with(extension))))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| format!("ZIP archive truncated at offset {}", offset))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| format!("ZIP archive truncated at offset {}", offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::MSG;
+
+    const SIGNED: &[u8] = include_bytes!("../testdata/signed.jar");
+    const LONG_NAME: &str = "data/a-directory-name-long-enough-to-continue-the-manifest-line/bytes.bin";
+
+    fn read(archive: &Archive, name: &str) -> Vec<u8> {
+        archive.read(archive.entry(name).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn archive() {
+        let archive = Archive::parse(SIGNED).unwrap();
+        assert_eq!(archive.entries.len(), 5);
+        assert_eq!(archive.entry("hello.txt").unwrap().method, 8);
+        assert_eq!(read(&archive, "hello.txt"), MSG);
+        assert_eq!(archive.entry(LONG_NAME).unwrap().method, 0);
+        assert_eq!(read(&archive, LONG_NAME), (0..=255).collect::<Vec<u8>>());
+        let blocks: Vec<_> = archive.signature_blocks().into_iter().map(|(entry, sf)| (entry.name.clone(), sf)).collect();
+        assert_eq!(blocks, [("META-INF/ALICE.RSA".to_string(), "META-INF/ALICE.SF".to_string())]);
+        assert!(Archive::parse(MSG).is_err());
+    }
+
+    #[test]
+    fn manifest_sections() {
+        let archive = Archive::parse(SIGNED).unwrap();
+        let bytes = read(&archive, MANIFEST);
+        let manifest = Manifest::parse(&bytes).unwrap();
+        assert_eq!(manifest.main.get("manifest-version"), Some("1.0"));
+        assert_eq!(manifest.sections.len(), 2);
+        // the name goes on over a continuation line
+        let section = manifest.section(LONG_NAME).unwrap();
+        assert_eq!(check_digests(section, "-Digest", &(0..=255).collect::<Vec<u8>>()), Some(Ok(())));
+        assert_eq!(check_digests(section, "-Digest", b"other"), Some(Err("sha256 digest does not match".to_string())));
+        assert_eq!(check_digests(section, "-Digest-Manifest", b""), None);
+        assert!(bytes[section.raw.clone()].windows(3).any(|window| window == b"\r\n "));
+    }
+
+    #[test]
+    fn signature_file() {
+        let archive = Archive::parse(SIGNED).unwrap();
+        let signature_file = Manifest::parse(&read(&archive, "META-INF/ALICE.SF")).unwrap();
+        let bytes = read(&archive, MANIFEST);
+        let (errors, names) = check_signature_file(&signature_file, &Manifest::parse(&bytes).unwrap(), &bytes);
+        assert!(errors.is_empty());
+        assert_eq!(names, ["hello.txt", LONG_NAME]);
+
+        // a section added to the manifest breaks its whole digest, not the one of each signed section
+        let added = [bytes.as_slice(), b"Name: extra.txt\r\nSHA-256-Digest: AAAA\r\n\r\n"].concat();
+        let (errors, names) = check_signature_file(&signature_file, &Manifest::parse(&added).unwrap(), &added);
+        assert!(errors.is_empty());
+        assert_eq!(names.len(), 2);
+
+        let changed = String::from_utf8(bytes).unwrap().replacen("Name: hello.txt", "Name: hello.txt\r\nX-Note: changed", 1);
+        let (errors, names) = check_signature_file(&signature_file, &Manifest::parse(changed.as_bytes()).unwrap(), changed.as_bytes());
+        assert_eq!(errors, ["hello.txt: sha256 digest does not match"]);
+        assert_eq!(names, [LONG_NAME]);
+    }
+
+    #[test]
+    fn signature_related_names() {
+        for name in ["META-INF/MANIFEST.MF", "META-INF/ALICE.SF", "META-INF/alice.rsa", "META-INF/SIG-x", "META-INF/", "dir/"] {
+            assert!(is_signature_related(name), "{}", name);
+        }
+        for name in ["hello.txt", "META-INF/services/x.SF", "META-INF/LICENSE"] {
+            assert!(!is_signature_related(name), "{}", name);
+        }
+    }
+}
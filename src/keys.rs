@@ -0,0 +1,82 @@
+// private key loading (PKCS#8, PKCS#1 and SEC1, PEM or DER)
+
+use std::fs::File;
+use std::io::Read;
+
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::{RsaPrivateKey, RsaPublicKey};
+
+use crate::SubjectPublicKeyInfo;
+
+pub enum PrivateKey {
+    Rsa(Box<RsaPrivateKey>),
+    P256(p256::SecretKey),
+    P384(p384::SecretKey),
+}
+
+impl PrivateKey {
+    pub fn from_der(der: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        // PKCS#8 carries the algorithm, just try each supported one
+        if let Ok(key) = RsaPrivateKey::from_pkcs8_der(der) {
+            return Ok(PrivateKey::Rsa(Box::new(key)));
+        }
+        if let Ok(key) = p256::SecretKey::from_pkcs8_der(der) {
+            return Ok(PrivateKey::P256(key));
+        }
+        if let Ok(key) = p384::SecretKey::from_pkcs8_der(der) {
+            return Ok(PrivateKey::P384(key));
+        }
+        // legacy "RSA PRIVATE KEY" / "EC PRIVATE KEY" encodings
+        if let Ok(key) = RsaPrivateKey::from_pkcs1_der(der) {
+            return Ok(PrivateKey::Rsa(Box::new(key)));
+        }
+        if let Ok(key) = p256::SecretKey::from_sec1_der(der) {
+            return Ok(PrivateKey::P256(key));
+        }
+        if let Ok(key) = p384::SecretKey::from_sec1_der(der) {
+            return Ok(PrivateKey::P384(key));
+        }
+        Err("unsupported or malformed private key".into())
+    }
+
+    pub fn from_pem(data: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        // skip "EC PARAMETERS" blocks that openssl puts in front of the key
+        for block in pem::parse_many(data)? {
+            if block.tag().ends_with("PRIVATE KEY") {
+                if block.tag() == "ENCRYPTED PRIVATE KEY" {
+                    return Err("encrypted private keys are not supported".into());
+                }
+                return PrivateKey::from_der(block.contents());
+            }
+        }
+        Err("no private key found in PEM data".into())
+    }
+
+    // whether the certificate key is the public half of this one
+    pub fn matches(&self, spki: &SubjectPublicKeyInfo) -> bool {
+        let key = &spki.subject_public_key;
+        match self {
+            PrivateKey::Rsa(private) => RsaPublicKey::from_pkcs1_der(key).is_ok_and(|public| public == private.to_public_key()),
+            // compressed or not, the points compare equal
+            PrivateKey::P256(private) => p256::PublicKey::from_sec1_bytes(key).is_ok_and(|public| public == private.public_key()),
+            PrivateKey::P384(private) => p384::PublicKey::from_sec1_bytes(key).is_ok_and(|public| public == private.public_key()),
+        }
+    }
+}
+
+pub fn is_pem(data: &[u8]) -> bool {
+    data.windows(11).any(|w| w == b"-----BEGIN ")
+}
+
+pub fn load_private_key(path: &str) -> Result<PrivateKey, Box<dyn std::error::Error>> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    if is_pem(&buffer) {
+        PrivateKey::from_pem(&buffer)
+    } else {
+        PrivateKey::from_der(&buffer)
+    }
+}
@@ -0,0 +1,162 @@
+// long-term validation data (RFC 5126 section 6): the certificate-values, revocation-values and the
+// complete references of every signer, an offline source for chain building and revocation
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::attributes::{CrlOcspRef, OtherCertId};
+use crate::certs::{self, CertFormat};
+use crate::crl::CertificateList;
+use crate::ocsp::BasicOcspResponse;
+use crate::pretty::format_time;
+use crate::{Certificate, SignedData};
+
+#[derive(Debug, Default)]
+pub struct LtvData {
+    pub certs: Vec<Certificate>,
+    pub crls: Vec<CertificateList>,
+    pub ocsp_responses: Vec<BasicOcspResponse>,
+    pub certificate_refs: Vec<OtherCertId>,
+    pub revocation_refs: Vec<CrlOcspRef>,
+}
+
+impl LtvData {
+    // the unsigned attributes of all the signers together
+    pub fn from_signed_data(signed_data: &SignedData) -> Result<Self, String> {
+        let mut ltv = LtvData::default();
+        for signer_info in &signed_data.signer_infos {
+            for certs in signer_info.certificate_values().transpose()?.into_iter().flatten() {
+                ltv.certs.extend(certs);
+            }
+            for values in signer_info.revocation_values().transpose()?.into_iter().flatten() {
+                ltv.crls.extend(values.crls);
+                ltv.ocsp_responses.extend(values.ocsp_responses);
+            }
+            for refs in signer_info.complete_certificate_references().transpose()?.into_iter().flatten() {
+                ltv.certificate_refs.extend(refs);
+            }
+            for refs in signer_info.complete_revocation_references().transpose()?.into_iter().flatten() {
+                ltv.revocation_refs.extend(refs);
+            }
+        }
+        Ok(ltv)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certs.is_empty() && self.crls.is_empty() && self.ocsp_responses.is_empty()
+            && self.certificate_refs.is_empty() && self.revocation_refs.is_empty()
+    }
+
+    // the references that resolve to no value here or in the SignedData, empty when all do;
+    // OCSP hashes are taken over the BasicOCSPResponse, the form revocation-values stores
+    pub fn check_references(&self, signed_data: &SignedData) -> Vec<String> {
+        let mut problems = Vec::new();
+        for cert_ref in &self.certificate_refs {
+            let found = self.certs.iter().chain(&signed_data.certs).any(|cert| cert_ref.cert_hash.matches(&cert.raw));
+            if !found {
+                problems.push(format!("certificate reference {} has no value", hex::encode(&cert_ref.cert_hash.hash)));
+            }
+        }
+
+        let signed_data_crls = certs::split_crls(&signed_data.crls);
+        for revocation_ref in &self.revocation_refs {
+            for crl_hash in &revocation_ref.crl_hashes {
+                let found = self.crls.iter().map(|crl| &crl.raw[..])
+                    .chain(signed_data_crls.iter().map(|der| &der[..]))
+                    .any(|der| crl_hash.matches(der));
+                if !found {
+                    problems.push(format!("CRL reference {} has no value", hex::encode(&crl_hash.hash)));
+                }
+            }
+            for ocsp_id in &revocation_ref.ocsp_ids {
+                let found = self.ocsp_responses.iter().any(|response| {
+                    response.responder_id == ocsp_id.responder_id
+                        && response.produced_at == ocsp_id.produced_at
+                        && ocsp_id.response_hash.as_ref().is_none_or(|hash| hash.matches(&response.raw))
+                });
+                if !found {
+                    problems.push(format!("OCSP reference produced at {} has no value", format_time(ocsp_id.produced_at)));
+                }
+            }
+        }
+        problems
+    }
+
+    // writes the values into dir, certificates as <CN>_<serial>.der, crl_<n>.crl and ocsp_<n>.der
+    pub fn extract(&self, dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        fs::create_dir_all(dir)?;
+        let mut written = Vec::new();
+        for cert in &self.certs {
+            let path = dir.join(certs::file_name(cert, CertFormat::Der));
+            fs::write(&path, certs::encode_certificate(cert, CertFormat::Der))?;
+            written.push(path);
+        }
+        for (i, crl) in self.crls.iter().enumerate() {
+            let path = dir.join(format!("crl_{}.crl", i));
+            fs::write(&path, &crl.raw)?;
+            written.push(path);
+        }
+        for (i, response) in self.ocsp_responses.iter().enumerate() {
+            let path = dir.join(format!("ocsp_{}.der", i));
+            fs::write(&path, &response.raw)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, INT};
+    use crate::verify::{self, VerifyOptions, VerifyStatus};
+    use crate::{parse_pkcs7, Pkcs7};
+
+    fn ltv_p7m() -> Pkcs7 {
+        parse_pkcs7(include_bytes!("../testdata/ltv.p7m")).unwrap()
+    }
+
+    #[test]
+    fn values_and_references() {
+        let pkcs7 = ltv_p7m();
+        let signed_data = pkcs7.signed_data().unwrap();
+        let ltv = LtvData::from_signed_data(signed_data).unwrap();
+        assert_eq!(ltv.certs.len(), 1);
+        assert_eq!(ltv.certs[0].raw, testdata::der(INT));
+        assert_eq!(ltv.crls.len(), 2);
+        assert_eq!(ltv.certificate_refs.len(), 2);
+        assert_eq!(ltv.revocation_refs[0].crl_hashes.len(), 2);
+        assert!(ltv.check_references(signed_data).is_empty());
+
+        let ltv = LtvData { certs: Vec::new(), crls: Vec::new(), ..ltv };
+        let problems = ltv.check_references(signed_data);
+        assert_eq!(problems.len(), 3);
+        assert!(problems[0].starts_with("certificate reference "));
+        assert!(problems[1].starts_with("CRL reference "));
+    }
+
+    #[test]
+    fn intermediate_from_certificate_values() {
+        let pkcs7 = ltv_p7m();
+        let signed_data = pkcs7.signed_data().unwrap();
+        let status = |options: &VerifyOptions| verify::verify_signed_data(signed_data, options).unwrap()[0].status;
+        assert_eq!(status(&testdata::options()), VerifyStatus::Untrusted);
+        let options = VerifyOptions { ltv: LtvData::from_signed_data(signed_data).unwrap(), ..testdata::options() };
+        assert_eq!(status(&options), VerifyStatus::Valid);
+    }
+
+    #[test]
+    fn extract() {
+        let pkcs7 = ltv_p7m();
+        let ltv = LtvData::from_signed_data(pkcs7.signed_data().unwrap()).unwrap();
+        let dir = std::env::temp_dir().join(format!("p7b_verifier_ltv_{}", std::process::id()));
+        let written = ltv.extract(&dir).unwrap();
+        let names: Vec<_> = written.iter().map(|path| path.file_name().unwrap().to_str().unwrap().to_string()).collect();
+        let crl = fs::read(dir.join("crl_0.crl")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(names.len(), 3);
+        assert!(names[0].ends_with(".der"));
+        assert_eq!(names[1..], ["crl_0.crl", "crl_1.crl"]);
+        assert_eq!(crl, include_bytes!("../testdata/int.crl"));
+    }
+}
@@ -7,34 +7,80 @@ Verification of trust (certificate path)
 */
 
 
-use bcder::{decode::{IntoSource, Source}, OctetString};
+use bcder::decode::IntoSource;
 #[allow(dead_code)]
-use bcder::{Oid, Tag, Mode};
+use bcder::{BitString, Oid, OctetString, Tag, Mode};
 use bcder::decode::{self, Constructed, DecodeError};
 use std::fs::File;
 use std::io::Read;
 
+use bytes::Bytes;
 use chrono::{NaiveDateTime, TimeZone, Utc};
-use std::time::{SystemTime, UNIX_EPOCH};
+
+pub mod attributes;
+pub mod authenticode;
+pub mod cades;
+pub mod certs;
+pub mod cli;
+pub mod crl;
+pub mod crypto;
+pub mod dump;
+pub mod encode;
+pub mod enveloped;
+pub mod extensions;
+pub mod jar;
+pub mod keys;
+pub mod ltv;
+pub mod name;
+pub mod nested;
+pub mod ocsp;
+pub mod oid;
+pub mod path;
+pub mod pdf;
+pub mod pkcs12;
+pub mod pretty;
+pub mod report;
+pub mod sign;
+pub mod signature;
+pub mod smime;
+#[cfg(test)]
+pub mod testdata;
+pub mod timestamp;
+pub mod trust;
+pub mod tsl;
+pub mod verify;
+
+use enveloped::EnvelopedData;
 
 //use chrono::{NaiveDateTime, TimeZone, Utc};
 //use ring::signature::{self, UnparsedPublicKey};
 
+#[derive(Debug)]
 pub struct Pkcs7 {
     pub content_type: Oid,
-    pub content: SignedData,
-    pub content_bytes: Vec<u8>,
+    pub content: Pkcs7Content,
+    pub content_bytes: Bytes, // DER of the [0] content, a slice of the parsed input
+}
+
+// content parsed according to content_type
+#[derive(Debug)]
+pub enum Pkcs7Content {
+    SignedData(SignedData),
+    EnvelopedData(EnvelopedData),
+    AuthEnvelopedData(EnvelopedData),
 }
 
+#[derive(Debug)]
 pub struct SignedData {
     pub version: u8,
     pub digest_algorithms: Vec<AlgorithmIdentifier>,
     pub content_info: ContentInfo,
-    pub certs: Vec<Certificate>,
-    pub crls: Vec<u8>,
+    pub certs: Vec<Certificate>, // empty when the [0] field is absent
+    pub crls: Vec<u8>, // DER of the RevocationInfoChoices ([1] content), empty when absent
     pub signer_infos: Vec<SignerInfo>, // Multiple SignerInfo structures
 }
 
+#[derive(Debug)]
 pub struct SignerInfo {
     pub version: u8,
     pub signer_identifier: SignerIdentifier,
@@ -44,7 +90,7 @@ pub struct SignerInfo {
     pub auth_bytes: Vec<u8>,
     pub signature_algorithm: AlgorithmIdentifier,
     pub signature: Vec<u8>, // The actual signature (Encrypted digest)
-    //pub unauthenticated_attributes: Option<AuthenticatedAttributes>, // Optional field
+    pub unauth_attributes: Option<Vec<Attribute>>, // Optional field (countersignatures, timestamps, ...)
 }
 #[derive(Debug)]
 
@@ -81,49 +127,68 @@ pub struct AuthenticatedAttributes {
 #[derive(Debug)]
 pub struct Attribute {
     pub oid: Oid,           
-    pub value: Vec<u8>, 
+    pub value: Vec<u8>, // DER of the attribute values (content of the SET)
 }
 
 pub struct AttributeValue{
     pub bytes_value: Vec<u8>,
 }
 
+#[derive(Debug)]
 pub struct ContentInfo {
     pub content_type: Oid,
     pub content: Option<Vec<u8>>, // Encapsulated content
+    pub content_raw: Option<Bytes>, // the eContent TLV as received, written back unchanged; None for built ones
 }
 
+#[derive(Debug)]
 pub struct Certificate {
     pub tbs_certificate: TbsCertificate,
     pub signature_algorithm: AlgorithmIdentifier,
     pub signature_value: Vec<u8>,
+    pub raw: Bytes, // DER of the whole certificate, a slice of the parsed input
 }
 
+#[derive(Debug)]
 pub struct TbsCertificate {
     pub version: Option<u8>,
     pub serial_number: String,
     pub signature_algorithm: AlgorithmIdentifier,
-    pub issuer: Vec<u8>,
+    pub issuer: Bytes, // Name, content of the SEQUENCE
     pub validity: Validity,
-    pub subject: Vec<u8>,
+    pub subject: Bytes,
     pub subject_public_key_info: SubjectPublicKeyInfo,
-    //pub tbs_bytes: Vec<u8>,
+    pub issuer_unique_id: Option<Vec<u8>>, // raw BIT STRING content
+    pub subject_unique_id: Option<Vec<u8>>,
+    pub extensions: Option<Vec<Extension>>,
+    pub tbs_bytes: Bytes, // exact DER the issuer signed
 }
-#[derive(Debug)]
 
+#[derive(Debug)]
+pub struct Extension {
+    pub extn_id: Oid,
+    pub critical: bool,
+    pub extn_value: Vec<u8>, // DER of the extension value (OCTET STRING content)
+}
+#[derive(Debug, PartialEq, Eq)]
 pub struct AlgorithmIdentifier {
     pub algorithm: Oid,
     pub parameters: Option<Vec<u8>>, // Optional parameters
 }
 
+#[derive(Debug)]
 pub struct Validity {
-    pub not_before: u64,
-    pub not_after: u64,
+    pub not_before: i64, // UNIX epoch seconds
+    pub not_after: i64,
+    pub not_before_tag: Tag, // UTCTime or GeneralizedTime, kept for re-encoding
+    pub not_after_tag: Tag,
 }
 
+#[derive(Debug)]
 pub struct SubjectPublicKeyInfo {
     pub algorithm: AlgorithmIdentifier,
-    pub subject_public_key: Vec<u8>,
+    pub subject_public_key: Vec<u8>, // BIT STRING octets: RSAPublicKey DER or EC point
+    pub raw: Bytes, // DER of the whole SubjectPublicKeyInfo
 }
 
 impl Pkcs7 {
@@ -133,20 +198,24 @@ impl Pkcs7 {
 
             let content_type = Oid::take_from(cons)?;
 
-            let content_captured = cons.capture_all()?;
-            let mut content_bytes = content_captured.as_slice().to_vec();
-            content_bytes.drain(0..4); //remove tag and lenght bytes
-
-            let content_source = content_captured.into_source(); 
-
-            let content = Constructed::decode(content_source, Mode::Ber, |cons|{
-                let content_parsed = cons.take_constructed_if(Tag::CTX_0, |cons| {
-                    SignedData::take_from(cons)
-                })?;
-                Ok(content_parsed)
-            }).expect("failed to parse content");
+            // the [0] explicit content, its DER kept as content_bytes
+            let mut content_bytes = Bytes::new();
+            let content = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                content_bytes = cons.capture_all()?.into_bytes();
+                // decoded from the captured Bytes so that the raw fields stay slices of the input
+                Constructed::decode(content_bytes.clone(), Mode::Ber, |cons| {
+                    if content_type == oid::SIGNED_DATA {
+                        Ok(Pkcs7Content::SignedData(SignedData::take_from(cons)?))
+                    } else if content_type == oid::ENVELOPED_DATA {
+                        Ok(Pkcs7Content::EnvelopedData(EnvelopedData::take_from(cons)?))
+                    } else if content_type == oid::AUTH_ENVELOPED_DATA {
+                        Ok(Pkcs7Content::AuthEnvelopedData(EnvelopedData::take_auth_from(cons)?))
+                    } else {
+                        Err(DecodeError::content(format!("unsupported content type {}", oid::describe(&content_type)), decode::Pos::default()))
+                    }
+                }).map_err(|e| cons.content_err(format!("failed to parse content: {}", e)))
+            })?.ok_or_else(|| cons.content_err("missing content"))?;
 
-            
             Ok(Pkcs7 {
                 content_type,
                 content,
@@ -156,12 +225,18 @@ impl Pkcs7 {
         })
     }
 
-    pub fn to_string(&self) -> String {
-        format!(
-            "Pkcs7 {{\n  content_type: {},\n  content: {}\n}}",
-            self.content_type.to_string(),
-            self.content.to_string(),
-        )
+    pub fn signed_data(&self) -> Option<&SignedData> {
+        match &self.content {
+            Pkcs7Content::SignedData(signed_data) => Some(signed_data),
+            _ => None,
+        }
+    }
+
+    pub fn enveloped_data(&self) -> Option<&EnvelopedData> {
+        match &self.content {
+            Pkcs7Content::EnvelopedData(enveloped) | Pkcs7Content::AuthEnvelopedData(enveloped) => Some(enveloped),
+            _ => None,
+        }
     }
 }
 
@@ -179,43 +254,37 @@ impl SignedData {
             })?;
             let content_info = ContentInfo::take_from(cons)?;
             
-            let certs = cons.take_constructed_if(Tag::CTX_0, |cons| {
+            let certs = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
                 let mut certificates = Vec::new();
                 while let Ok(cert) = Certificate::take_from(cons) {
                     certificates.push(cert);
                 }
                 Ok(certificates)
-            })?;
+            })?.unwrap_or_default();
 
-            let signer_infos = cons.take_set(|cons| {
-                println!("*** Signer");
+            let crls = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+                Ok(cons.capture_all()?.as_slice().to_vec())
+            })?.unwrap_or_default();
 
+            // empty in certs-only bundles, some writers even leave out the SET
+            let signer_infos = cons.take_opt_set(|cons| {
                 let mut signers = Vec::new();
                 while let Ok(signer) = SignerInfo::take_from(cons) {
                     signers.push(signer);
                 }
                 Ok(signers)
-            })?;
+            })?.unwrap_or_default();
 
             Ok(SignedData {
                 version,
                 digest_algorithms,
                 content_info,
                 certs,
-                crls: Vec::new(), // Optional, can be parsed later
+                crls,
                 signer_infos,
             })
         })
     }
-
-    pub fn to_string(&self) -> String {
-        format!(
-            "SignedData {{\n  version: {},\n  content_info: {},\n  signer_infos: {}\n}}",
-            self.version,
-            self.content_info.to_string(),
-            self.signer_infos.iter().map(|s| s.to_string()).collect::<String>(),
-        )
-    }
 }
 
 impl SignerInfo {
@@ -230,21 +299,20 @@ impl SignerInfo {
             
             let digest_algorithm = AlgorithmIdentifier::take_from(cons)?;
             
-            let auth_captured = cons.capture_one()?;
-            let mut auth_bytes = auth_captured.as_slice().to_vec();
-            auth_bytes.drain(0..2); //remove implicit tag and lenght (A0,len)
-            let auth_source = auth_captured.into_source();
+            // content of the implicit [0] (no tag and length), the signature is over SET OF + these bytes
+            let auth_captured = cons.take_opt_constructed_if(Tag::CTX_0, |cons| cons.capture_all())?;
+            let auth_bytes = auth_captured.as_ref().map(|c| c.as_slice().to_vec()).unwrap_or_default();
 
-            let auth_attributes = Constructed::decode(auth_source, Mode::Ber, |cons|{
-                let auth_attrs = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+            let auth_attributes = match auth_captured {
+                Some(captured) => Some(Constructed::decode(captured.into_source(), Mode::Ber, |cons|{
                     let mut attributes = Vec::new();
                     while let Ok(attr) = Attribute::take_from(cons){
                         attributes.push(attr);
                     }
                     Ok(attributes)
-                })?;
-                Ok(auth_attrs)
-            }).expect("failed to parse auth attributes");
+                }).map_err(|e| DecodeError::content(format!("failed to parse auth attributes: {}", e), decode::Pos::default()))?),
+                None => None,
+            };
             
             //println!("auth attr: {:?}",auth_attributes);
 
@@ -259,12 +327,14 @@ impl SignerInfo {
                 Ok(sign_bytes)
             })?;
 
-            /*let unauthenticated_attributes = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
-                AuthenticatedAttributes::take_from(cons)
-            })?;*/
+            let unauth_attributes = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+                let mut attributes = Vec::new();
+                while let Ok(attr) = Attribute::take_from(cons){
+                    attributes.push(attr);
+                }
+                Ok(attributes)
+            })?;
 
-            cons.skip_all().unwrap_or(());
-            //let unauthenticated_attributes = None;
             Ok(SignerInfo {
                 version,
                 signer_identifier,
@@ -273,25 +343,14 @@ impl SignerInfo {
                 auth_bytes,
                 signature_algorithm,
                 signature,
-                //unauthenticated_attributes,
+                unauth_attributes,
             })
         })
     }
-
-    pub fn to_string(&self) -> String {
-        format!(
-            "SignerInfo {{\n  version: {},\n  digest_algorithm: {},\n  encrypted_digest: {:?}\n}}",
-            self.version,
-            self.digest_algorithm.to_string(),
-            self.signature,
-        )
-    }
 }
 
 impl SignerIdentifier {
     pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
-        println!("parsing signer identifier");
-
         let signer_identifier = cons.take_sequence(|cons| {
             // Capture the issuer bytes as needed
             let issuer = cons.take_sequence(|cons|{
@@ -495,10 +554,9 @@ impl Attribute {
             let oid = Oid::take_from(cons)?;
             //println!("parsed attr with OID {:?}",oid.as_ref().to_vec());
             
-            //value = vec di bytes (AttributeValue senza i 2 byte di Tag)
+            //value = DER of the AttributeValues, tags included
             let value = cons.take_set(|cons|{
-                let mut bytes_value = cons.capture_all()?.as_slice().to_vec();
-                bytes_value.drain(0..2);
+                let bytes_value = cons.capture_all()?.as_slice().to_vec();
                 //println!("bytes_value: {:?}\n",bytes_value);
                 Ok(bytes_value)
                 /*let mut attr_values = Vec::new();
@@ -511,14 +569,6 @@ impl Attribute {
             Ok(Attribute { oid, value })
         })
     }
-
-    pub fn to_string(&self) -> String {
-        format!(
-            "Attribute {{\n  oid: {},\n  value: {:?}\n}}",
-            self.oid.to_string(),
-            self.value
-        )
-    }
 }
 
 
@@ -590,35 +640,61 @@ impl Attribute {
 
 impl ContentInfo {
     pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
-        cons.take_sequence(|cons| {
+        cons.take_sequence(ContentInfo::take_content_from)
+    }
 
-            let content_type = Oid::take_from(cons)?;
-            let content = cons.take_opt_constructed_if(Tag::CTX_0, |content| {
-                let bytes = content.take_primitive(|_, content| {
-                    Ok(content.slice_all()?.to_vec())
-                })?;
-                println!("bytes of content: {:?}",bytes);
-                Ok(bytes)
-            })?;
+    // the fields inside the SEQUENCE
+    pub fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let content_type = Oid::take_from(cons)?;
+        // eContent OCTET STRING, may be constructed in BER; PKCS#7 v1.5 content is ANY and Authenticode
+        // puts its SpcIndirectDataContent there as it is, signing the value without tag and length
+        let mut content = None;
+        let content_raw = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+            Ok(cons.capture(|cons| {
+                if let Some(bytes) = OctetString::take_opt_from(cons)? {
+                    content = Some(bytes.into_bytes().to_vec());
+                    return Ok(());
+                }
+                content = Some(cons.take_value(|_, content| match content {
+                    decode::Content::Primitive(content) => Ok(content.take_all()?.to_vec()),
+                    decode::Content::Constructed(content) => Ok(content.capture_all()?.into_bytes().to_vec()),
+                })?);
+                Ok(())
+            })?.into_bytes())
+        })?;
 
-            Ok(ContentInfo {
-                content_type,
-                content,
-            })
+        Ok(ContentInfo {
+            content_type,
+            content,
+            content_raw,
         })
     }
-
-    pub fn to_string(&self) -> String {
-        format!(
-            "ContentInfo {{\n  content_type: {},\n  content: {:?}\n}}",
-            self.content_type.to_string(),
-            self.content
-        )
-    }
 }
 
 impl Certificate {
     pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let mut certificate = None;
+        let raw = cons.capture(|cons| {
+            certificate = Some(Certificate::take_fields_from(cons)?);
+            Ok(())
+        })?.into_bytes();
+
+        let mut certificate = certificate.ok_or_else(|| {
+            DecodeError::content("missing certificate", decode::Pos::default())
+        })?;
+        certificate.raw = raw;
+        Ok(certificate)
+    }
+
+    // DER or BER certificate, raw fields are slices of one copy of der
+    pub fn from_der(der: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let cert = Constructed::decode(Bytes::copy_from_slice(der), Mode::Ber, |cons| {
+            Certificate::take_from(cons)
+        }).map_err(|err| format!("Error decoding certificate: {}", err))?;
+        Ok(cert)
+    }
+
+    fn take_fields_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
         cons.take_sequence(|cons| {
 
             let tbs_certificate = TbsCertificate::take_from(cons)?;
@@ -640,18 +716,25 @@ impl Certificate {
                 tbs_certificate,
                 signature_algorithm,
                 signature_value,
+                raw: Bytes::new(),
             })
             
         })
     }
-    
 
-    pub fn to_string(&self) -> String {
-        format!(
-            "Certificate {{\n  tbs_certificate: {},\n  signature_algorithm: {},\n  signature_value: {:?}\n}}",
-            self.tbs_certificate.to_string(),
-            self.signature_algorithm.to_string(),
-            self.signature_value,
+    // checks the signature over the TBS bytes with the issuer's key, not the names or the validity
+    pub fn verify_signed_by(&self, issuer: &Certificate) -> Result<(), signature::SignatureError> {
+        // the outer algorithm must repeat the one inside the signed part
+        let inner = &self.tbs_certificate.signature_algorithm;
+        if inner.algorithm != self.signature_algorithm.algorithm || inner.parameters != self.signature_algorithm.parameters {
+            return Err(signature::SignatureError::Malformed("signature algorithm differs from the TBS one".into()));
+        }
+        signature::verify_signature(
+            &issuer.tbs_certificate.subject_public_key_info,
+            &self.signature_algorithm,
+            None,
+            &self.tbs_certificate.tbs_bytes,
+            &self.signature_value,
         )
     }
 
@@ -659,8 +742,20 @@ impl Certificate {
 
 impl TbsCertificate {
     pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let mut tbs_certificate = None;
+        let tbs_bytes = cons.capture(|cons| {
+            tbs_certificate = Some(TbsCertificate::take_fields_from(cons)?);
+            Ok(())
+        })?.into_bytes();
+
+        let mut tbs_certificate = tbs_certificate.ok_or_else(|| {
+            DecodeError::content("missing tbsCertificate", decode::Pos::default())
+        })?;
+        tbs_certificate.tbs_bytes = tbs_bytes;
+        Ok(tbs_certificate)
+    }
 
-        //let mut tbs_bytes: Vec<u8> = Vec::new();
+    fn take_fields_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
         cons.take_sequence(|cons| {
             
             //version = optional field
@@ -692,9 +787,7 @@ impl TbsCertificate {
                 })?;
                 
                 let issuer_bytes = constructed_content.capture_all()?;
-                let issuer_vec = issuer_bytes.to_vec();
-
-                Ok(issuer_vec)
+                Ok(issuer_bytes.into_bytes())
             })?;
 
             //asn1 format YYMMDDHHMMSSZ
@@ -706,15 +799,29 @@ impl TbsCertificate {
                     DecodeError::content(format!("Expected constructed content: {}", e), decode::Pos::default())
                 })?;
                 
-                let issuer_bytes = constructed_content.capture_all()?;
-                let subject_vec = issuer_bytes.to_vec();
-                //println!("sub {:?}",subject_vec);
-                Ok(subject_vec)
+                let subject_bytes = constructed_content.capture_all()?;
+                Ok(subject_bytes.into_bytes())
             })?;
 
             let subject_public_key_info = SubjectPublicKeyInfo::take_from(cons)?;
 
-            _ = cons.skip_all();
+            // v2 unique identifiers, [1] and [2] IMPLICIT BIT STRING
+            let issuer_unique_id = cons.take_opt_value_if(Tag::CTX_1, |content| {
+                Ok(content.as_primitive()?.take_all()?.to_vec())
+            })?;
+            let subject_unique_id = cons.take_opt_value_if(Tag::CTX_2, |content| {
+                Ok(content.as_primitive()?.take_all()?.to_vec())
+            })?;
+
+            let extensions = cons.take_opt_constructed_if(Tag::CTX_3, |cons| {
+                cons.take_sequence(|cons| {
+                    let mut extensions = Vec::new();
+                    while let Ok(extension) = Extension::take_from(cons) {
+                        extensions.push(extension);
+                    }
+                    Ok(extensions)
+                })
+            })?;
             
             Ok(TbsCertificate {
                 version,
@@ -724,142 +831,149 @@ impl TbsCertificate {
                 validity,
                 subject,
                 subject_public_key_info,
-                //tbs_bytes,
+                issuer_unique_id,
+                subject_unique_id,
+                extensions,
+                tbs_bytes: Bytes::new(),
             })
         })
     }
-    pub fn to_string(&self) -> String {
-        format!(
-            "TbsCertificate {{\n    version: {:?},\n    serial_number: {:?},\n    signature_algorithm: {},\n    issuer: {:?},\n    validity: {},\n    subject: {:?},\n    subject_public_key_info: {}\n  }}",
-            self.version,
-            self.serial_number,
-            self.signature_algorithm.to_string(),
-            self.issuer,
-            self.validity.to_string(),
-            self.subject,
-            self.subject_public_key_info.to_string()
-        )
+
+    pub fn find_extension(&self, extn_id: bcder::ConstOid) -> Option<&Extension> {
+        self.extensions.as_ref()?.iter().find(|extension| extension.extn_id == extn_id)
+    }
+
+    // None without the extension, Err when it is present but does not decode
+    pub fn qc_statements(&self) -> Option<Result<extensions::QcStatements, String>> {
+        let extension = self.find_extension(oid::QC_STATEMENTS)?;
+        Some(extensions::qc_statements(&extension.extn_value).ok_or_else(|| "malformed qcStatements extension".to_string()))
     }
-  
 
 }
 
-impl AlgorithmIdentifier {
+impl Extension {
     pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
         cons.take_sequence(|cons| {
-
-            let algorithm = Oid::take_from(cons)?;
-            let parameters = None; //not needed
-            _= cons.skip_all();
-
-            Ok(AlgorithmIdentifier {
-                algorithm,
-                parameters,
+            let extn_id = Oid::take_from(cons)?;
+            let critical = cons.take_opt_bool()?.unwrap_or(false); // DEFAULT FALSE
+            let extn_value = OctetString::take_from(cons)?.into_bytes().to_vec();
+
+            Ok(Extension {
+                extn_id,
+                critical,
+                extn_value,
             })
         })
     }
-    pub fn to_string(&self) -> String {
-        format!(
-            "AlgorithmIdentifier {{\n    algorithm: {},\n    parameters: {:?}\n  }}",
-            self.algorithm.to_string(),
-            self.parameters
-        )
-    }
 }
 
-impl Validity {
+impl AlgorithmIdentifier {
     pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
         cons.take_sequence(|cons| {
+            AlgorithmIdentifier::take_content_from(cons)
+        })
+    }
 
-            let not_before_str = cons.take_primitive(|_, content| {
-                let bytes = content.slice_all()?;
-                let time_str = String::from_utf8(bytes.to_vec()).map_err(|_| {
-                    DecodeError::content("Invalid UTF-8 sequence", decode::Pos::default())
-                })?;
-                _ = content.skip_all();
-                Ok(time_str)
-            })?;
-
-            let not_after_str = cons.take_primitive(|_, content| {
-                let bytes = content.slice_all()?;
-                let time_str = String::from_utf8(bytes.to_vec()).map_err(|_| {
-                    DecodeError::content("Invalid UTF-8 sequence", decode::Pos::default())
-                })?;
-                _ = content.skip_all();
-                Ok(time_str)
-            })?;
+    // also used where the SEQUENCE tag is replaced by an IMPLICIT one
+    pub fn take_content_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let algorithm = Oid::take_from(cons)?;
+
+        // raw DER of the parameters (IVs, OAEP hashes, ...), None when absent
+        let params = cons.capture_all()?;
+        let parameters = if params.as_slice().is_empty() {
+            None
+        } else {
+            Some(params.as_slice().to_vec())
+        };
+
+        Ok(AlgorithmIdentifier {
+            algorithm,
+            parameters,
+        })
+    }
+}
 
-            // converts string into UNIX epoch time
-            let not_before = Validity::parse_asn1_to_timestamp(&not_before_str)
-                .map_err(|_| DecodeError::content("Failed to parse not_before timestamp", decode::Pos::default()))?;
-            let not_after = Validity::parse_asn1_to_timestamp(&not_after_str)
-                .map_err(|_| DecodeError::content("Failed to parse not_after timestamp", decode::Pos::default()))?;
+impl Validity {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let (not_before, not_before_tag) = Validity::take_time_from(cons)?;
+            let (not_after, not_after_tag) = Validity::take_time_from(cons)?;
 
             Ok(Validity {
                 not_before,
                 not_after,
+                not_before_tag,
+                not_after_tag,
             })
         })
     }
 
-    fn parse_asn1_to_timestamp(date_str: &str) -> Result<u64, DecodeError<std::string::FromUtf8Error>> {
-        let naive_time = NaiveDateTime::parse_from_str(date_str, "%y%m%d%H%M%SZ")
+    // Time CHOICE as UNIX epoch seconds, with the tag it came in
+    pub fn take_time_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<(i64, Tag), DecodeError<S::Error>> {
+        let (tag, time_str) = cons.take_primitive(|tag, content| {
+            let bytes = content.slice_all()?;
+            let time_str = String::from_utf8(bytes.to_vec()).map_err(|_| {
+                DecodeError::content("Invalid UTF-8 sequence", decode::Pos::default())
+            })?;
+            _ = content.skip_all();
+            Ok((tag, time_str))
+        })?;
+
+        // converts string into UNIX epoch time
+        let timestamp = Validity::parse_asn1_to_timestamp(&time_str, tag)
+            .map_err(|_| DecodeError::content(format!("Failed to parse time {}", time_str), decode::Pos::default()))?;
+        Ok((timestamp, tag))
+    }
+
+    // UTCTime YYMMDDHHMMSSZ (YY >= 50 is 19YY), GeneralizedTime YYYYMMDDHHMMSSZ
+    pub fn parse_asn1_to_timestamp(date_str: &str, tag: Tag) -> Result<i64, DecodeError<std::string::FromUtf8Error>> {
+        let full_date = if tag == Tag::UTC_TIME {
+            let century = if date_str.get(0..2).is_some_and(|yy| yy >= "50") { "19" } else { "20" };
+            format!("{}{}", century, date_str)
+        } else {
+            date_str.to_string()
+        };
+        let naive_time = NaiveDateTime::parse_from_str(&full_date, "%Y%m%d%H%M%SZ")
             .map_err(|_| DecodeError::content("Invalid date format", decode::Pos::default()))?;
         
-        let timestamp = Utc.from_utc_datetime(&naive_time).timestamp() as u64;
+        let timestamp = Utc.from_utc_datetime(&naive_time).timestamp();
 
         Ok(timestamp)
     }
 
-    pub fn to_string(&self) -> String {
-        format!(
-            "Validity {{\n    not_before: {},\n    not_after: {}\n  }}",
-            self.not_before,
-            self.not_after
-        )
+    pub fn format_asn1_time(timestamp: i64, tag: Tag) -> String {
+        let time = Utc.timestamp_opt(timestamp, 0).single().unwrap_or_default();
+        if tag == Tag::UTC_TIME {
+            time.format("%y%m%d%H%M%SZ").to_string()
+        } else {
+            time.format("%Y%m%d%H%M%SZ").to_string()
+        }
     }
 }
 
 impl SubjectPublicKeyInfo {
     pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
-        cons.take_sequence(|cons| {
-            
-            let algorithm = AlgorithmIdentifier::take_from(cons)?;
-
-            let subject_public_key = cons.take_primitive(|_, content| {
-                let mut key_bytes = content.slice_all()?.to_vec();
-                _= content.skip_all();
-
-                //remove first 9 -> Tag, sequence, ecc; last 5 -> exp
-                if key_bytes.len() > 14 {
-                    key_bytes.drain(0..9);
-                    key_bytes.truncate(key_bytes.len()-5); //exp
-                }
-                else {
-                    println!("Failed to load pub Key");
-                    }
-                
-                Ok(key_bytes)
-                //let hex_bytes = hex::encode(&key_bytes);
-                //Ok(hex_bytes)
-            })?;
-            _ = cons.skip_all();
-            
-            //println!("subpubkey {:?}", subject_public_key);
-
-            Ok(SubjectPublicKeyInfo {
-                algorithm,
-                subject_public_key,
-            })
+        let mut fields = None;
+        let raw = cons.capture(|cons| {
+            fields = Some(cons.take_sequence(|cons| {
+                let algorithm = AlgorithmIdentifier::take_from(cons)?;
+
+                // whole key, RSAPublicKey { modulus, exponent } or the EC point
+                let subject_public_key = BitString::take_from(cons)?.octet_bytes().to_vec();
+                Ok((algorithm, subject_public_key))
+            })?);
+            Ok(())
+        })?.into_bytes();
+
+        let (algorithm, subject_public_key) = fields.ok_or_else(|| {
+            DecodeError::content("missing subjectPublicKeyInfo", decode::Pos::default())
+        })?;
+        Ok(SubjectPublicKeyInfo {
+            algorithm,
+            subject_public_key,
+            raw,
         })
     }  
-    pub fn to_string(&self) -> String {
-        format!(
-            "SubjectPublicKeyInfo {{\n    algorithm: {},\n    subject_public_key: {:?}\n  }}",
-            self.algorithm.to_string(),
-            self.subject_public_key
-        )
-    }     
 }
 
 /* load single x509 file
@@ -889,43 +1003,98 @@ fn load_certificate(path: &str) -> Result<Certificate, Box<dyn std::error::Error
 }
 */
 
-fn load_pkcs7(path: &str) -> Result<Pkcs7, Box<dyn std::error::Error>> {
+pub fn load_pkcs7(path: &str) -> Result<Pkcs7, Box<dyn std::error::Error>> {
     let mut file = File::open(path)?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
 
-    let pem = pem::parse(buffer)?;
-    let bytes = pem.contents();
+    parse_pkcs7(&buffer)
+}
+
+// DER of the first PEM block, or the file as is
+pub fn load_der(path: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    if keys::is_pem(&buffer) {
+        Ok(pem::parse(buffer)?.into_contents())
+    } else {
+        Ok(buffer)
+    }
+}
+
+// PEM or DER (.p7b, .p7m); BER so that streamed openssl output loads too
+pub fn parse_pkcs7(data: &[u8]) -> Result<Pkcs7, Box<dyn std::error::Error>> {
+    // one copy into Bytes, raw fields of the parsed structures are slices of it
+    let bytes = if keys::is_pem(data) {
+        Bytes::from(pem::parse(data)?.into_contents())
+    } else {
+        Bytes::copy_from_slice(data)
+    };
 
-    let pkcs7 = Constructed::decode(bytes, Mode::Der, |constructed| {
+    let pkcs7 = Constructed::decode(bytes, Mode::Ber, |constructed| {
         Pkcs7::take_from(constructed)
-    }).map_err(|err| {
-        eprintln!("Error decoding PKCS#7: {:?}", err);
-        Box::new(err) as Box<dyn std::error::Error>
-    })?;
+    }).map_err(|err| format!("Error decoding PKCS#7: {}", err))?;
 
     Ok(pkcs7)
 }
 
-
 /*
     problema
 
 */
+
 fn main() {
-    match load_pkcs7("../sdoc.p7b") {
-        Ok(pkcs7) => {
-            println!("PKCS#7 file loaded successfully!");
-            println!("signed attributes: {:?}",pkcs7.content.signer_infos[0].auth_attributes);
-            //println!("tbs_bytes: {:?}",pkcs7.content.certs[0].tbs_certificate.tbs_bytes);
-            //println!("auth attr{:?}",pkcs7.content.signer_infos[0].authenticated_attributes);
-            //let a = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-            //println!("now {}", a)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    std::process::exit(cli::run(&args));
+}
 
-        },
-        Err(e) => println!("Failed to load PKCS#7 file: {}", e),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, ALICE, INT, ROOT};
+
+    #[test]
+    fn truncated_content_info_is_a_decode_error() {
+        // signedData without the [0] content
+        let der = hex::decode("300b06092a864886f70d010702").unwrap();
+        assert!(parse_pkcs7(&der).is_err_and(|e| e.to_string().contains("missing content")));
+        // a short-form [0] length around an empty SignedData
+        let der = hex::decode("300f06092a864886f70d010702a0023000").unwrap();
+        assert!(parse_pkcs7(&der).is_err());
     }
-}
-    
 
+    #[test]
+    fn certificate_signed_by_issuer() {
+        let (root, int, alice) = (testdata::cert(ROOT), testdata::cert(INT), testdata::cert(ALICE));
+        assert!(root.verify_signed_by(&root).is_ok());
+        assert!(int.verify_signed_by(&root).is_ok());
+        assert!(alice.verify_signed_by(&int).is_ok()); // ECDSA P-256 over an RSA certificate
+        assert!(alice.verify_signed_by(&root).is_err());
+        // the signed bytes are the TBSCertificate as received
+        let der = testdata::der(ALICE);
+        let tbs = &alice.tbs_certificate.tbs_bytes;
+        assert!(der.windows(tbs.len()).any(|window| window == tbs.as_ref()));
+    }
+
+    #[test]
+    fn raw_fields_share_the_input() {
+        let pkcs7 = parse_pkcs7(include_bytes!("../testdata/signed.p7m")).unwrap();
+        let content = pkcs7.content_bytes.as_ptr_range();
+        let within = |bytes: &Bytes| content.contains(&bytes.as_ptr()) && bytes.len() <= pkcs7.content_bytes.len();
+        for cert in &pkcs7.signed_data().unwrap().certs {
+            let tbs = &cert.tbs_certificate;
+            assert!(within(&cert.raw) && within(&tbs.tbs_bytes) && within(&tbs.subject) && within(&tbs.subject_public_key_info.raw));
+        }
+    }
 
+    #[test]
+    fn altered_certificate_fails() {
+        let mut der = testdata::der(ALICE);
+        let at = der.windows(5).position(|window| window == b"Alice").unwrap();
+        der[at] = b'M';
+        let mallory = Constructed::decode(der.as_slice(), Mode::Der, Certificate::take_from).unwrap();
+        assert!(mallory.verify_signed_by(&testdata::cert(INT)).is_err());
+    }
+}
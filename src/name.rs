@@ -0,0 +1,52 @@
+// reading X.501 Names kept as raw bytes (content of the Name SEQUENCE)
+
+use bcder::decode::{self, Constructed};
+use bcder::{Mode, Oid, Tag};
+
+use crate::oid;
+
+// every AttributeTypeAndValue in order, values decoded as text
+pub fn entries(name: &[u8]) -> Vec<(Oid, String)> {
+    let mut entries = Vec::new();
+    let _ = Constructed::decode(name, Mode::Ber, |cons| {
+        // RDNSequence: SET OF AttributeTypeAndValue, usually one per set
+        while let Some(()) = cons.take_opt_set(|cons| {
+            while let Some(()) = cons.take_opt_sequence(|cons| {
+                let attribute_type = Oid::take_from(cons)?;
+                let value = cons.take_value(|tag, content| {
+                    let bytes = content.as_primitive()?.take_all()?.to_vec();
+                    Ok(decode_string(tag, bytes))
+                })?;
+                entries.push((attribute_type, value));
+                Ok(())
+            })? {}
+            Ok(())
+        })? {}
+        Ok(())
+    }).map_err(|_: decode::DecodeError<_>| ());
+    entries
+}
+
+pub fn common_name(name: &[u8]) -> Option<String> {
+    entries(name).into_iter().find(|(attribute_type, _)| *attribute_type == oid::COMMON_NAME).map(|(_, value)| value)
+}
+
+// one line, openssl style: CN=Foo, O=Bar
+pub fn to_string(name: &[u8]) -> String {
+    entries(name).iter().map(|(attribute_type, value)| {
+        format!("{}={}", oid::describe(attribute_type), value)
+    }).collect::<Vec<_>>().join(", ")
+}
+
+// DirectoryString and friends
+fn decode_string(tag: Tag, bytes: Vec<u8>) -> String {
+    if tag == Tag::BMP_STRING {
+        let units: Vec<u16> = bytes.chunks(2).map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)])).collect();
+        String::from_utf16_lossy(&units)
+    } else if tag == Tag::TELETEX_STRING {
+        // latin1 is what the T61 strings in the wild actually hold
+        bytes.iter().map(|&b| b as char).collect()
+    } else {
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+}
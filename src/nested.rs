@@ -0,0 +1,130 @@
+// nested signatures, as Italian .p7m files signed several times (doc.pdf.p7m.p7m): every SignedData
+// layer encapsulates the next one, the innermost encapsulates the document
+
+use std::path::{Path, PathBuf};
+
+use bcder::decode::Constructed;
+use bcder::Mode;
+use bytes::Bytes;
+use serde::Serialize;
+
+use crate::report::VerificationReport;
+use crate::{keys, oid, parse_pkcs7, Pkcs7, Pkcs7Content, SignedData};
+
+const MAX_LAYERS: usize = 16;
+
+#[derive(Serialize)]
+pub struct UnwrapReport {
+    pub layers: Vec<VerificationReport>, // outermost first
+    pub document: String, // where the document was written
+}
+
+// the SignedData layers, outermost first; the inner ones are a ContentInfo in id-data
+// or the SignedData itself under eContentType signedData
+pub fn unwrap(data: &[u8]) -> Result<Vec<SignedData>, Box<dyn std::error::Error>> {
+    let outer = into_signed_data(parse_any(data)?).ok_or("not a SignedData")?;
+    let mut layers = vec![outer];
+    while let Some(inner) = layers.last().and_then(inner_layer) {
+        if layers.len() == MAX_LAYERS {
+            return Err(format!("more than {} nested signatures", MAX_LAYERS).into());
+        }
+        layers.push(inner);
+    }
+    Ok(layers)
+}
+
+// the content of the innermost layer, None when it is detached
+pub fn document(layers: &[SignedData]) -> Option<&[u8]> {
+    layers.last()?.content_info.content.as_deref()
+}
+
+// doc.pdf.p7m.p7m -> doc.pdf, other names get .out
+pub fn document_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let signed_name = name.clone();
+    while name.len() > 4 && name[name.len() - 4..].eq_ignore_ascii_case(".p7m") {
+        name.truncate(name.len() - 4);
+    }
+    if name == signed_name {
+        name.push_str(".out");
+    }
+    path.with_file_name(name)
+}
+
+fn inner_layer(layer: &SignedData) -> Option<SignedData> {
+    let content = layer.content_info.content.as_deref()?;
+    if layer.content_info.content_type == oid::SIGNED_DATA {
+        Constructed::decode(Bytes::copy_from_slice(content), Mode::Ber, SignedData::take_from).ok()
+    } else if layer.content_info.content_type == oid::DATA {
+        // any other document does not parse as a ContentInfo
+        parse_any(content).ok().and_then(into_signed_data)
+    } else {
+        None
+    }
+}
+
+fn into_signed_data(pkcs7: Pkcs7) -> Option<SignedData> {
+    match pkcs7.content {
+        Pkcs7Content::SignedData(signed_data) => Some(signed_data),
+        _ => None,
+    }
+}
+
+// DER, PEM, or bare base64 as some signing tools write it
+fn parse_any(data: &[u8]) -> Result<Pkcs7, Box<dyn std::error::Error>> {
+    let is_base64 = |c: &u8| c.is_ascii_alphanumeric() || b"+/=\r\n".contains(c);
+    if !keys::is_pem(data) && !data.is_empty() && data.iter().all(is_base64) {
+        // the PEM reader wants its own 64 column lines
+        let base64: Vec<u8> = data.iter().copied().filter(|c| !c.is_ascii_whitespace()).collect();
+        let mut wrapped = b"-----BEGIN PKCS7-----\n".to_vec();
+        for line in base64.chunks(64) {
+            wrapped.extend_from_slice(line);
+            wrapped.push(b'\n');
+        }
+        wrapped.extend_from_slice(b"-----END PKCS7-----\n");
+        return parse_pkcs7(&wrapped);
+    }
+    parse_pkcs7(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, MSG, SIGNED};
+    use crate::verify::{self, VerifyStatus};
+
+    const NESTED: &[u8] = include_bytes!("../testdata/nested.p7m");
+
+    fn statuses(layers: &[SignedData]) -> Vec<VerifyStatus> {
+        let options = testdata::options();
+        layers.iter().map(|layer| verify::verify_signed_data(layer, &options).unwrap()[0].status).collect()
+    }
+
+    #[test]
+    fn layers() {
+        for der in [NESTED, include_bytes!("../testdata/nested_econtent.p7m")] {
+            let layers = unwrap(der).unwrap();
+            assert_eq!(layers.len(), 2);
+            assert_eq!(document(&layers), Some(MSG));
+            assert_eq!(statuses(&layers), [VerifyStatus::Valid, VerifyStatus::Valid]);
+        }
+        assert_eq!(unwrap(SIGNED).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn bare_base64() {
+        // the PEM body without its BEGIN and END lines
+        let pem = pem::encode(&pem::Pem::new("PKCS7", NESTED));
+        let wrapped: Vec<u8> = pem.lines().filter(|line| !line.starts_with("-----")).flat_map(|line| [line, "\r\n"].concat().into_bytes()).collect();
+        let layers = unwrap(&wrapped).unwrap();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(document(&layers), Some(MSG));
+    }
+
+    #[test]
+    fn document_names() {
+        assert_eq!(document_path(Path::new("dir/doc.pdf.p7m.P7M")), Path::new("dir/doc.pdf"));
+        assert_eq!(document_path(Path::new("doc.p7m")), Path::new("doc"));
+        assert_eq!(document_path(Path::new("signed.der")), Path::new("signed.der.out"));
+    }
+}
@@ -0,0 +1,254 @@
+// OCSP responses (RFC 6960 section 4.2): the per-certificate statuses and the responder's signature
+
+use bcder::decode::{self, Constructed, DecodeError};
+use bcder::{BitString, Mode, OctetString, Oid, Tag};
+use bytes::Bytes;
+
+use crate::crypto::HashAlg;
+use crate::encode::{to_der, RawDer};
+use crate::signature::{self, SignatureError};
+use crate::{extensions, name, oid};
+use crate::{AlgorithmIdentifier, Certificate, Validity};
+
+#[derive(Debug)]
+pub struct BasicOcspResponse {
+    pub responder_id: ResponderId,
+    pub produced_at: i64,
+    pub responses: Vec<SingleResponse>,
+    pub tbs_bytes: Bytes, // exact DER of the ResponseData the responder signed
+    pub signature_algorithm: AlgorithmIdentifier,
+    pub signature_value: Vec<u8>,
+    pub certs: Vec<Certificate>, // the responder certificate for delegated responders
+    pub raw: Bytes,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ResponderId {
+    ByName(Bytes), // Name, content of the SEQUENCE
+    ByKey(Vec<u8>), // SHA-1 of the responder public key
+}
+
+#[derive(Debug)]
+pub struct SingleResponse {
+    pub cert_id: CertId,
+    pub status: CertStatus,
+    pub this_update: i64,
+    pub next_update: Option<i64>,
+}
+
+#[derive(Debug)]
+pub struct CertId {
+    pub hash_algorithm: AlgorithmIdentifier,
+    pub issuer_name_hash: Vec<u8>,
+    pub issuer_key_hash: Vec<u8>,
+    pub serial_number: String, // hex, as in TbsCertificate
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertStatus {
+    Good,
+    Revoked { at: i64 },
+    Unknown,
+}
+
+impl BasicOcspResponse {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        let mut response = None;
+        let raw = cons.capture(|cons| {
+            response = Some(cons.take_sequence(|cons| {
+                let mut tbs = None;
+                let tbs_bytes = cons.capture(|cons| {
+                    tbs = Some(take_response_data_from(cons)?);
+                    Ok(())
+                })?.into_bytes();
+                let signature_algorithm = AlgorithmIdentifier::take_from(cons)?;
+                let signature_value = BitString::take_from(cons)?.octet_bytes().to_vec();
+                let certs = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+                    cons.take_sequence(|cons| {
+                        let mut certs = Vec::new();
+                        while let Ok(cert) = Certificate::take_from(cons) {
+                            certs.push(cert);
+                        }
+                        Ok(certs)
+                    })
+                })?.unwrap_or_default();
+                Ok((tbs, tbs_bytes, signature_algorithm, signature_value, certs))
+            })?);
+            Ok(())
+        })?.into_bytes();
+
+        let (tbs, tbs_bytes, signature_algorithm, signature_value, certs) = response.ok_or_else(|| {
+            DecodeError::content("missing BasicOCSPResponse", decode::Pos::default())
+        })?;
+        let (responder_id, produced_at, responses) = tbs.ok_or_else(|| {
+            DecodeError::content("missing ResponseData", decode::Pos::default())
+        })?;
+        Ok(BasicOcspResponse {
+            responder_id,
+            produced_at,
+            responses,
+            tbs_bytes,
+            signature_algorithm,
+            signature_value,
+            certs,
+            raw,
+        })
+    }
+
+    // a BasicOCSPResponse, or a full OCSPResponse as responders send it
+    pub fn from_der(der: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let der = Bytes::copy_from_slice(der);
+        let basic = Constructed::decode(der.clone(), Mode::Ber, |cons| {
+            cons.take_sequence(|cons| {
+                let status = cons.take_opt_primitive_if(Tag::ENUMERATED, |content| content.to_u8())?;
+                if status.is_none() {
+                    cons.skip_all()?;
+                    return Ok(None);
+                }
+                if status != Some(0) {
+                    return Err(DecodeError::content(format!("OCSP response status {}", status.unwrap_or_default()), decode::Pos::default()));
+                }
+                // responseBytes [0] EXPLICIT, the response type has to be id-pkix-ocsp-basic
+                cons.take_constructed_if(Tag::CTX_0, |cons| {
+                    cons.take_sequence(|cons| {
+                        let response_type = Oid::take_from(cons)?;
+                        if response_type != oid::OCSP_BASIC {
+                            return Err(DecodeError::content("not a basic OCSP response", decode::Pos::default()));
+                        }
+                        Ok(Some(OctetString::take_from(cons)?.to_bytes()))
+                    })
+                })
+            })
+        }).map_err(|err| format!("Error decoding OCSP response: {}", err))?;
+
+        let response = Constructed::decode(basic.unwrap_or(der), Mode::Ber, BasicOcspResponse::take_from)
+            .map_err(|err| format!("Error decoding OCSP response: {}", err))?;
+        Ok(response)
+    }
+
+    pub fn verify_signed_by(&self, responder: &Certificate) -> Result<(), SignatureError> {
+        signature::verify_signature(
+            &responder.tbs_certificate.subject_public_key_info,
+            &self.signature_algorithm,
+            None,
+            &self.tbs_bytes,
+            &self.signature_value,
+        )
+    }
+
+    // the CA itself, or a certificate it issued for OCSP signing (RFC 6960 section 4.2.2.2)
+    pub fn responder<'a>(&'a self, issuer: &'a Certificate) -> Option<&'a Certificate> {
+        if self.responder_id.matches(issuer) {
+            return Some(issuer);
+        }
+        self.certs.iter().find(|cert| {
+            self.responder_id.matches(cert)
+                && cert.tbs_certificate.issuer == issuer.tbs_certificate.subject
+                && cert.verify_signed_by(issuer).is_ok()
+                && cert.tbs_certificate.find_extension(oid::EXT_KEY_USAGE)
+                    .and_then(|ext| extensions::ext_key_usage(&ext.extn_value))
+                    .is_some_and(|purposes| purposes.iter().any(|p| *p == oid::KP_OCSP_SIGNING))
+        })
+    }
+
+    // the status of cert as issued by issuer, when this response covers it
+    pub fn find(&self, cert: &Certificate, issuer: &Certificate) -> Option<&SingleResponse> {
+        self.responses.iter().find(|response| response.cert_id.matches(cert, issuer))
+    }
+}
+
+impl ResponderId {
+    // byName [1] and byKey [2], both EXPLICIT
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_value(|tag, content| {
+            if tag == Tag::CTX_1 {
+                let name = content.as_constructed()?.take_sequence(|cons| Ok(cons.capture_all()?.into_bytes()))?;
+                Ok(ResponderId::ByName(name))
+            } else if tag == Tag::CTX_2 {
+                let key_hash = OctetString::take_from(content.as_constructed()?)?;
+                Ok(ResponderId::ByKey(key_hash.to_bytes().to_vec()))
+            } else {
+                Err(content.content_err("unexpected ResponderID"))
+            }
+        })
+    }
+
+    pub fn matches(&self, cert: &Certificate) -> bool {
+        match self {
+            ResponderId::ByName(name) => *name == cert.tbs_certificate.subject,
+            ResponderId::ByKey(hash) => HashAlg::Sha1.digest(&cert.tbs_certificate.subject_public_key_info.subject_public_key) == *hash,
+        }
+    }
+}
+
+impl std::fmt::Display for ResponderId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ResponderId::ByName(subject) => f.pad(&name::to_string(subject)),
+            ResponderId::ByKey(hash) => f.pad(&format!("key {}", hex::encode(hash))),
+        }
+    }
+}
+
+impl CertId {
+    pub fn matches(&self, cert: &Certificate, issuer: &Certificate) -> bool {
+        let Some(hash) = HashAlg::from_oid(&self.hash_algorithm.algorithm) else {
+            return false;
+        };
+        // the whole issuer Name, tag and length included
+        let issuer_name = to_der(bcder::encode::sequence(RawDer(&issuer.tbs_certificate.subject)));
+        self.serial_number == cert.tbs_certificate.serial_number
+            && self.issuer_name_hash == hash.digest(&issuer_name)
+            && self.issuer_key_hash == hash.digest(&issuer.tbs_certificate.subject_public_key_info.subject_public_key)
+    }
+}
+
+fn take_response_data_from<S: decode::Source>(
+    cons: &mut Constructed<S>,
+) -> Result<(ResponderId, i64, Vec<SingleResponse>), DecodeError<S::Error>> {
+    cons.take_sequence(|cons| {
+        cons.take_opt_constructed_if(Tag::CTX_0, |cons| cons.skip_all())?; // version, v1 only
+        let responder_id = ResponderId::take_from(cons)?;
+        let (produced_at, _) = Validity::take_time_from(cons)?;
+        let responses = cons.take_sequence(|cons| {
+            let mut responses = Vec::new();
+            while let Some(response) = cons.take_opt_sequence(take_single_response_from)? {
+                responses.push(response);
+            }
+            Ok(responses)
+        })?;
+        cons.skip_all()?; // responseExtensions
+        Ok((responder_id, produced_at, responses))
+    })
+}
+
+fn take_single_response_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<SingleResponse, DecodeError<S::Error>> {
+    let cert_id = cons.take_sequence(|cons| {
+        let hash_algorithm = AlgorithmIdentifier::take_from(cons)?;
+        let issuer_name_hash = OctetString::take_from(cons)?.to_bytes().to_vec();
+        let issuer_key_hash = OctetString::take_from(cons)?.to_bytes().to_vec();
+        let serial_number = cons.take_primitive_if(Tag::INTEGER, |content| Ok(hex::encode(content.take_all()?)))?;
+        Ok(CertId { hash_algorithm, issuer_name_hash, issuer_key_hash, serial_number })
+    })?;
+    // good [0] and unknown [2] are IMPLICIT NULL, revoked [1] an IMPLICIT RevokedInfo
+    let status = cons.take_value(|tag, content| {
+        if tag == Tag::CTX_0 {
+            content.as_primitive()?.skip_all()?;
+            Ok(CertStatus::Good)
+        } else if tag == Tag::CTX_1 {
+            let cons = content.as_constructed()?;
+            let (at, _) = Validity::take_time_from(cons)?;
+            cons.skip_all()?; // revocationReason
+            Ok(CertStatus::Revoked { at })
+        } else if tag == Tag::CTX_2 {
+            content.as_primitive()?.skip_all()?;
+            Ok(CertStatus::Unknown)
+        } else {
+            Err(content.content_err("unexpected CertStatus"))
+        }
+    })?;
+    let (this_update, _) = Validity::take_time_from(cons)?;
+    let next_update = cons.take_opt_constructed_if(Tag::CTX_0, |cons| Ok(Validity::take_time_from(cons)?.0))?;
+    cons.skip_all()?; // singleExtensions
+    Ok(SingleResponse { cert_id, status, this_update, next_update })
+}
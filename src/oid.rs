@@ -0,0 +1,483 @@
+// object identifiers used by the parser, stored as DER content bytes
+
+use std::sync::RwLock;
+
+use bcder::{ConstOid, Oid};
+use bytes::Bytes;
+
+// for building structures that hold an owned Oid
+pub fn owned(oid: ConstOid) -> Oid {
+    Oid(Bytes::from_static(oid.0))
+}
+
+// cms content types
+pub const DATA: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 7, 1]);
+pub const SIGNED_DATA: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 7, 2]);
+pub const ENVELOPED_DATA: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 7, 3]);
+pub const AUTH_ENVELOPED_DATA: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 1, 23]);
+pub const ENCRYPTED_DATA: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 7, 6]);
+
+// digest algorithms
+pub const SHA1: ConstOid = Oid(&[43, 14, 3, 2, 26]);
+pub const SHA224: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 2, 4]);
+pub const SHA256: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 2, 1]);
+pub const SHA384: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 2, 2]);
+pub const SHA512: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 2, 3]);
+pub const HMAC_WITH_SHA1: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 2, 7]);
+pub const HMAC_WITH_SHA224: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 2, 8]);
+pub const HMAC_WITH_SHA256: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 2, 9]);
+pub const HMAC_WITH_SHA384: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 2, 10]);
+pub const HMAC_WITH_SHA512: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 2, 11]);
+
+// public key, key transport and signature algorithms
+pub const RSA_ENCRYPTION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 1]);
+pub const RSAES_OAEP: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 7]);
+pub const RSASSA_PSS: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 10]);
+pub const MGF1: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 8]);
+pub const EC_PUBLIC_KEY: ConstOid = Oid(&[42, 134, 72, 206, 61, 2, 1]);
+pub const SECP256R1: ConstOid = Oid(&[42, 134, 72, 206, 61, 3, 1, 7]);
+pub const SECP384R1: ConstOid = Oid(&[43, 129, 4, 0, 34]);
+
+pub const SHA1_WITH_RSA_ENCRYPTION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 5]);
+pub const SHA256_WITH_RSA_ENCRYPTION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 11]);
+pub const SHA384_WITH_RSA_ENCRYPTION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 12]);
+pub const SHA512_WITH_RSA_ENCRYPTION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 13]);
+pub const SHA224_WITH_RSA_ENCRYPTION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 14]);
+pub const ECDSA_WITH_SHA1: ConstOid = Oid(&[42, 134, 72, 206, 61, 4, 1]);
+pub const ECDSA_WITH_SHA224: ConstOid = Oid(&[42, 134, 72, 206, 61, 4, 3, 1]);
+pub const ECDSA_WITH_SHA256: ConstOid = Oid(&[42, 134, 72, 206, 61, 4, 3, 2]);
+pub const ECDSA_WITH_SHA384: ConstOid = Oid(&[42, 134, 72, 206, 61, 4, 3, 3]);
+pub const ECDSA_WITH_SHA512: ConstOid = Oid(&[42, 134, 72, 206, 61, 4, 3, 4]);
+
+// ecdh key agreement schemes (RFC 5753)
+pub const DH_SINGLE_PASS_STD_DH_SHA1KDF: ConstOid = Oid(&[43, 129, 5, 16, 134, 72, 63, 0, 2]);
+pub const DH_SINGLE_PASS_STD_DH_SHA224KDF: ConstOid = Oid(&[43, 129, 4, 1, 11, 0]);
+pub const DH_SINGLE_PASS_STD_DH_SHA256KDF: ConstOid = Oid(&[43, 129, 4, 1, 11, 1]);
+pub const DH_SINGLE_PASS_STD_DH_SHA384KDF: ConstOid = Oid(&[43, 129, 4, 1, 11, 2]);
+pub const DH_SINGLE_PASS_STD_DH_SHA512KDF: ConstOid = Oid(&[43, 129, 4, 1, 11, 3]);
+pub const DH_SINGLE_PASS_COFACTOR_DH_SHA1KDF: ConstOid = Oid(&[43, 129, 5, 16, 134, 72, 63, 0, 3]);
+pub const DH_SINGLE_PASS_COFACTOR_DH_SHA224KDF: ConstOid = Oid(&[43, 129, 4, 1, 14, 0]);
+pub const DH_SINGLE_PASS_COFACTOR_DH_SHA256KDF: ConstOid = Oid(&[43, 129, 4, 1, 14, 1]);
+pub const DH_SINGLE_PASS_COFACTOR_DH_SHA384KDF: ConstOid = Oid(&[43, 129, 4, 1, 14, 2]);
+pub const DH_SINGLE_PASS_COFACTOR_DH_SHA512KDF: ConstOid = Oid(&[43, 129, 4, 1, 14, 3]);
+
+// symmetric ciphers and key wrap
+pub const AES128_CBC: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 2]);
+pub const AES192_CBC: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 22]);
+pub const AES256_CBC: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 42]);
+pub const AES128_GCM: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 6]);
+pub const AES192_GCM: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 26]);
+pub const AES256_GCM: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 46]);
+pub const AES128_WRAP: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 5]);
+pub const AES192_WRAP: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 25]);
+pub const AES256_WRAP: ConstOid = Oid(&[96, 134, 72, 1, 101, 3, 4, 1, 45]);
+pub const DES_CBC: ConstOid = Oid(&[43, 14, 3, 2, 7]);
+pub const DES_EDE3_CBC: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 3, 7]);
+pub const RC2_CBC: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 3, 2]);
+
+// password based key derivation
+pub const PBKDF2: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 5, 12]);
+pub const PWRI_KEK: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 3, 9]);
+pub const PBES2: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 5, 13]);
+
+// PKCS#12 (RFC 7292): password based encryption, bag types and bag attributes
+pub const PBE_SHA1_RC4_128: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 1, 1]);
+pub const PBE_SHA1_3DES: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 1, 3]);
+pub const PBE_SHA1_2DES: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 1, 4]);
+pub const PBE_SHA1_RC2_128: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 1, 5]);
+pub const PBE_SHA1_RC2_40: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 1, 6]);
+pub const KEY_BAG: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 10, 1, 1]);
+pub const PKCS8_SHROUDED_KEY_BAG: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 10, 1, 2]);
+pub const CERT_BAG: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 10, 1, 3]);
+pub const CRL_BAG: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 10, 1, 4]);
+pub const SECRET_BAG: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 10, 1, 5]);
+pub const SAFE_CONTENTS_BAG: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 12, 10, 1, 6]);
+pub const X509_CERTIFICATE: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 22, 1]);
+pub const FRIENDLY_NAME: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 20]);
+pub const LOCAL_KEY_ID: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 21]);
+
+// name attributes
+pub const COMMON_NAME: ConstOid = Oid(&[85, 4, 3]);
+pub const SERIAL_NUMBER: ConstOid = Oid(&[85, 4, 5]);
+pub const COUNTRY_NAME: ConstOid = Oid(&[85, 4, 6]);
+pub const LOCALITY_NAME: ConstOid = Oid(&[85, 4, 7]);
+pub const STATE_OR_PROVINCE_NAME: ConstOid = Oid(&[85, 4, 8]);
+pub const ORGANIZATION_NAME: ConstOid = Oid(&[85, 4, 10]);
+pub const ORGANIZATIONAL_UNIT_NAME: ConstOid = Oid(&[85, 4, 11]);
+pub const EMAIL_ADDRESS: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 1]);
+
+// certificate extensions
+pub const SUBJECT_KEY_IDENTIFIER: ConstOid = Oid(&[85, 29, 14]);
+pub const KEY_USAGE: ConstOid = Oid(&[85, 29, 15]);
+pub const SUBJECT_ALT_NAME: ConstOid = Oid(&[85, 29, 17]);
+pub const BASIC_CONSTRAINTS: ConstOid = Oid(&[85, 29, 19]);
+pub const CRL_NUMBER: ConstOid = Oid(&[85, 29, 20]);
+pub const CRL_REASON: ConstOid = Oid(&[85, 29, 21]);
+pub const NAME_CONSTRAINTS: ConstOid = Oid(&[85, 29, 30]);
+pub const CRL_DISTRIBUTION_POINTS: ConstOid = Oid(&[85, 29, 31]);
+pub const CERTIFICATE_POLICIES: ConstOid = Oid(&[85, 29, 32]);
+pub const ANY_POLICY: ConstOid = Oid(&[85, 29, 32, 0]);
+pub const POLICY_MAPPINGS: ConstOid = Oid(&[85, 29, 33]);
+pub const AUTHORITY_KEY_IDENTIFIER: ConstOid = Oid(&[85, 29, 35]);
+pub const POLICY_CONSTRAINTS: ConstOid = Oid(&[85, 29, 36]);
+pub const EXT_KEY_USAGE: ConstOid = Oid(&[85, 29, 37]);
+pub const INHIBIT_ANY_POLICY: ConstOid = Oid(&[85, 29, 54]);
+pub const AUTHORITY_INFO_ACCESS: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 1, 1]);
+pub const QC_STATEMENTS: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 1, 3]);
+
+// ETSI EN 319 412-5 qualified certificate statements, 0.4.0.1862.1
+pub const QCS_COMPLIANCE: ConstOid = Oid(&[4, 0, 142, 70, 1, 1]);
+pub const QCS_LIMIT_VALUE: ConstOid = Oid(&[4, 0, 142, 70, 1, 2]);
+pub const QCS_RETENTION_PERIOD: ConstOid = Oid(&[4, 0, 142, 70, 1, 3]);
+pub const QCS_SSCD: ConstOid = Oid(&[4, 0, 142, 70, 1, 4]);
+pub const QCS_PDS: ConstOid = Oid(&[4, 0, 142, 70, 1, 5]);
+pub const QCS_TYPE: ConstOid = Oid(&[4, 0, 142, 70, 1, 6]);
+pub const QCT_ESIGN: ConstOid = Oid(&[4, 0, 142, 70, 1, 6, 1]);
+pub const QCT_ESEAL: ConstOid = Oid(&[4, 0, 142, 70, 1, 6, 2]);
+pub const QCT_WEB: ConstOid = Oid(&[4, 0, 142, 70, 1, 6, 3]);
+pub const QCS_CC_LEGISLATION: ConstOid = Oid(&[4, 0, 142, 70, 1, 7]);
+
+// extended key usage purposes
+pub const ANY_EXTENDED_KEY_USAGE: ConstOid = Oid(&[85, 29, 37, 0]);
+pub const KP_SERVER_AUTH: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 3, 1]);
+pub const KP_CLIENT_AUTH: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 3, 2]);
+pub const KP_CODE_SIGNING: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 3, 3]);
+pub const KP_EMAIL_PROTECTION: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 3, 4]);
+pub const KP_TIME_STAMPING: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 3, 8]);
+pub const KP_OCSP_SIGNING: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 3, 9]);
+pub const KP_DOCUMENT_SIGNING: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 3, 36]);
+
+// PKCS#9 signed attributes
+pub const CONTENT_TYPE: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 3]);
+pub const MESSAGE_DIGEST: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 4]);
+pub const SIGNING_TIME: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 5]);
+pub const SMIME_CAPABILITIES: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 15]);
+pub const CMS_ALGORITHM_PROTECTION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 52]);
+
+// ESS (RFC 2634, RFC 5035) and CAdES signed attributes
+pub const SIGNING_CERTIFICATE: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 12]);
+pub const SIGNING_CERTIFICATE_V2: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 47]);
+pub const COMMITMENT_TYPE_INDICATION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 16]);
+
+// commitment types (RFC 5126)
+pub const PROOF_OF_ORIGIN: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 6, 1]);
+pub const PROOF_OF_RECEIPT: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 6, 2]);
+pub const PROOF_OF_DELIVERY: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 6, 3]);
+pub const PROOF_OF_SENDER: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 6, 4]);
+pub const PROOF_OF_APPROVAL: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 6, 5]);
+pub const PROOF_OF_CREATION: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 6, 6]);
+
+// CAdES (ETSI EN 319 122-1) unsigned attributes
+pub const CERT_VALUES: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 23]);
+pub const REVOCATION_VALUES: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 24]);
+pub const CERTIFICATE_REFS: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 21]);
+pub const REVOCATION_REFS: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 22]);
+pub const ARCHIVE_TIME_STAMP_V3: ConstOid = Oid(&[4, 0, 141, 69, 2, 4]);
+pub const ATS_HASH_INDEX_V3: ConstOid = Oid(&[4, 0, 129, 149, 50, 1, 5]);
+
+// RFC 3161 time-stamping
+pub const SIGNATURE_TIME_STAMP_TOKEN: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 2, 14]);
+pub const TST_INFO: ConstOid = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 1, 4]);
+
+// OCSP (RFC 6960)
+pub const OCSP_BASIC: ConstOid = Oid(&[43, 6, 1, 5, 5, 7, 48, 1, 1]);
+
+// Microsoft Authenticode
+pub const SPC_INDIRECT_DATA: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 2, 1, 4]);
+pub const SPC_PE_IMAGE_DATA: ConstOid = Oid(&[43, 6, 1, 4, 1, 130, 55, 2, 1, 15]);
+
+// built-in names, short ones as openssl prints them
+const BUILT_IN: &[(ConstOid, &str, &str, OidKind)] = &[
+    (DATA, "pkcs7-data", "PKCS#7 data", OidKind::ContentType),
+    (SIGNED_DATA, "pkcs7-signedData", "PKCS#7 signed data", OidKind::ContentType),
+    (ENVELOPED_DATA, "pkcs7-envelopedData", "PKCS#7 enveloped data", OidKind::ContentType),
+    (AUTH_ENVELOPED_DATA, "id-ct-authEnvelopedData", "CMS authenticated enveloped data", OidKind::ContentType),
+    (ENCRYPTED_DATA, "pkcs7-encryptedData", "PKCS#7 encrypted data", OidKind::ContentType),
+    (TST_INFO, "id-smime-ct-TSTInfo", "RFC 3161 time-stamp token info", OidKind::ContentType),
+    (SHA1, "sha1", "SHA-1", OidKind::Digest),
+    (SHA224, "sha224", "SHA-224", OidKind::Digest),
+    (SHA256, "sha256", "SHA-256", OidKind::Digest),
+    (SHA384, "sha384", "SHA-384", OidKind::Digest),
+    (SHA512, "sha512", "SHA-512", OidKind::Digest),
+    (HMAC_WITH_SHA1, "hmacWithSHA1", "HMAC with SHA-1", OidKind::Mac),
+    (HMAC_WITH_SHA224, "hmacWithSHA224", "HMAC with SHA-224", OidKind::Mac),
+    (HMAC_WITH_SHA256, "hmacWithSHA256", "HMAC with SHA-256", OidKind::Mac),
+    (HMAC_WITH_SHA384, "hmacWithSHA384", "HMAC with SHA-384", OidKind::Mac),
+    (HMAC_WITH_SHA512, "hmacWithSHA512", "HMAC with SHA-512", OidKind::Mac),
+    (RSA_ENCRYPTION, "rsaEncryption", "RSA", OidKind::PublicKey),
+    (RSAES_OAEP, "rsaesOaep", "RSAES-OAEP key transport", OidKind::KeyManagement),
+    (RSASSA_PSS, "rsassaPss", "RSASSA-PSS signature", OidKind::Signature),
+    (MGF1, "mgf1", "mask generation function 1", OidKind::KeyManagement),
+    (EC_PUBLIC_KEY, "id-ecPublicKey", "elliptic curve public key", OidKind::PublicKey),
+    (SECP256R1, "prime256v1", "NIST P-256", OidKind::Curve),
+    (SECP384R1, "secp384r1", "NIST P-384", OidKind::Curve),
+    (SHA1_WITH_RSA_ENCRYPTION, "sha1WithRSAEncryption", "RSA PKCS#1 v1.5 with SHA-1", OidKind::Signature),
+    (SHA224_WITH_RSA_ENCRYPTION, "sha224WithRSAEncryption", "RSA PKCS#1 v1.5 with SHA-224", OidKind::Signature),
+    (SHA256_WITH_RSA_ENCRYPTION, "sha256WithRSAEncryption", "RSA PKCS#1 v1.5 with SHA-256", OidKind::Signature),
+    (SHA384_WITH_RSA_ENCRYPTION, "sha384WithRSAEncryption", "RSA PKCS#1 v1.5 with SHA-384", OidKind::Signature),
+    (SHA512_WITH_RSA_ENCRYPTION, "sha512WithRSAEncryption", "RSA PKCS#1 v1.5 with SHA-512", OidKind::Signature),
+    (ECDSA_WITH_SHA1, "ecdsa-with-SHA1", "ECDSA with SHA-1", OidKind::Signature),
+    (ECDSA_WITH_SHA224, "ecdsa-with-SHA224", "ECDSA with SHA-224", OidKind::Signature),
+    (ECDSA_WITH_SHA256, "ecdsa-with-SHA256", "ECDSA with SHA-256", OidKind::Signature),
+    (ECDSA_WITH_SHA384, "ecdsa-with-SHA384", "ECDSA with SHA-384", OidKind::Signature),
+    (ECDSA_WITH_SHA512, "ecdsa-with-SHA512", "ECDSA with SHA-512", OidKind::Signature),
+    (DH_SINGLE_PASS_STD_DH_SHA1KDF, "dhSinglePass-stdDH-sha1kdf-scheme", "ECDH standard with SHA-1 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_STD_DH_SHA224KDF, "dhSinglePass-stdDH-sha224kdf-scheme", "ECDH standard with SHA-224 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_STD_DH_SHA256KDF, "dhSinglePass-stdDH-sha256kdf-scheme", "ECDH standard with SHA-256 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_STD_DH_SHA384KDF, "dhSinglePass-stdDH-sha384kdf-scheme", "ECDH standard with SHA-384 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_STD_DH_SHA512KDF, "dhSinglePass-stdDH-sha512kdf-scheme", "ECDH standard with SHA-512 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_COFACTOR_DH_SHA1KDF, "dhSinglePass-cofactorDH-sha1kdf-scheme", "ECDH cofactor with SHA-1 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_COFACTOR_DH_SHA224KDF, "dhSinglePass-cofactorDH-sha224kdf-scheme", "ECDH cofactor with SHA-224 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_COFACTOR_DH_SHA256KDF, "dhSinglePass-cofactorDH-sha256kdf-scheme", "ECDH cofactor with SHA-256 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_COFACTOR_DH_SHA384KDF, "dhSinglePass-cofactorDH-sha384kdf-scheme", "ECDH cofactor with SHA-384 X9.63 KDF", OidKind::KeyManagement),
+    (DH_SINGLE_PASS_COFACTOR_DH_SHA512KDF, "dhSinglePass-cofactorDH-sha512kdf-scheme", "ECDH cofactor with SHA-512 X9.63 KDF", OidKind::KeyManagement),
+    (AES128_CBC, "aes-128-cbc", "AES-128 in CBC mode", OidKind::Cipher),
+    (AES192_CBC, "aes-192-cbc", "AES-192 in CBC mode", OidKind::Cipher),
+    (AES256_CBC, "aes-256-cbc", "AES-256 in CBC mode", OidKind::Cipher),
+    (AES128_GCM, "aes-128-gcm", "AES-128 in GCM mode", OidKind::Cipher),
+    (AES192_GCM, "aes-192-gcm", "AES-192 in GCM mode", OidKind::Cipher),
+    (AES256_GCM, "aes-256-gcm", "AES-256 in GCM mode", OidKind::Cipher),
+    (AES128_WRAP, "id-aes128-wrap", "AES-128 key wrap", OidKind::KeyManagement),
+    (AES192_WRAP, "id-aes192-wrap", "AES-192 key wrap", OidKind::KeyManagement),
+    (AES256_WRAP, "id-aes256-wrap", "AES-256 key wrap", OidKind::KeyManagement),
+    (DES_CBC, "des-cbc", "DES in CBC mode", OidKind::Cipher),
+    (DES_EDE3_CBC, "des-ede3-cbc", "triple DES in CBC mode", OidKind::Cipher),
+    (RC2_CBC, "rc2-cbc", "RC2 in CBC mode", OidKind::Cipher),
+    (PBKDF2, "PBKDF2", "PKCS#5 password based key derivation 2", OidKind::KeyManagement),
+    (PWRI_KEK, "id-alg-PWRI-KEK", "password recipient key wrap", OidKind::KeyManagement),
+    (PBES2, "PBES2", "PKCS#5 password based encryption scheme 2", OidKind::KeyManagement),
+    (PBE_SHA1_RC4_128, "pbeWithSHA1And128BitRC4", "PKCS#12 PBE with SHA-1 and 128 bit RC4", OidKind::KeyManagement),
+    (PBE_SHA1_3DES, "pbeWithSHA1And3-KeyTripleDES-CBC", "PKCS#12 PBE with SHA-1 and 3 key triple DES", OidKind::KeyManagement),
+    (PBE_SHA1_2DES, "pbeWithSHA1And2-KeyTripleDES-CBC", "PKCS#12 PBE with SHA-1 and 2 key triple DES", OidKind::KeyManagement),
+    (PBE_SHA1_RC2_128, "pbeWithSHA1And128BitRC2-CBC", "PKCS#12 PBE with SHA-1 and 128 bit RC2", OidKind::KeyManagement),
+    (PBE_SHA1_RC2_40, "pbeWithSHA1And40BitRC2-CBC", "PKCS#12 PBE with SHA-1 and 40 bit RC2", OidKind::KeyManagement),
+    (KEY_BAG, "keyBag", "PKCS#12 key bag", OidKind::Other),
+    (PKCS8_SHROUDED_KEY_BAG, "pkcs8ShroudedKeyBag", "PKCS#12 shrouded key bag", OidKind::Other),
+    (CERT_BAG, "certBag", "PKCS#12 certificate bag", OidKind::Other),
+    (CRL_BAG, "crlBag", "PKCS#12 CRL bag", OidKind::Other),
+    (SECRET_BAG, "secretBag", "PKCS#12 secret bag", OidKind::Other),
+    (SAFE_CONTENTS_BAG, "safeContentsBag", "PKCS#12 safe contents bag", OidKind::Other),
+    (X509_CERTIFICATE, "x509Certificate", "X.509 certificate in a PKCS#12 bag", OidKind::Other),
+    (FRIENDLY_NAME, "friendlyName", "PKCS#9 friendly name", OidKind::Attribute),
+    (LOCAL_KEY_ID, "localKeyID", "PKCS#9 local key identifier", OidKind::Attribute),
+    (COMMON_NAME, "CN", "common name", OidKind::NameAttribute),
+    (SERIAL_NUMBER, "serialNumber", "serial number", OidKind::NameAttribute),
+    (COUNTRY_NAME, "C", "country", OidKind::NameAttribute),
+    (LOCALITY_NAME, "L", "locality", OidKind::NameAttribute),
+    (STATE_OR_PROVINCE_NAME, "ST", "state or province", OidKind::NameAttribute),
+    (ORGANIZATION_NAME, "O", "organization", OidKind::NameAttribute),
+    (ORGANIZATIONAL_UNIT_NAME, "OU", "organizational unit", OidKind::NameAttribute),
+    (EMAIL_ADDRESS, "emailAddress", "e-mail address", OidKind::NameAttribute),
+    (SUBJECT_KEY_IDENTIFIER, "subjectKeyIdentifier", "subject key identifier", OidKind::Extension),
+    (KEY_USAGE, "keyUsage", "key usage", OidKind::Extension),
+    (SUBJECT_ALT_NAME, "subjectAltName", "subject alternative name", OidKind::Extension),
+    (BASIC_CONSTRAINTS, "basicConstraints", "basic constraints", OidKind::Extension),
+    (CRL_NUMBER, "crlNumber", "CRL number", OidKind::Extension),
+    (CRL_REASON, "CRLReason", "CRL reason code", OidKind::Extension),
+    (NAME_CONSTRAINTS, "nameConstraints", "name constraints", OidKind::Extension),
+    (CRL_DISTRIBUTION_POINTS, "crlDistributionPoints", "CRL distribution points", OidKind::Extension),
+    (CERTIFICATE_POLICIES, "certificatePolicies", "certificate policies", OidKind::Extension),
+    (ANY_POLICY, "anyPolicy", "any certificate policy", OidKind::Other),
+    (POLICY_MAPPINGS, "policyMappings", "policy mappings", OidKind::Extension),
+    (POLICY_CONSTRAINTS, "policyConstraints", "policy constraints", OidKind::Extension),
+    (INHIBIT_ANY_POLICY, "inhibitAnyPolicy", "inhibit any policy", OidKind::Extension),
+    (AUTHORITY_KEY_IDENTIFIER, "authorityKeyIdentifier", "authority key identifier", OidKind::Extension),
+    (EXT_KEY_USAGE, "extendedKeyUsage", "extended key usage", OidKind::Extension),
+    (AUTHORITY_INFO_ACCESS, "authorityInfoAccess", "authority information access", OidKind::Extension),
+    (QC_STATEMENTS, "qcStatements", "qualified certificate statements", OidKind::Extension),
+    (QCS_COMPLIANCE, "QcCompliance", "EU qualified certificate", OidKind::Other),
+    (QCS_LIMIT_VALUE, "QcLimitValue", "transaction value limit", OidKind::Other),
+    (QCS_RETENTION_PERIOD, "QcRetentionPeriod", "registration data retention period", OidKind::Other),
+    (QCS_SSCD, "QcSSCD", "private key in a qualified signature creation device", OidKind::Other),
+    (QCS_PDS, "QcPDS", "PKI disclosure statements", OidKind::Other),
+    (QCS_TYPE, "QcType", "qualified certificate type", OidKind::Other),
+    (QCT_ESIGN, "esign", "qualified certificate for electronic signatures", OidKind::Other),
+    (QCT_ESEAL, "eseal", "qualified certificate for electronic seals", OidKind::Other),
+    (QCT_WEB, "web", "qualified certificate for website authentication", OidKind::Other),
+    (QCS_CC_LEGISLATION, "QcCClegislation", "countries of the qualified certificate legislation", OidKind::Other),
+    (ANY_EXTENDED_KEY_USAGE, "anyExtendedKeyUsage", "any extended key usage", OidKind::KeyPurpose),
+    (KP_SERVER_AUTH, "serverAuth", "TLS server authentication", OidKind::KeyPurpose),
+    (KP_CLIENT_AUTH, "clientAuth", "TLS client authentication", OidKind::KeyPurpose),
+    (KP_CODE_SIGNING, "codeSigning", "code signing", OidKind::KeyPurpose),
+    (KP_EMAIL_PROTECTION, "emailProtection", "e-mail protection", OidKind::KeyPurpose),
+    (KP_TIME_STAMPING, "timeStamping", "time stamping", OidKind::KeyPurpose),
+    (KP_OCSP_SIGNING, "OCSPSigning", "OCSP signing", OidKind::KeyPurpose),
+    (KP_DOCUMENT_SIGNING, "documentSigning", "document signing", OidKind::KeyPurpose),
+    (CONTENT_TYPE, "contentType", "content type", OidKind::Attribute),
+    (MESSAGE_DIGEST, "messageDigest", "message digest", OidKind::Attribute),
+    (SIGNING_TIME, "signingTime", "signing time", OidKind::Attribute),
+    (SMIME_CAPABILITIES, "smimeCapabilities", "S/MIME capabilities", OidKind::Attribute),
+    (CMS_ALGORITHM_PROTECTION, "id-aa-CMSAlgorithmProtection", "CMS algorithm protection", OidKind::Attribute),
+    (SIGNING_CERTIFICATE, "id-smime-aa-signingCertificate", "ESS signing certificate", OidKind::Attribute),
+    (SIGNING_CERTIFICATE_V2, "id-smime-aa-signingCertificateV2", "ESS signing certificate v2", OidKind::Attribute),
+    (COMMITMENT_TYPE_INDICATION, "id-aa-ets-commitmentType", "commitment type indication", OidKind::Attribute),
+    (CERT_VALUES, "id-aa-ets-certValues", "certificate values", OidKind::Attribute),
+    (REVOCATION_VALUES, "id-aa-ets-revocationValues", "revocation values", OidKind::Attribute),
+    (CERTIFICATE_REFS, "id-aa-ets-certificateRefs", "complete certificate references", OidKind::Attribute),
+    (REVOCATION_REFS, "id-aa-ets-revocationRefs", "complete revocation references", OidKind::Attribute),
+    (ARCHIVE_TIME_STAMP_V3, "id-aa-ets-archiveTimestampV3", "archive time-stamp v3", OidKind::Attribute),
+    (ATS_HASH_INDEX_V3, "id-aa-ATSHashIndex-v3", "archive time-stamp hash index v3", OidKind::Attribute),
+    (PROOF_OF_ORIGIN, "id-cti-ets-proofOfOrigin", "proof of origin", OidKind::Other),
+    (PROOF_OF_RECEIPT, "id-cti-ets-proofOfReceipt", "proof of receipt", OidKind::Other),
+    (PROOF_OF_DELIVERY, "id-cti-ets-proofOfDelivery", "proof of delivery", OidKind::Other),
+    (PROOF_OF_SENDER, "id-cti-ets-proofOfSender", "proof of sender", OidKind::Other),
+    (PROOF_OF_APPROVAL, "id-cti-ets-proofOfApproval", "proof of approval", OidKind::Other),
+    (PROOF_OF_CREATION, "id-cti-ets-proofOfCreation", "proof of creation", OidKind::Other),
+    (SIGNATURE_TIME_STAMP_TOKEN, "id-smime-aa-timeStampToken", "signature time-stamp token", OidKind::Attribute),
+    (OCSP_BASIC, "basicOCSPResponse", "basic OCSP response", OidKind::Other),
+    (SPC_INDIRECT_DATA, "SPC_INDIRECT_DATA_OBJID", "Authenticode indirect data", OidKind::ContentType),
+    (SPC_PE_IMAGE_DATA, "SPC_PE_IMAGE_DATAOBJ", "Authenticode PE image data", OidKind::Other),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OidKind {
+    ContentType,
+    Attribute,
+    Digest,
+    Mac,
+    Signature,
+    PublicKey,
+    Curve,
+    Cipher,
+    KeyManagement, // key transport, agreement, wrap and derivation
+    Extension,
+    KeyPurpose,
+    NameAttribute,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct OidInfo {
+    pub oid: Oid,
+    pub name: String,
+    pub description: String,
+    pub kind: OidKind,
+}
+
+// registered at run time, looked up before the built-in table so they can also rename
+static REGISTERED: RwLock<Vec<OidInfo>> = RwLock::new(Vec::new());
+
+// adds a private OID, or replaces the name of a known one
+pub fn register(oid: Oid, name: &str, description: &str, kind: OidKind) {
+    let mut registered = REGISTERED.write().unwrap_or_else(|e| e.into_inner());
+    registered.retain(|info| info.oid != oid);
+    registered.push(OidInfo {
+        oid,
+        name: name.to_string(),
+        description: description.to_string(),
+        kind,
+    });
+}
+
+// "1.3.6.1.4.1.99999.1 myAttribute some description", one per line, # starts a comment
+pub fn register_file(path: &str) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut count = 0;
+    for line in std::fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.splitn(3, char::is_whitespace);
+        let dotted = fields.next().unwrap_or_default();
+        let name = fields.next().ok_or_else(|| format!("{}: missing name", dotted))?;
+        let description = fields.next().unwrap_or(name).trim();
+        register(from_dotted(dotted)?, name, description, OidKind::Other);
+        count += 1;
+    }
+    Ok(count)
+}
+
+pub fn lookup<T: AsRef<[u8]>>(oid: &Oid<T>) -> Option<OidInfo> {
+    let registered = REGISTERED.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(info) = registered.iter().find(|info| info.oid.as_ref() == oid.as_ref()) {
+        return Some(info.clone());
+    }
+    BUILT_IN.iter().find(|(known, ..)| known.0 == oid.as_ref()).map(|(known, name, description, kind)| OidInfo {
+        oid: owned(known.clone()),
+        name: name.to_string(),
+        description: description.to_string(),
+        kind: *kind,
+    })
+}
+
+// reverse lookup, "sha256" -> 2.16.840.1.101.3.4.2.1
+pub fn by_name(name: &str) -> Option<Oid> {
+    let registered = REGISTERED.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(info) = registered.iter().find(|info| info.name == name) {
+        return Some(info.oid.clone());
+    }
+    BUILT_IN.iter().find(|(_, known, ..)| *known == name).map(|(oid, ..)| owned(oid.clone()))
+}
+
+pub fn name<T: AsRef<[u8]>>(oid: &Oid<T>) -> Option<String> {
+    lookup(oid).map(|info| info.name)
+}
+
+pub fn kind<T: AsRef<[u8]>>(oid: &Oid<T>) -> OidKind {
+    lookup(oid).map_or(OidKind::Other, |info| info.kind)
+}
+
+// the short name, the dotted form for unknown ones
+pub fn describe<T: AsRef<[u8]>>(oid: &Oid<T>) -> String {
+    name(oid).unwrap_or_else(|| oid.to_string())
+}
+
+// dotted decimal to the DER content bytes
+pub fn from_dotted(dotted: &str) -> Result<Oid, String> {
+    let arcs = dotted.split('.')
+        .map(|arc| arc.parse::<u64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("bad OID {}", dotted))?;
+    if arcs.len() < 2 || arcs[0] > 2 || (arcs[0] < 2 && arcs[1] > 39) {
+        return Err(format!("bad OID {}", dotted));
+    }
+    let mut bytes = Vec::new();
+    let first = arcs[0].checked_mul(40).and_then(|a| a.checked_add(arcs[1])).ok_or_else(|| format!("bad OID {}", dotted))?;
+    for arc in std::iter::once(first).chain(arcs[2..].iter().copied()) {
+        // base 128, high bit set on all but the last byte
+        let mut encoded = vec![(arc & 0x7f) as u8];
+        let mut rest = arc >> 7;
+        while rest > 0 {
+            encoded.push((rest & 0x7f) as u8 | 0x80);
+            rest >>= 7;
+        }
+        bytes.extend(encoded.iter().rev());
+    }
+    Ok(Oid(Bytes::from(bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotted() {
+        assert_eq!(from_dotted("2.16.840.1.101.3.4.2.1").unwrap(), owned(SHA256));
+        assert_eq!(from_dotted("1.2.840.113549.1.7.2").unwrap().to_string(), "1.2.840.113549.1.7.2");
+        for bad in ["", "1", "1.x", "3.1", "1.40", "1..2"] {
+            assert!(from_dotted(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn built_in() {
+        assert_eq!(describe(&SHA256), "sha256");
+        assert_eq!(kind(&SHA256), OidKind::Digest);
+        assert_eq!(by_name("sha256"), Some(owned(SHA256)));
+        assert_eq!(describe(&from_dotted("1.2.3.4.5").unwrap()), "1.2.3.4.5");
+        assert_eq!(kind(&from_dotted("1.2.3.4.5").unwrap()), OidKind::Other);
+    }
+
+    #[test]
+    fn registered_from_file() {
+        let path = std::env::temp_dir().join(format!("p7b_verifier_oids_{}", std::process::id()));
+        std::fs::write(&path, "# private arcs\n1.3.6.1.4.1.99999.1 acmeAttribute Acme attribute\n\n1.3.6.1.4.1.99999.2 acmeOther\n").unwrap();
+        let count = register_file(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(count.unwrap(), 2);
+        let info = lookup(&from_dotted("1.3.6.1.4.1.99999.1").unwrap()).unwrap();
+        assert_eq!((info.name.as_str(), info.description.as_str()), ("acmeAttribute", "Acme attribute"));
+        assert_eq!(lookup(&from_dotted("1.3.6.1.4.1.99999.2").unwrap()).unwrap().description, "acmeOther");
+        assert_eq!(by_name("acmeOther"), Some(from_dotted("1.3.6.1.4.1.99999.2").unwrap()));
+    }
+}
@@ -0,0 +1,526 @@
+// RFC 5280 section 6.1 path processing beyond signatures, validity and basicConstraints: the
+// nameConstraints of the CAs and the certificate policies the path is valid for
+
+use std::net::IpAddr;
+
+use bcder::Oid;
+use bytes::Bytes;
+
+use crate::{extensions, name, oid};
+use crate::Certificate;
+
+// a GeneralSubtree base (RFC 5280 section 4.2.1.10)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameSubtree {
+    Dns(String), // the host and its subdomains
+    Email(String), // a mailbox, every mailbox of a host, or of the subdomains of .domain
+    Directory(Vec<u8>), // Name content, subjects whose RDNs start with it
+    Uri(String), // the host of the URI, or the subdomains of .domain
+    Ip(Vec<u8>), // address then mask, 8 or 32 bytes
+    Other(String), // otherName, x400Address, ediPartyName, registeredID: no names of these are checked
+}
+
+// the names of a certificate a subtree can apply to
+#[derive(Debug)]
+pub enum SubjectName {
+    Dns(String),
+    Email(String),
+    Directory(Bytes),
+    Uri(String),
+    Ip(Vec<u8>),
+}
+
+// what path processing starts with (RFC 5280 section 6.1.1)
+#[derive(Debug, Clone, Default)]
+pub struct PolicyOptions {
+    pub initial_policies: Option<Vec<Oid>>, // user-initial-policy-set, anyPolicy when None
+    pub explicit_policy: bool, // initial-explicit-policy: the path has to be valid for one of them
+    pub inhibit_policy_mapping: bool,
+    pub inhibit_any_policy: bool,
+}
+
+impl std::fmt::Display for SubjectName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SubjectName::Dns(dns) => write!(f, "DNS:{}", dns),
+            SubjectName::Email(email) => write!(f, "email:{}", email),
+            SubjectName::Directory(subject) => write!(f, "DirName:{}", name::to_string(subject)),
+            SubjectName::Uri(uri) => write!(f, "URI:{}", uri),
+            SubjectName::Ip(ip) => write!(f, "IP Address:{}", ip_to_string(ip)),
+        }
+    }
+}
+
+// the subject, its emailAddress attributes and the DNS, email, URI and IP subjectAltNames
+pub fn subject_names(cert: &Certificate) -> Vec<SubjectName> {
+    let tbs = &cert.tbs_certificate;
+    let mut names = Vec::new();
+    if !tbs.subject.is_empty() {
+        names.push(SubjectName::Directory(tbs.subject.clone()));
+    }
+    for (attribute_type, value) in name::entries(&tbs.subject) {
+        if attribute_type == oid::EMAIL_ADDRESS {
+            names.push(SubjectName::Email(value));
+        }
+    }
+    let alt_names = tbs.find_extension(oid::SUBJECT_ALT_NAME)
+        .and_then(|extension| extensions::general_names(&extension.extn_value))
+        .unwrap_or_default();
+    for alt_name in alt_names {
+        if let Some(email) = alt_name.strip_prefix("email:") {
+            names.push(SubjectName::Email(email.to_string()));
+        } else if let Some(dns) = alt_name.strip_prefix("DNS:") {
+            names.push(SubjectName::Dns(dns.to_string()));
+        } else if let Some(uri) = alt_name.strip_prefix("URI:") {
+            names.push(SubjectName::Uri(uri.to_string()));
+        } else if let Some(ip) = alt_name.strip_prefix("IP Address:").and_then(|ip| ip.parse::<IpAddr>().ok()) {
+            names.push(SubjectName::Ip(match ip {
+                IpAddr::V4(ip) => ip.octets().to_vec(),
+                IpAddr::V6(ip) => ip.octets().to_vec(),
+            }));
+        }
+    }
+    names
+}
+
+impl NameSubtree {
+    // None when the subtree is of another kind than the name
+    pub fn matches(&self, name: &SubjectName) -> Option<bool> {
+        match (self, name) {
+            (NameSubtree::Dns(base), SubjectName::Dns(dns)) => Some(dns_in(dns, base)),
+            (NameSubtree::Email(base), SubjectName::Email(email)) => {
+                let Some((_, host)) = email.rsplit_once('@') else {
+                    return Some(false);
+                };
+                Some(match base {
+                    _ if base.contains('@') => email.eq_ignore_ascii_case(base),
+                    _ if base.starts_with('.') => dns_in(host, base) && !host.eq_ignore_ascii_case(&base[1..]),
+                    _ => host.eq_ignore_ascii_case(base),
+                })
+            },
+            // RDN by RDN, the encodings are compared as they are
+            (NameSubtree::Directory(base), SubjectName::Directory(subject)) => Some(subject.starts_with(base)),
+            // a URI without a host does not match any subtree
+            (NameSubtree::Uri(base), SubjectName::Uri(uri)) => Some(uri_host(uri).is_some_and(|host| match base.starts_with('.') {
+                true => dns_in(host, base),
+                false => host.eq_ignore_ascii_case(base),
+            })),
+            (NameSubtree::Ip(base), SubjectName::Ip(ip)) => Some(base.len() == 2 * ip.len() && {
+                let (address, mask) = base.split_at(ip.len());
+                ip.iter().zip(address).zip(mask).all(|((ip, address), mask)| ip & mask == address & mask)
+            }),
+            _ => None,
+        }
+    }
+}
+
+// the names of the certificate against permitted and excluded subtrees, `by` says whose they are;
+// a kind of name without permitted subtrees is not restricted
+pub fn check_names(cert: &Certificate, permitted: &[NameSubtree], excluded: &[NameSubtree], by: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+    let serial = &cert.tbs_certificate.serial_number;
+    for name in subject_names(cert) {
+        if excluded.iter().any(|subtree| subtree.matches(&name) == Some(true)) {
+            errors.push(format!("certificate {}: {} is excluded by {}", serial, name, by));
+        }
+        let permitted: Vec<bool> = permitted.iter().filter_map(|subtree| subtree.matches(&name)).collect();
+        if !permitted.is_empty() && !permitted.contains(&true) {
+            errors.push(format!("certificate {}: {} is not permitted by {}", serial, name, by));
+        }
+    }
+    errors
+}
+
+// the nameConstraints of every CA over the certificates below it; the path goes from the certificate
+// the anchor issued down to the signer, self-issued intermediates are not checked (section 6.1.3 (b))
+pub fn check_name_constraints(path: &[&Certificate]) -> Vec<String> {
+    let mut errors = Vec::new();
+    for (i, ca) in path.iter().enumerate() {
+        let Some(extension) = ca.tbs_certificate.find_extension(oid::NAME_CONSTRAINTS) else {
+            continue;
+        };
+        let serial = &ca.tbs_certificate.serial_number;
+        let Some((permitted, excluded)) = extensions::name_constraints(&extension.extn_value) else {
+            errors.push(format!("certificate {}: nameConstraints cannot be decoded", serial));
+            continue;
+        };
+        let by = format!("the nameConstraints of certificate {}", serial);
+        for (j, cert) in path.iter().enumerate().skip(i + 1) {
+            if j + 1 < path.len() && is_self_issued(cert) {
+                continue;
+            }
+            errors.extend(check_names(cert, &permitted, &excluded, &by));
+        }
+    }
+    errors
+}
+
+// the valid_policy_tree of section 6.1, one level per depth
+struct PolicyTree {
+    levels: Vec<Vec<PolicyNode>>,
+}
+
+struct PolicyNode {
+    valid_policy: Oid,
+    expected_policies: Vec<Oid>,
+    parent: Option<usize>, // in the level above
+}
+
+// the certificate policies the path is valid for (section 6.1.2 to 6.1.5), the path as for
+// check_name_constraints; anyPolicy alone when any is; an error when the path has to be valid for
+// a policy and is not
+pub fn check_policies(path: &[&Certificate], options: &PolicyOptions) -> Result<Vec<Oid>, String> {
+    let n = path.len();
+    let any_policy = oid::owned(oid::ANY_POLICY);
+    let initial = |inhibit: bool| if inhibit { 0 } else { n + 1 };
+    let mut explicit_policy = initial(options.explicit_policy);
+    let mut policy_mapping = initial(options.inhibit_policy_mapping);
+    let mut inhibit_any_policy = initial(options.inhibit_any_policy);
+    let mut tree = Some(PolicyTree::new());
+
+    for (i, cert) in path.iter().enumerate() {
+        let last = i + 1 == n;
+        let tbs = &cert.tbs_certificate;
+        let serial = &tbs.serial_number;
+        let self_issued = is_self_issued(cert);
+
+        let policies = match tbs.find_extension(oid::CERTIFICATE_POLICIES) {
+            Some(extension) => Some(extensions::certificate_policies(&extension.extn_value)
+                .ok_or_else(|| format!("certificate {}: certificatePolicies cannot be decoded", serial))?),
+            None => None,
+        };
+        tree = match (tree, policies) {
+            (Some(mut valid), Some(policies)) => {
+                valid.add_level(&policies, inhibit_any_policy > 0 || (!last && self_issued));
+                valid.non_null()
+            },
+            _ => None,
+        };
+        if explicit_policy == 0 && tree.is_none() {
+            return Err(format!("certificate {}: no certificate policy is valid for the path and one is required", serial));
+        }
+        if last {
+            break;
+        }
+
+        // preparation for the next certificate (section 6.1.4)
+        if let Some(extension) = tbs.find_extension(oid::POLICY_MAPPINGS) {
+            let mappings = extensions::policy_mappings(&extension.extn_value)
+                .ok_or_else(|| format!("certificate {}: policyMappings cannot be decoded", serial))?;
+            if mappings.iter().any(|(issuer, subject)| *issuer == any_policy || *subject == any_policy) {
+                return Err(format!("certificate {}: policyMappings maps anyPolicy", serial));
+            }
+            tree = tree.and_then(|mut valid| {
+                valid.map_policies(&mappings, policy_mapping > 0);
+                valid.non_null()
+            });
+        }
+        if !self_issued {
+            explicit_policy = explicit_policy.saturating_sub(1);
+            policy_mapping = policy_mapping.saturating_sub(1);
+            inhibit_any_policy = inhibit_any_policy.saturating_sub(1);
+        }
+        if let Some(extension) = tbs.find_extension(oid::POLICY_CONSTRAINTS) {
+            let constraints = extensions::policy_constraints(&extension.extn_value)
+                .ok_or_else(|| format!("certificate {}: policyConstraints cannot be decoded", serial))?;
+            if let Some(skip) = constraints.require_explicit_policy {
+                explicit_policy = explicit_policy.min(skip as usize);
+            }
+            if let Some(skip) = constraints.inhibit_policy_mapping {
+                policy_mapping = policy_mapping.min(skip as usize);
+            }
+        }
+        if let Some(extension) = tbs.find_extension(oid::INHIBIT_ANY_POLICY) {
+            let skip = extensions::inhibit_any_policy(&extension.extn_value)
+                .ok_or_else(|| format!("certificate {}: inhibitAnyPolicy cannot be decoded", serial))?;
+            inhibit_any_policy = inhibit_any_policy.min(skip as usize);
+        }
+    }
+
+    // wrap-up (section 6.1.5)
+    explicit_policy = explicit_policy.saturating_sub(1);
+    let signer_requires_policy = path.last()
+        .and_then(|signer| signer.tbs_certificate.find_extension(oid::POLICY_CONSTRAINTS))
+        .and_then(|extension| extensions::policy_constraints(&extension.extn_value))
+        .is_some_and(|constraints| constraints.require_explicit_policy == Some(0));
+    if signer_requires_policy {
+        explicit_policy = 0;
+    }
+    let valid_policies = match tree {
+        Some(mut valid) => {
+            if let Some(initial_policies) = options.initial_policies.as_deref().filter(|policies| !policies.contains(&any_policy)) {
+                valid.intersect(initial_policies);
+            }
+            valid.leaves()
+        },
+        None => Vec::new(),
+    };
+    if explicit_policy == 0 && valid_policies.is_empty() {
+        let acceptable = options.initial_policies.iter().flatten().map(oid::describe).collect::<Vec<_>>();
+        return Err(match acceptable.is_empty() {
+            true => "no certificate policy is valid for the path and one is required".to_string(),
+            false => format!("the path is not valid for any of the acceptable policies {}", acceptable.join(", ")),
+        });
+    }
+    Ok(valid_policies)
+}
+
+impl PolicyTree {
+    fn new() -> Self {
+        let any_policy = oid::owned(oid::ANY_POLICY);
+        PolicyTree {
+            levels: vec![vec![PolicyNode { valid_policy: any_policy.clone(), expected_policies: vec![any_policy], parent: None }]],
+        }
+    }
+
+    // None once the root is gone, the NULL tree
+    fn non_null(self) -> Option<Self> {
+        (!self.levels[0].is_empty()).then_some(self)
+    }
+
+    // section 6.1.3 (d): the policies of the next certificate as children of the nodes expecting them,
+    // of anyPolicy nodes otherwise; anyPolicy in the certificate, when allowed, expands every node
+    fn add_level(&mut self, policies: &[Oid], any_policy_allowed: bool) {
+        let any_policy = oid::owned(oid::ANY_POLICY);
+        let above = &self.levels[self.levels.len() - 1];
+        let mut level = Vec::new();
+        for policy in policies.iter().filter(|policy| **policy != any_policy) {
+            let mut parents: Vec<usize> = (0..above.len()).filter(|i| above[*i].expected_policies.contains(policy)).collect();
+            if parents.is_empty() {
+                parents = (0..above.len()).filter(|i| above[*i].valid_policy == any_policy).collect();
+            }
+            for parent in parents {
+                level.push(PolicyNode { valid_policy: policy.clone(), expected_policies: vec![policy.clone()], parent: Some(parent) });
+            }
+        }
+        if any_policy_allowed && policies.contains(&any_policy) {
+            for (parent, node) in above.iter().enumerate() {
+                for expected in &node.expected_policies {
+                    if !level.iter().any(|child| child.parent == Some(parent) && child.valid_policy == *expected) {
+                        level.push(PolicyNode { valid_policy: expected.clone(), expected_policies: vec![expected.clone()], parent: Some(parent) });
+                    }
+                }
+            }
+        }
+        self.levels.push(level);
+        self.prune();
+    }
+
+    // section 6.1.4 (b): a mapped policy expects the subject domain policies in its place, or is
+    // deleted when mapping is inhibited
+    fn map_policies(&mut self, mappings: &[(Oid, Oid)], mapping_allowed: bool) {
+        let any_policy = oid::owned(oid::ANY_POLICY);
+        let depth = self.levels.len() - 1;
+        let mut issuer_policies: Vec<&Oid> = mappings.iter().map(|(issuer, _)| issuer).collect();
+        issuer_policies.dedup();
+        for issuer_policy in issuer_policies {
+            if !mapping_allowed {
+                self.remove(depth, |node, _| node.valid_policy == *issuer_policy);
+                continue;
+            }
+            let subject_policies: Vec<Oid> = mappings.iter()
+                .filter(|(issuer, _)| issuer == issuer_policy)
+                .map(|(_, subject)| subject.clone())
+                .collect();
+            let level = &mut self.levels[depth];
+            if level.iter().any(|node| node.valid_policy == *issuer_policy) {
+                for node in level.iter_mut().filter(|node| node.valid_policy == *issuer_policy) {
+                    node.expected_policies = subject_policies.clone();
+                }
+            } else if let Some(parent) = level.iter().find(|node| node.valid_policy == any_policy).and_then(|node| node.parent) {
+                level.push(PolicyNode { valid_policy: issuer_policy.clone(), expected_policies: subject_policies, parent: Some(parent) });
+            }
+        }
+        self.prune();
+    }
+
+    // section 6.1.5 (g) (iii): only the acceptable policies are kept below anyPolicy nodes, a leaf
+    // anyPolicy becomes the acceptable policies themselves
+    fn intersect(&mut self, acceptable: &[Oid]) {
+        let any_policy = oid::owned(oid::ANY_POLICY);
+        for depth in 1..self.levels.len() {
+            self.remove(depth, |node, parent| {
+                parent.is_some_and(|parent| parent.valid_policy == any_policy)
+                    && node.valid_policy != any_policy
+                    && !acceptable.contains(&node.valid_policy)
+            });
+        }
+        let depth = self.levels.len() - 1;
+        let below_any: Vec<Oid> = self.levels.iter().enumerate().skip(1).flat_map(|(depth, level)| {
+            let above = &self.levels[depth - 1];
+            let any_policy = &any_policy;
+            level.iter()
+                .filter(move |node| node.parent.is_some_and(|parent| above[parent].valid_policy == *any_policy))
+                .map(|node| node.valid_policy.clone())
+        }).collect();
+        let any_leaves: Vec<usize> = self.levels[depth].iter().filter(|node| node.valid_policy == any_policy).filter_map(|node| node.parent).collect();
+        for parent in any_leaves {
+            for policy in acceptable.iter().filter(|policy| !below_any.contains(policy)) {
+                self.levels[depth].push(PolicyNode { valid_policy: policy.clone(), expected_policies: vec![policy.clone()], parent: Some(parent) });
+            }
+        }
+        self.remove(depth, |node, _| node.valid_policy == any_policy);
+        self.prune();
+    }
+
+    // the valid policies of the deepest level, once each
+    fn leaves(&self) -> Vec<Oid> {
+        let mut policies: Vec<Oid> = Vec::new();
+        for node in &self.levels[self.levels.len() - 1] {
+            if !policies.contains(&node.valid_policy) {
+                policies.push(node.valid_policy.clone());
+            }
+        }
+        policies
+    }
+
+    // nodes above the deepest level without children go, up to the root
+    fn prune(&mut self) {
+        for depth in (0..self.levels.len() - 1).rev() {
+            let below = &self.levels[depth + 1];
+            let keep: Vec<bool> = (0..self.levels[depth].len()).map(|i| below.iter().any(|child| child.parent == Some(i))).collect();
+            self.retain(depth, keep);
+        }
+    }
+
+    // removes the nodes of a depth the predicate, given the node and its parent, selects
+    fn remove(&mut self, depth: usize, predicate: impl Fn(&PolicyNode, Option<&PolicyNode>) -> bool) {
+        let keep: Vec<bool> = self.levels[depth].iter().map(|node| {
+            let parent = node.parent.and_then(|parent| self.levels.get(depth.wrapping_sub(1))?.get(parent));
+            !predicate(node, parent)
+        }).collect();
+        self.retain(depth, keep);
+    }
+
+    // keeps the nodes of a depth that are marked, the descendants of the others go with them
+    fn retain(&mut self, depth: usize, mut keep: Vec<bool>) {
+        for depth in depth..self.levels.len() {
+            if keep.iter().all(|keep| *keep) {
+                return;
+            }
+            let mut next = 0;
+            let new_index: Vec<Option<usize>> = keep.iter().map(|keep| keep.then(|| { next += 1; next - 1 })).collect();
+            let mut i = 0;
+            self.levels[depth].retain(|_| { i += 1; keep[i - 1] });
+            keep = match self.levels.get_mut(depth + 1) {
+                Some(below) => below.iter_mut().map(|child| {
+                    child.parent = child.parent.and_then(|parent| new_index[parent]);
+                    child.parent.is_some()
+                }).collect(),
+                None => return,
+            };
+        }
+    }
+}
+
+fn is_self_issued(cert: &Certificate) -> bool {
+    cert.tbs_certificate.subject == cert.tbs_certificate.issuer
+}
+
+// the host itself or one of its subdomains, a leading dot only allows subdomains
+fn dns_in(host: &str, base: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    let base = base.to_ascii_lowercase();
+    match base.strip_prefix('.') {
+        Some(_) => host.ends_with(&base),
+        None => host == base || host.ends_with(&format!(".{}", base)),
+    }
+}
+
+// scheme://[userinfo@]host[:port][/path]
+fn uri_host(uri: &str) -> Option<&str> {
+    let (_, rest) = uri.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+fn ip_to_string(ip: &[u8]) -> String {
+    match <[u8; 4]>::try_from(ip) {
+        Ok(octets) => IpAddr::from(octets).to_string(),
+        Err(_) => match <[u8; 16]>::try_from(ip) {
+            Ok(octets) => IpAddr::from(octets).to_string(),
+            Err(_) => hex::encode(ip),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, ALICE, INT};
+
+    const POLICY: &str = "1.3.6.1.4.1.99999.1"; // of the constrained CA, mapped to .2 for its subjects
+    const MAPPED: &str = "1.3.6.1.4.1.99999.2";
+
+    fn nc() -> Certificate {
+        testdata::cert(include_bytes!("../testdata/nc.crt"))
+    }
+
+    fn carol() -> Certificate {
+        testdata::cert(include_bytes!("../testdata/carol.crt"))
+    }
+
+    fn policies(policies: &[&str], explicit_policy: bool) -> PolicyOptions {
+        let initial_policies = policies.iter().map(|policy| oid::from_dotted(policy).unwrap()).collect();
+        PolicyOptions { initial_policies: Some(initial_policies), explicit_policy, ..PolicyOptions::default() }
+    }
+
+    #[test]
+    fn subtrees() {
+        let dns = |name: &str| SubjectName::Dns(name.to_string());
+        let email = |name: &str| SubjectName::Email(name.to_string());
+        let uri = |name: &str| SubjectName::Uri(name.to_string());
+        assert_eq!(NameSubtree::Dns("example.com".into()).matches(&dns("www.Example.com")), Some(true));
+        assert_eq!(NameSubtree::Dns("example.com".into()).matches(&dns("badexample.com")), Some(false));
+        assert_eq!(NameSubtree::Dns("example.com".into()).matches(&email("a@example.com")), None);
+        assert_eq!(NameSubtree::Email("a@example.com".into()).matches(&email("A@example.com")), Some(true));
+        assert_eq!(NameSubtree::Email("example.com".into()).matches(&email("a@mail.example.com")), Some(false));
+        assert_eq!(NameSubtree::Email(".example.com".into()).matches(&email("a@mail.example.com")), Some(true));
+        assert_eq!(NameSubtree::Email(".example.com".into()).matches(&email("a@example.com")), Some(false));
+        assert_eq!(NameSubtree::Uri(".example.com".into()).matches(&uri("https://www.example.com:8443/x")), Some(true));
+        assert_eq!(NameSubtree::Uri("example.com".into()).matches(&uri("urn:example.com")), Some(false));
+        let net = NameSubtree::Ip(vec![10, 0, 0, 0, 255, 0, 0, 0]);
+        assert_eq!(net.matches(&SubjectName::Ip(vec![10, 1, 2, 3])), Some(true));
+        assert_eq!(net.matches(&SubjectName::Ip(vec![11, 1, 2, 3])), Some(false));
+        assert_eq!(net.matches(&SubjectName::Ip(vec![0; 16])), Some(false));
+    }
+
+    #[test]
+    fn name_constraints() {
+        let nc = nc();
+        assert!(check_name_constraints(&[&nc, &carol()]).is_empty());
+        let mallory = testdata::cert(include_bytes!("../testdata/mallory.crt"));
+        let errors = check_name_constraints(&[&nc, &mallory]);
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(errors[0].contains("email:mallory@example.org is not permitted"));
+        assert!(errors[1].contains("IP Address:10.1.2.3 is excluded"));
+    }
+
+    #[test]
+    fn policy_mapping() {
+        let (nc, carol) = (nc(), carol());
+        let path = [&nc, &carol];
+        // the leaves are in the subject domain, the acceptable policies apply to the CA one
+        let mapped = oid::from_dotted(MAPPED).unwrap();
+        assert_eq!(check_policies(&path, &PolicyOptions::default()).unwrap(), std::slice::from_ref(&mapped));
+        assert_eq!(check_policies(&path, &policies(&[POLICY], true)).unwrap(), [mapped]);
+        let error = check_policies(&path, &policies(&[MAPPED], true)).unwrap_err();
+        assert!(error.starts_with("the path is not valid for any of the acceptable policies"), "{}", error);
+        // without the mapping the subject policy is not one of the CA
+        let inhibited = PolicyOptions { inhibit_policy_mapping: true, ..policies(&[POLICY], true) };
+        assert!(check_policies(&path, &inhibited).is_err());
+    }
+
+    #[test]
+    fn explicit_policy() {
+        let (int, alice) = (testdata::cert(INT), testdata::cert(ALICE));
+        assert!(check_policies(&[&int, &alice], &PolicyOptions::default()).unwrap().is_empty());
+        let required = PolicyOptions { explicit_policy: true, ..PolicyOptions::default() };
+        assert!(check_policies(&[&int, &alice], &required).is_err());
+    }
+}
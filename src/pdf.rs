@@ -0,0 +1,436 @@
+// PDF signatures (ISO 32000-1 section 12.8, ETSI EN 319 142 PAdES): the signature dictionaries, the CMS
+// in /Contents and the bytes /ByteRange says were signed. No PDF parser: the dictionaries are found
+// from their /ByteRange key, which is never compressed since the signature has to point into the file.
+
+use std::ops::Range;
+
+use serde::Serialize;
+
+use crate::crypto::HashAlg;
+use crate::dump;
+use crate::report::VerificationReport;
+use crate::timestamp::TimeStampToken;
+use crate::SignedData;
+
+// the CMS signs the byte range itself
+pub const DETACHED_SUB_FILTERS: [&str; 2] = ["adbe.pkcs7.detached", "ETSI.CAdES.detached"];
+// the CMS encapsulates the SHA-1 of the byte range
+pub const SHA1_SUB_FILTER: &str = "adbe.pkcs7.sha1";
+// a document time-stamp, /Contents is a TimeStampToken over the byte range
+pub const TIME_STAMP_SUB_FILTER: &str = "ETSI.RFC3161";
+
+#[derive(Debug)]
+pub struct PdfSignature {
+    pub offset: usize, // of the /ByteRange key
+    pub byte_range: [usize; 4], // offset and length of the two signed parts
+    pub sub_filter: Option<String>,
+    pub name: Option<String>,
+    pub reason: Option<String>,
+    pub signing_time: Option<String>, // /M as written, D:YYYYMMDDHHmmSSOHH'mm'
+    pub contents: Vec<u8>, // DER, without the zero padding
+    pub signed_bytes: Vec<u8>,
+    pub coverage: Result<(), String>, // Ok when the range is the whole file but /Contents
+}
+
+#[derive(Serialize)]
+pub struct PdfSignatureReport {
+    pub sub_filter: Option<String>,
+    pub name: Option<String>,
+    pub reason: Option<String>,
+    pub signing_time: Option<String>,
+    pub byte_range: [usize; 4],
+    pub covers_whole_file: bool,
+    pub coverage_error: Option<String>,
+    pub content_error: Option<String>, // the SHA-1 or time-stamp imprint of the byte range
+    pub verification: VerificationReport,
+}
+
+impl PdfSignature {
+    pub fn is_detached(&self) -> bool {
+        self.sub_filter.as_deref().is_none_or(|sub_filter| DETACHED_SUB_FILTERS.contains(&sub_filter))
+    }
+
+    // the byte range against what the CMS encapsulates, for the sub filters that do not sign it detached
+    pub fn check_content(&self, signed_data: &SignedData) -> Result<(), String> {
+        match self.sub_filter.as_deref() {
+            Some(SHA1_SUB_FILTER) => {
+                let digest = signed_data.content_info.content.as_deref().ok_or("adbe.pkcs7.sha1 without the digest")?;
+                if digest != HashAlg::Sha1.digest(&self.signed_bytes) {
+                    return Err("the SHA-1 in the signature is not the one of the byte range".to_string());
+                }
+                Ok(())
+            },
+            Some(TIME_STAMP_SUB_FILTER) => TimeStampToken::from_der(&self.contents)?.verify(&self.signed_bytes),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl PdfSignatureReport {
+    pub fn new(signature: &PdfSignature, content: &Result<(), String>, verification: VerificationReport) -> Self {
+        PdfSignatureReport {
+            sub_filter: signature.sub_filter.clone(),
+            name: signature.name.clone(),
+            reason: signature.reason.clone(),
+            signing_time: signature.signing_time.clone(),
+            byte_range: signature.byte_range,
+            covers_whole_file: signature.coverage.is_ok(),
+            coverage_error: signature.coverage.clone().err(),
+            content_error: content.clone().err(),
+            verification,
+        }
+    }
+}
+
+// every signature dictionary of the file, in file order; the later revisions come last
+pub fn signatures(pdf: &[u8]) -> Result<Vec<Result<PdfSignature, String>>, String> {
+    if find(&pdf[..pdf.len().min(1024)], b"%PDF-", 0).is_none() {
+        return Err("not a PDF file".to_string());
+    }
+    let mut signatures = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = find(pdf, b"/ByteRange", pos) {
+        pos = offset + 1;
+        match parse_signature(pdf, offset) {
+            Ok(Some(signature)) => signatures.push(Ok(signature)),
+            Ok(None) => {},
+            Err(e) => signatures.push(Err(format!("signature at offset {}: {}", offset, e))),
+        }
+    }
+    Ok(signatures)
+}
+
+fn parse_signature(pdf: &[u8], offset: usize) -> Result<Option<PdfSignature>, String> {
+    let byte_range = parse_byte_range(pdf, offset + b"/ByteRange".len())?;
+    let [start1, len1, start2, len2] = byte_range;
+    let gap = start1.checked_add(len1).filter(|gap_start| *gap_start <= start2).map(|gap_start| gap_start..start2);
+    let end = start2.checked_add(len2).filter(|end| *end <= pdf.len());
+    let (Some(gap), Some(_)) = (gap, end) else {
+        return Err(format!("ByteRange {:?} is outside the file", byte_range));
+    };
+    if gap.len() < 2 || pdf[gap.start] != b'<' || pdf[gap.end - 1] != b'>' {
+        return Err("the bytes between the ranges are not a hex string".to_string());
+    }
+
+    let hex_digits: Vec<u8> = pdf[gap.start + 1..gap.end - 1].iter().copied().filter(|c| !c.is_ascii_whitespace()).collect();
+    let mut contents = hex::decode(&hex_digits).map_err(|e| format!("bad /Contents: {}", e))?;
+    let der_len = dump::value_len(&contents).map_err(|e| format!("bad /Contents: {}", e))?;
+    contents.truncate(der_len);
+
+    let dict = dictionary_around(pdf, offset, &gap).ok_or("no dictionary around /ByteRange")?;
+    if let Some(pos) = dict_value(pdf, &dict, &gap, b"Type") {
+        if !matches!(name_at(pdf, pos).as_deref(), Some("Sig" | "DocTimeStamp")) {
+            return Ok(None);
+        }
+    }
+
+    let coverage = if start1 != 0 {
+        Err("the signed bytes do not start at the beginning of the file".to_string())
+    } else if start2 + len2 != pdf.len() {
+        Err(format!("{} bytes after the signed range, the file was changed after signing", pdf.len() - start2 - len2))
+    } else if dict_value(pdf, &dict, &gap, b"Contents") != Some(gap.start) {
+        Err("the unsigned bytes are not the /Contents of the signature".to_string())
+    } else {
+        Ok(())
+    };
+
+    let mut signed_bytes = pdf[start1..start1 + len1].to_vec();
+    signed_bytes.extend_from_slice(&pdf[start2..start2 + len2]);
+    Ok(Some(PdfSignature {
+        offset,
+        byte_range,
+        sub_filter: dict_value(pdf, &dict, &gap, b"SubFilter").and_then(|pos| name_at(pdf, pos)),
+        name: dict_value(pdf, &dict, &gap, b"Name").and_then(|pos| string_at(pdf, pos)),
+        reason: dict_value(pdf, &dict, &gap, b"Reason").and_then(|pos| string_at(pdf, pos)),
+        signing_time: dict_value(pdf, &dict, &gap, b"M").and_then(|pos| string_at(pdf, pos)),
+        contents,
+        signed_bytes,
+        coverage,
+    }))
+}
+
+// [start1 len1 start2 len2]
+fn parse_byte_range(pdf: &[u8], mut pos: usize) -> Result<[usize; 4], String> {
+    pos = skip_whitespace(pdf, pos);
+    if pdf.get(pos) != Some(&b'[') {
+        return Err("ByteRange is not an array".to_string());
+    }
+    let mut range = [0; 4];
+    for value in range.iter_mut() {
+        pos = skip_whitespace(pdf, pos + 1);
+        let digits = pdf[pos..].iter().take_while(|c| c.is_ascii_digit()).count();
+        *value = std::str::from_utf8(&pdf[pos..pos + digits]).ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or("ByteRange needs four offsets")?;
+        pos += digits - 1;
+    }
+    if pdf.get(skip_whitespace(pdf, pos + 1)) != Some(&b']') {
+        return Err("ByteRange needs four offsets".to_string());
+    }
+    Ok(range)
+}
+
+// the innermost << >> holding pos; the hex string in the gap is skipped, it can be megabytes
+fn dictionary_around(pdf: &[u8], pos: usize, gap: &Range<usize>) -> Option<Range<usize>> {
+    let mut start = pos;
+    while start > 0 {
+        start -= 1;
+        if gap.contains(&start) {
+            start = gap.start;
+            continue;
+        }
+        if pdf[start..].starts_with(b"<<") {
+            if let Some(end) = dictionary_end(pdf, start, gap) {
+                if end > pos {
+                    return Some(start..end);
+                }
+            }
+        }
+    }
+    None
+}
+
+// one past the >> closing the dictionary at start
+fn dictionary_end(pdf: &[u8], start: usize, gap: &Range<usize>) -> Option<usize> {
+    let mut depth = 0;
+    let mut pos = start;
+    while pos < pdf.len() {
+        if pos == gap.start {
+            pos = gap.end;
+            continue;
+        }
+        match pdf[pos] {
+            b'(' => pos = skip_literal_string(pdf, pos),
+            b'<' if pdf.get(pos + 1) == Some(&b'<') => {
+                depth += 1;
+                pos += 2;
+            },
+            b'<' => pos = find(pdf, b">", pos)? + 1,
+            b'>' if pdf.get(pos + 1) == Some(&b'>') => {
+                depth -= 1;
+                pos += 2;
+                if depth == 0 {
+                    return Some(pos);
+                }
+            },
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+// where the value of /key starts, only keys of the dictionary itself, not of the ones nested in it
+fn dict_value(pdf: &[u8], dict: &Range<usize>, gap: &Range<usize>, key: &[u8]) -> Option<usize> {
+    let mut depth = 0;
+    let mut pos = dict.start;
+    while pos < dict.end {
+        if pos == gap.start {
+            pos = gap.end;
+            continue;
+        }
+        match pdf[pos] {
+            b'(' => pos = skip_literal_string(pdf, pos),
+            b'<' if pdf.get(pos + 1) == Some(&b'<') => {
+                depth += 1;
+                pos += 2;
+            },
+            b'<' => pos = find(pdf, b">", pos)? + 1,
+            b'>' if pdf.get(pos + 1) == Some(&b'>') => {
+                depth -= 1;
+                pos += 2;
+            },
+            b'/' if depth == 1 => {
+                let name_end = pos + 1 + pdf[pos + 1..].iter().take_while(|c| !is_delimiter(**c)).count();
+                if &pdf[pos + 1..name_end] == key {
+                    return Some(skip_whitespace(pdf, name_end));
+                }
+                pos = name_end;
+            },
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+fn name_at(pdf: &[u8], pos: usize) -> Option<String> {
+    if pdf.get(pos) != Some(&b'/') {
+        return None;
+    }
+    let name = pdf[pos + 1..].iter().take_while(|c| !is_delimiter(**c)).copied().collect();
+    String::from_utf8(name).ok()
+}
+
+// a literal (...) or hex <...> string; UTF-16BE with its BOM, PDFDocEncoding taken as Latin-1 otherwise
+fn string_at(pdf: &[u8], pos: usize) -> Option<String> {
+    let bytes = match pdf.get(pos)? {
+        b'(' => unescape_literal(&pdf[pos + 1..skip_literal_string(pdf, pos) - 1]),
+        b'<' => {
+            let end = find(pdf, b">", pos)?;
+            let digits: Vec<u8> = pdf[pos + 1..end].iter().copied().filter(|c| !c.is_ascii_whitespace()).collect();
+            hex::decode(digits).ok()?
+        },
+        _ => return None,
+    };
+    match bytes.strip_prefix(&[0xfe, 0xff]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+            String::from_utf16(&units).ok()
+        },
+        None => Some(bytes.iter().map(|b| char::from(*b)).collect()),
+    }
+}
+
+// one past the ) closing the string at pos; parentheses nest, a backslash escapes the next byte
+fn skip_literal_string(pdf: &[u8], pos: usize) -> usize {
+    let mut depth = 0;
+    let mut i = pos;
+    while i < pdf.len() {
+        match pdf[i] {
+            b'\\' => i += 1,
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+    pdf.len()
+}
+
+fn unescape_literal(raw: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(raw.len());
+    let mut bytes = raw.iter().copied().peekable();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            out.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'n') => out.push(b'\n'),
+            Some(b'r') => out.push(b'\r'),
+            Some(b't') => out.push(b'\t'),
+            Some(b'b') => out.push(8),
+            Some(b'f') => out.push(12),
+            Some(b'\r' | b'\n') => {}, // line continuation
+            Some(digit @ b'0'..=b'7') => {
+                // up to three octal digits
+                let mut value = u32::from(digit - b'0');
+                for _ in 0..2 {
+                    match bytes.peek() {
+                        Some(next @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(next - b'0');
+                            bytes.next();
+                        },
+                        _ => break,
+                    }
+                }
+                out.push(value as u8);
+            },
+            Some(other) => out.push(other),
+            None => {},
+        }
+    }
+    out
+}
+
+fn is_delimiter(c: u8) -> bool {
+    c.is_ascii_whitespace() || c == 0 || b"()<>[]{}/%".contains(&c)
+}
+
+fn skip_whitespace(pdf: &[u8], mut pos: usize) -> usize {
+    while pdf.get(pos).is_some_and(|c| c.is_ascii_whitespace() || *c == 0) {
+        pos += 1;
+    }
+    pos
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack.get(from..)?.windows(needle.len()).position(|window| window == needle).map(|pos| pos + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata;
+    use crate::verify::{self, VerifyOptions, VerifyStatus};
+    use crate::parse_pkcs7;
+
+    const SIGNED: &[u8] = include_bytes!("../testdata/signed.pdf");
+    const SHA1: &[u8] = include_bytes!("../testdata/sha1.pdf");
+    const DOC_TIME_STAMP: &[u8] = include_bytes!("../testdata/docts.pdf");
+
+    fn signature(pdf: &[u8]) -> PdfSignature {
+        let mut signatures = signatures(pdf).unwrap();
+        assert_eq!(signatures.len(), 1);
+        signatures.pop().unwrap().unwrap()
+    }
+
+    // the page size changed inside the signed range
+    fn tampered(pdf: &[u8]) -> Vec<u8> {
+        let mut pdf = pdf.to_vec();
+        let at = pdf.windows(18).position(|window| window == b"/MediaBox [0 0 200").unwrap();
+        pdf[at + 17] = b'9';
+        pdf
+    }
+
+    fn verify(signature: &PdfSignature) -> VerifyStatus {
+        let pkcs7 = parse_pkcs7(&signature.contents).unwrap();
+        let signed_data = pkcs7.signed_data().unwrap();
+        let detached_content = signature.is_detached().then(|| signature.signed_bytes.clone());
+        let options = VerifyOptions { detached_content, ..testdata::options() };
+        let status = VerifyStatus::combine(verify::verify_signed_data(signed_data, &options).unwrap().iter().map(|r| r.status));
+        match signature.check_content(signed_data) {
+            Ok(()) => status,
+            Err(_) => VerifyStatus::InvalidSignature,
+        }
+    }
+
+    #[test]
+    fn signature_dictionary() {
+        let signature = self::signature(SIGNED);
+        assert_eq!(signature.sub_filter.as_deref(), Some("ETSI.CAdES.detached"));
+        assert_eq!(signature.name.as_deref(), Some("Alice (test)"));
+        assert_eq!(signature.reason.as_deref(), Some("test"));
+        assert_eq!(signature.signing_time.as_deref(), Some("D:20261019120000+00'00'"));
+        assert_eq!(signature.coverage, Ok(()));
+        let [start, first, second, rest] = signature.byte_range;
+        assert_eq!((start, second + rest), (0, SIGNED.len()));
+        assert_eq!(signature.signed_bytes, [&SIGNED[..first], &SIGNED[second..]].concat());
+        assert!(signature.is_detached());
+        assert_eq!(verify(&signature), VerifyStatus::Valid);
+    }
+
+    #[test]
+    fn incremental_update_is_not_covered() {
+        let appended = [SIGNED, b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n%%EOF\n"].concat();
+        let appended = signature(&appended);
+        assert!(appended.coverage.is_err());
+        assert_eq!(verify(&appended), VerifyStatus::Valid);
+    }
+
+    #[test]
+    fn tampered_documents() {
+        assert_eq!(verify(&signature(&tampered(SIGNED))), VerifyStatus::InvalidSignature);
+
+        let sha1 = signature(&tampered(SHA1));
+        assert!(!sha1.is_detached());
+        let pkcs7 = parse_pkcs7(&sha1.contents).unwrap();
+        assert_eq!(sha1.check_content(pkcs7.signed_data().unwrap()).unwrap_err(),
+                   "the SHA-1 in the signature is not the one of the byte range");
+
+        let time_stamp = signature(&tampered(DOC_TIME_STAMP));
+        let pkcs7 = parse_pkcs7(&time_stamp.contents).unwrap();
+        assert_eq!(time_stamp.check_content(pkcs7.signed_data().unwrap()).unwrap_err(),
+                   "time-stamp imprint does not match the signature");
+    }
+
+    #[test]
+    fn legacy_and_document_time_stamp() {
+        assert_eq!(verify(&signature(SHA1)), VerifyStatus::Valid);
+        assert_eq!(verify(&signature(DOC_TIME_STAMP)), VerifyStatus::Valid);
+    }
+}
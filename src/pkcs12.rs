@@ -0,0 +1,381 @@
+// PKCS#12 (RFC 7292) PFX files: the password MAC over the authenticated safe, and the certificates and
+// private keys of its bags, encrypted with PBES2 (PBKDF2 with AES or triple DES) or the legacy PKCS#12
+// PBE ciphers (triple DES, RC2). Only password integrity and privacy, not the public key modes.
+
+use aes::cipher::{BlockCipher, BlockDecryptMut, InnerIvInit, KeyInit};
+use bcder::decode::{self, Constructed, DecodeError};
+use bcder::{Mode, OctetString, Oid, Tag};
+use bytes::Bytes;
+
+use crate::crypto::HashAlg;
+use crate::enveloped::{self, EncryptedContentInfo};
+use crate::oid;
+use crate::{AlgorithmIdentifier, Certificate, ContentInfo};
+
+#[derive(Debug)]
+pub struct Pfx {
+    pub version: u8,
+    pub auth_safe: ContentInfo, // id-data with the DER of the AuthenticatedSafe
+    pub mac_data: Option<MacData>,
+}
+
+#[derive(Debug)]
+pub struct MacData {
+    pub digest_algorithm: AlgorithmIdentifier,
+    pub digest: Vec<u8>,
+    pub salt: Vec<u8>,
+    pub iterations: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Pkcs12Contents {
+    pub certs: Vec<CertBag>,
+    pub keys: Vec<KeyBag>,
+    pub other_bags: Vec<Oid>, // CRL, secret and unknown bags, skipped
+}
+
+#[derive(Debug)]
+pub struct CertBag {
+    pub cert: Certificate,
+    pub attributes: BagAttributes,
+}
+
+#[derive(Debug)]
+pub struct KeyBag {
+    pub pkcs8: Vec<u8>, // DER PrivateKeyInfo, decrypted
+    pub encryption: Option<AlgorithmIdentifier>, // of a pkcs8ShroudedKeyBag
+    pub attributes: BagAttributes,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct BagAttributes {
+    pub friendly_name: Option<String>,
+    pub local_key_id: Option<Vec<u8>>, // ties a key to its certificate
+}
+
+struct SafeBag {
+    bag_id: Oid,
+    value: Bytes, // DER of the [0] EXPLICIT value
+    attributes: BagAttributes,
+}
+
+impl Pfx {
+    pub fn from_der(der: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let pfx = Constructed::decode(Bytes::copy_from_slice(der), Mode::Ber, |cons| {
+            cons.take_sequence(|cons| {
+                let version = cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+                let auth_safe = ContentInfo::take_from(cons)?;
+                let mac_data = cons.take_opt_sequence(|cons| {
+                    let (digest_algorithm, digest) = cons.take_sequence(|cons| {
+                        let digest_algorithm = AlgorithmIdentifier::take_from(cons)?;
+                        let digest = OctetString::take_from(cons)?.into_bytes().to_vec();
+                        Ok((digest_algorithm, digest))
+                    })?;
+                    let salt = OctetString::take_from(cons)?.into_bytes().to_vec();
+                    let iterations = cons.take_opt_u32()?.unwrap_or(1);
+                    Ok(MacData { digest_algorithm, digest, salt, iterations })
+                })?;
+                Ok(Pfx { version, auth_safe, mac_data })
+            })
+        }).map_err(|err| format!("Error decoding PKCS#12: {}", err))?;
+        if pfx.version != 3 {
+            return Err(format!("PKCS#12 version {}, not 3", pfx.version).into());
+        }
+        Ok(pfx)
+    }
+
+    // the HMAC with a key derived from the password (RFC 7292 appendix B), None without MacData
+    pub fn verify_mac(&self, password: &str) -> Option<Result<(), String>> {
+        let mac_data = self.mac_data.as_ref()?;
+        let check = || {
+            let hash = HashAlg::from_oid(&mac_data.digest_algorithm.algorithm)
+                .ok_or_else(|| format!("unsupported MAC algorithm {}", oid::describe(&mac_data.digest_algorithm.algorithm)))?;
+            let data = self.auth_safe_der()?;
+            // an empty password is two zero bytes, or nothing at all for some writers
+            let mut passwords = vec![bmp_password(password)];
+            if password.is_empty() {
+                passwords.push(Vec::new());
+            }
+            let matches = passwords.iter().any(|password| {
+                let key = pkcs12_kdf(hash, 3, password, &mac_data.salt, mac_data.iterations, hash.output_len());
+                hash.hmac(&key, data) == mac_data.digest
+            });
+            match matches {
+                true => Ok(()),
+                false => Err("MAC verification failed, wrong password or modified file".to_string()),
+            }
+        };
+        Some(check())
+    }
+
+    // every bag of the authenticated safe, the encrypted ones decrypted with the password
+    pub fn contents(&self, password: &str) -> Result<Pkcs12Contents, String> {
+        let content_infos = Constructed::decode(Bytes::copy_from_slice(self.auth_safe_der()?), Mode::Ber, |cons| {
+            cons.take_sequence(|cons| {
+                let mut content_infos = Vec::new();
+                while let Some(content_info) = cons.take_opt_sequence(ContentInfo::take_content_from)? {
+                    content_infos.push(content_info);
+                }
+                Ok(content_infos)
+            })
+        }).map_err(|err| format!("Error decoding AuthenticatedSafe: {}", err))?;
+
+        let mut contents = Pkcs12Contents::default();
+        for content_info in content_infos {
+            let safe_contents = if content_info.content_type == oid::DATA {
+                content_info.content.ok_or("AuthenticatedSafe data without content")?
+            } else if content_info.content_type == oid::ENCRYPTED_DATA {
+                // EncryptedData ::= SEQUENCE { version, encryptedContentInfo }, the value as ContentInfo keeps it
+                let encrypted = content_info.content.ok_or("AuthenticatedSafe encryptedData without content")?;
+                let info = Constructed::decode(Bytes::from(encrypted), Mode::Ber, |cons| {
+                    cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+                    EncryptedContentInfo::take_from(cons)
+                }).map_err(|err| format!("Error decoding EncryptedData: {}", err))?;
+                let ciphertext = info.encrypted_content.as_deref().ok_or("EncryptedData without content")?;
+                pbe_decrypt(&info.content_encryption_algorithm, password, ciphertext)?
+            } else {
+                return Err(format!("{} in the AuthenticatedSafe is not supported", oid::describe(&content_info.content_type)));
+            };
+            contents.add_safe_contents(&safe_contents, password)?;
+        }
+        Ok(contents)
+    }
+
+    fn auth_safe_der(&self) -> Result<&[u8], String> {
+        if self.auth_safe.content_type != oid::DATA {
+            return Err(format!("{} integrity mode is not supported", oid::describe(&self.auth_safe.content_type)));
+        }
+        self.auth_safe.content.as_deref().ok_or_else(|| "AuthenticatedSafe without content".to_string())
+    }
+}
+
+impl Pkcs12Contents {
+    // the certificate of a key, by localKeyID, or the only certificate there is
+    pub fn cert_for(&self, key: &KeyBag) -> Option<&CertBag> {
+        match &key.attributes.local_key_id {
+            Some(id) => self.certs.iter().find(|cert| cert.attributes.local_key_id.as_ref() == Some(id)),
+            None if self.certs.len() == 1 => self.certs.first(),
+            None => None,
+        }
+    }
+
+    fn add_safe_contents(&mut self, der: &[u8], password: &str) -> Result<(), String> {
+        let bags = Constructed::decode(Bytes::copy_from_slice(der), Mode::Ber, |cons| {
+            cons.take_sequence(|cons| {
+                let mut bags = Vec::new();
+                while let Some(bag) = cons.take_opt_sequence(take_safe_bag_fields)? {
+                    bags.push(bag);
+                }
+                Ok(bags)
+            })
+        }).map_err(|err| format!("Error decoding SafeContents: {}", err))?;
+
+        for bag in bags {
+            if bag.bag_id == oid::CERT_BAG {
+                // CertBag ::= SEQUENCE { certId, certValue [0] EXPLICIT OCTET STRING }
+                let (cert_id, der) = Constructed::decode(bag.value.clone(), Mode::Ber, |cons| {
+                    cons.take_sequence(|cons| {
+                        let cert_id = Oid::take_from(cons)?;
+                        let der = cons.take_constructed_if(Tag::CTX_0, OctetString::take_from)?;
+                        Ok((cert_id, der.into_bytes()))
+                    })
+                }).map_err(|err| format!("Error decoding CertBag: {}", err))?;
+                if cert_id != oid::X509_CERTIFICATE {
+                    self.other_bags.push(cert_id);
+                    continue;
+                }
+                let cert = Certificate::from_der(&der).map_err(|e| e.to_string())?;
+                self.certs.push(CertBag { cert, attributes: bag.attributes });
+            } else if bag.bag_id == oid::KEY_BAG {
+                self.keys.push(KeyBag { pkcs8: bag.value.to_vec(), encryption: None, attributes: bag.attributes });
+            } else if bag.bag_id == oid::PKCS8_SHROUDED_KEY_BAG {
+                // EncryptedPrivateKeyInfo ::= SEQUENCE { encryptionAlgorithm, encryptedData OCTET STRING }
+                let (algorithm, encrypted) = Constructed::decode(bag.value.clone(), Mode::Ber, |cons| {
+                    cons.take_sequence(|cons| {
+                        let algorithm = AlgorithmIdentifier::take_from(cons)?;
+                        let encrypted = OctetString::take_from(cons)?.into_bytes();
+                        Ok((algorithm, encrypted))
+                    })
+                }).map_err(|err| format!("Error decoding EncryptedPrivateKeyInfo: {}", err))?;
+                let pkcs8 = pbe_decrypt(&algorithm, password, &encrypted)?;
+                self.keys.push(KeyBag { pkcs8, encryption: Some(algorithm), attributes: bag.attributes });
+            } else if bag.bag_id == oid::SAFE_CONTENTS_BAG {
+                self.add_safe_contents(&bag.value, password)?;
+            } else {
+                self.other_bags.push(bag.bag_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+// SafeBag ::= SEQUENCE { bagId, bagValue [0] EXPLICIT, bagAttributes SET OF PKCS12Attribute OPTIONAL }
+fn take_safe_bag_fields<S: decode::Source>(cons: &mut Constructed<S>) -> Result<SafeBag, DecodeError<S::Error>> {
+    let bag_id = Oid::take_from(cons)?;
+    let value = cons.take_constructed_if(Tag::CTX_0, |cons| cons.capture_one())?.into_bytes();
+    let mut attributes = BagAttributes::default();
+    cons.take_opt_set(|cons| {
+        while let Some((attr_type, values)) = cons.take_opt_sequence(|cons| {
+            let attr_type = Oid::take_from(cons)?;
+            let values = cons.take_set(|cons| cons.capture_all())?.into_bytes();
+            Ok((attr_type, values))
+        })? {
+            if attr_type == oid::FRIENDLY_NAME {
+                attributes.friendly_name = decode_bmp_string(&values);
+            } else if attr_type == oid::LOCAL_KEY_ID {
+                attributes.local_key_id = Constructed::decode(values, Mode::Ber, |cons| {
+                    Ok(OctetString::take_from(cons)?.into_bytes().to_vec())
+                }).ok();
+            }
+        }
+        Ok(())
+    })?;
+    Ok(SafeBag { bag_id, value, attributes })
+}
+
+// PBES2 (RFC 8018) or one of the PKCS#12 PBE schemes (RFC 7292 appendix C)
+pub fn pbe_decrypt(algorithm: &AlgorithmIdentifier, password: &str, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    let params = algorithm.parameters.as_deref().unwrap_or_default();
+    if algorithm.algorithm == oid::PBES2 {
+        let (derivation, scheme) = Constructed::decode(params, Mode::Ber, |cons| {
+            cons.take_sequence(|cons| Ok((AlgorithmIdentifier::take_from(cons)?, AlgorithmIdentifier::take_from(cons)?)))
+        }).map_err(|err| format!("Error decoding PBES2 parameters: {}", err))?;
+        let iv = enveloped::decode_octet_string(scheme.parameters.as_deref().unwrap_or_default()).map_err(|e| e.to_string())?;
+        // PBKDF2 takes the password as it is, UTF-8
+        let password = password.as_bytes();
+        if scheme.algorithm == oid::DES_EDE3_CBC {
+            let key = enveloped::derive_pbkdf2_key(&derivation, password, 24).map_err(|e| e.to_string())?;
+            return cbc_decrypt(des::TdesEde3::new_from_slice(&key).map_err(|e| e.to_string())?, &iv, ciphertext);
+        }
+        let key_len = enveloped::aes_cbc_key_len(&scheme.algorithm).map_err(|e| e.to_string())?;
+        let key = enveloped::derive_pbkdf2_key(&derivation, password, key_len).map_err(|e| e.to_string())?;
+        return enveloped::aes_cbc_decrypt(&key, &iv, ciphertext).map_err(|e| e.to_string());
+    }
+
+    // PBEParameter ::= SEQUENCE { salt OCTET STRING, iterations INTEGER }
+    let (salt, iterations) = Constructed::decode(params, Mode::Ber, |cons| {
+        cons.take_sequence(|cons| Ok((OctetString::take_from(cons)?.into_bytes().to_vec(), cons.take_u32()?)))
+    }).map_err(|err| format!("Error decoding PBE parameters: {}", err))?;
+    let password = bmp_password(password);
+    let derive = |id: u8, len: usize| pkcs12_kdf(HashAlg::Sha1, id, &password, &salt, iterations, len);
+    let alg = &algorithm.algorithm;
+    if *alg == oid::PBE_SHA1_3DES {
+        cbc_decrypt(des::TdesEde3::new_from_slice(&derive(1, 24)).map_err(|e| e.to_string())?, &derive(2, 8), ciphertext)
+    } else if *alg == oid::PBE_SHA1_2DES {
+        cbc_decrypt(des::TdesEde2::new_from_slice(&derive(1, 16)).map_err(|e| e.to_string())?, &derive(2, 8), ciphertext)
+    } else if *alg == oid::PBE_SHA1_RC2_128 {
+        cbc_decrypt(rc2::Rc2::new_with_eff_key_len(&derive(1, 16), 128), &derive(2, 8), ciphertext)
+    } else if *alg == oid::PBE_SHA1_RC2_40 {
+        cbc_decrypt(rc2::Rc2::new_with_eff_key_len(&derive(1, 5), 40), &derive(2, 8), ciphertext)
+    } else {
+        Err(format!("unsupported encryption algorithm {}", oid::describe(alg)))
+    }
+}
+
+// RFC 7292 appendix B.2: id 1 for keys, 2 for IVs, 3 for MAC keys
+fn pkcs12_kdf(hash: HashAlg, id: u8, password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let v = hash.block_len();
+    let repeat = |data: &[u8]| -> Vec<u8> {
+        data.iter().copied().cycle().take(data.len().div_ceil(v) * v).collect()
+    };
+    let mut input = repeat(salt);
+    input.extend(repeat(password));
+
+    let mut out = Vec::with_capacity(len + hash.output_len());
+    while out.len() < len {
+        let mut a = [vec![id; v], input.clone()].concat();
+        for _ in 0..iterations.max(1) {
+            a = hash.digest(&a);
+        }
+        out.extend_from_slice(&a);
+        // every v byte block of the input += B + 1, B being A repeated to v bytes
+        let b: Vec<u8> = a.iter().copied().cycle().take(v).collect();
+        for block in input.chunks_mut(v) {
+            let mut carry = 1u16;
+            for (x, y) in block.iter_mut().zip(&b).rev() {
+                let sum = *x as u16 + *y as u16 + carry;
+                *x = sum as u8;
+                carry = sum >> 8;
+            }
+        }
+    }
+    out.truncate(len);
+    out
+}
+
+// BMPString, big endian UTF-16 with a terminating zero
+fn bmp_password(password: &str) -> Vec<u8> {
+    password.encode_utf16().chain([0]).flat_map(u16::to_be_bytes).collect()
+}
+
+// the value of a friendlyName SET, a single BMPString
+fn decode_bmp_string(values: &[u8]) -> Option<String> {
+    let bytes = Constructed::decode(Bytes::copy_from_slice(values), Mode::Ber, |cons| {
+        cons.take_primitive_if(Tag::BMP_STRING, |content| content.take_all())
+    }).ok()?;
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16(&units).ok()
+}
+
+fn cbc_decrypt<C: BlockCipher + BlockDecryptMut>(cipher: C, iv: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+    use aes::cipher::block_padding::Pkcs7;
+
+    cbc::Decryptor::<C>::inner_iv_slice_init(cipher, iv)
+        .map_err(|_| "invalid IV length".to_string())?
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| "bad padding, wrong password?".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::PrivateKey;
+    use crate::testdata::{self, ALICE, EC, INT};
+
+    fn open(der: &[u8], password: &str) -> (Pfx, Pkcs12Contents) {
+        let pfx = Pfx::from_der(der).unwrap();
+        assert_eq!(pfx.verify_mac(password), Some(Ok(())));
+        let contents = pfx.contents(password).unwrap();
+        (pfx, contents)
+    }
+
+    // the only key, which has to be the one of the certificate it is tied to
+    fn key_and_cert(contents: &Pkcs12Contents) -> (PrivateKey, &Certificate) {
+        let [key] = contents.keys.as_slice() else { panic!("{} keys", contents.keys.len()) };
+        let cert = &contents.cert_for(key).unwrap().cert;
+        let private_key = PrivateKey::from_der(&key.pkcs8).unwrap();
+        assert!(private_key.matches(&cert.tbs_certificate.subject_public_key_info));
+        (private_key, cert)
+    }
+
+    #[test]
+    fn pbes2() {
+        let (pfx, contents) = open(include_bytes!("../testdata/alice.p12"), "secret");
+        assert_eq!(pfx.mac_data.unwrap().digest_algorithm.algorithm, oid::SHA256);
+        let certs: Vec<_> = contents.certs.iter().map(|bag| bag.cert.raw.to_vec()).collect();
+        assert_eq!(certs, [testdata::der(ALICE), testdata::der(INT)]);
+        assert_eq!(contents.certs[0].attributes.friendly_name.as_deref(), Some("alice"));
+        assert_eq!(contents.keys[0].encryption.as_ref().unwrap().algorithm, oid::PBES2);
+        let (_, cert) = key_and_cert(&contents);
+        assert_eq!(cert.raw.to_vec(), testdata::der(ALICE));
+    }
+
+    #[test]
+    fn legacy_ciphers() {
+        let (pfx, contents) = open(include_bytes!("../testdata/alice_3des.p12"), "secret");
+        assert_eq!(pfx.mac_data.unwrap().digest_algorithm.algorithm, oid::SHA1);
+        key_and_cert(&contents);
+
+        // RC2 for the certificates, an empty password
+        let (_, contents) = open(include_bytes!("../testdata/ec_legacy.p12"), "");
+        let (_, cert) = key_and_cert(&contents);
+        assert_eq!(cert.raw.to_vec(), testdata::der(EC));
+    }
+
+    #[test]
+    fn wrong_password() {
+        let pfx = Pfx::from_der(include_bytes!("../testdata/alice.p12")).unwrap();
+        assert_eq!(pfx.verify_mac("guess").unwrap().unwrap_err(), "MAC verification failed, wrong password or modified file");
+        assert!(pfx.contents("guess").is_err());
+    }
+}
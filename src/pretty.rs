@@ -0,0 +1,545 @@
+// human readable output in the spirit of `openssl x509 -text` and `openssl pkcs7 -print_certs`,
+// behind the Display implementations of the parsed structures
+
+use std::fmt;
+
+use bcder::decode::Constructed;
+use bcder::{Mode, OctetString, Oid, Tag};
+use chrono::{TimeZone, Utc};
+
+use crate::crl::{CertificateList, RevokedCertificate};
+use crate::crypto::HashAlg;
+use crate::enveloped::{EnvelopedData, OriginatorIdentifierOrKey, RecipientIdentifier, RecipientInfo};
+use crate::timestamp::TimeStampToken;
+use crate::attributes::{CmsAlgorithmProtection, CommitmentTypeIndication, SigningCertificate};
+use crate::{certs, extensions, name, oid};
+use crate::{AlgorithmIdentifier, Attribute, Certificate, Extension, Pkcs7, Pkcs7Content, SignedData, SignerIdentifier};
+use crate::{SignerInfo, SubjectPublicKeyInfo, Validity};
+
+const INDENT: &str = "    ";
+const HEX_PER_LINE: usize = 18; // bytes per line of hex blocks, as openssl
+
+// lines indented by nesting level
+#[derive(Default)]
+pub struct Printer {
+    out: String,
+    level: usize,
+}
+
+impl Printer {
+    pub fn line(&mut self, text: impl AsRef<str>) {
+        for _ in 0..self.level {
+            self.out.push_str(INDENT);
+        }
+        self.out.push_str(text.as_ref());
+        self.out.push('\n');
+    }
+
+    pub fn field(&mut self, label: &str, value: impl fmt::Display) {
+        self.line(format!("{}: {}", label, value));
+    }
+
+    // "label:" and the body one level deeper
+    pub fn section(&mut self, label: &str, body: impl FnOnce(&mut Printer)) {
+        self.line(format!("{}:", label));
+        self.level += 1;
+        body(self);
+        self.level -= 1;
+    }
+
+    pub fn hex_block(&mut self, label: &str, bytes: &[u8]) {
+        self.section(label, |p| {
+            for chunk in bytes.chunks(HEX_PER_LINE) {
+                p.line(hex_colons(chunk));
+            }
+        });
+    }
+
+    pub fn finish(self) -> String {
+        self.out.trim_end_matches('\n').to_string()
+    }
+}
+
+pub fn hex_colons(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
+
+// serials are kept as hex strings
+pub fn serial(hex_serial: &str) -> String {
+    hex::decode(hex_serial).map_or_else(|_| hex_serial.to_string(), |bytes| hex_colons(&bytes))
+}
+
+pub fn fingerprint(der: &[u8], hash: HashAlg) -> String {
+    hex_colons(&hash.digest(der)).to_uppercase()
+}
+
+pub fn format_time(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => timestamp.to_string(),
+    }
+}
+
+// modulus length for RSA, field size for the supported curves
+pub fn key_size(spki: &SubjectPublicKeyInfo) -> Option<usize> {
+    let algorithm = &spki.algorithm;
+    if algorithm.algorithm == oid::RSA_ENCRYPTION || algorithm.algorithm == oid::RSASSA_PSS {
+        let modulus = Constructed::decode(spki.subject_public_key.as_slice(), Mode::Der, |cons| {
+            cons.take_sequence(|cons| {
+                let modulus = cons.take_primitive_if(Tag::INTEGER, |content| Ok(content.take_all()?.to_vec()))?;
+                cons.skip_all()?;
+                Ok(modulus)
+            })
+        }).ok()?;
+        let modulus: Vec<u8> = modulus.into_iter().skip_while(|b| *b == 0).collect();
+        let first = *modulus.first()?;
+        Some(modulus.len() * 8 - first.leading_zeros() as usize)
+    } else if algorithm.algorithm == oid::EC_PUBLIC_KEY {
+        match curve(algorithm)? {
+            curve if curve == oid::SECP256R1 => Some(256),
+            curve if curve == oid::SECP384R1 => Some(384),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+fn curve(algorithm: &AlgorithmIdentifier) -> Option<Oid> {
+    Constructed::decode(algorithm.parameters.as_deref()?, Mode::Der, Oid::take_from).ok()
+}
+
+fn print_pkcs7(p: &mut Printer, pkcs7: &Pkcs7) {
+    match &pkcs7.content {
+        Pkcs7Content::SignedData(signed_data) => print_signed_data(p, signed_data),
+        Pkcs7Content::EnvelopedData(enveloped) | Pkcs7Content::AuthEnvelopedData(enveloped) => print_enveloped(p, enveloped),
+    }
+}
+
+fn print_signed_data(p: &mut Printer, signed_data: &SignedData) {
+    p.section("SignedData", |p| {
+        p.field("Version", signed_data.version);
+        let digests: Vec<String> = signed_data.digest_algorithms.iter().map(|a| a.to_string()).collect();
+        p.field("Digest Algorithms", digests.join(", "));
+        let content_info = &signed_data.content_info;
+        match &content_info.content {
+            Some(content) => p.field("Content", format!("{}, {} bytes", oid::describe(&content_info.content_type), content.len())),
+            None => p.field("Content", format!("{}, detached", oid::describe(&content_info.content_type))),
+        }
+
+        p.section(&format!("Certificates ({})", signed_data.certs.len()), |p| {
+            for cert in &signed_data.certs {
+                print_certificate(p, cert);
+            }
+        });
+        let crls = certs::split_crls(&signed_data.crls);
+        p.section(&format!("CRLs ({})", crls.len()), |p| {
+            for der in crls {
+                match CertificateList::from_der(&der) {
+                    Ok(crl) => print_crl(p, &crl),
+                    Err(_) => p.hex_block("Other Revocation Info", &der),
+                }
+            }
+        });
+        p.section(&format!("Signers ({})", signed_data.signer_infos.len()), |p| {
+            for signer_info in &signed_data.signer_infos {
+                print_signer_info(p, signer_info);
+            }
+        });
+    });
+}
+
+fn print_signer_info(p: &mut Printer, signer_info: &SignerInfo) {
+    p.section("Signer Info", |p| {
+        p.field("Version", signer_info.version);
+        print_issuer_and_serial(p, &signer_info.signer_identifier);
+        p.field("Digest Algorithm", &signer_info.digest_algorithm);
+        if let Some(attributes) = &signer_info.auth_attributes {
+            p.section("Signed Attributes", |p| attributes.iter().for_each(|a| print_attribute(p, a)));
+        }
+        p.field("Signature Algorithm", &signer_info.signature_algorithm);
+        p.hex_block("Signature", &signer_info.signature);
+        if let Some(attributes) = &signer_info.unauth_attributes {
+            p.section("Unsigned Attributes", |p| attributes.iter().for_each(|a| print_attribute(p, a)));
+        }
+    });
+}
+
+pub fn print_issuer_and_serial(p: &mut Printer, sid: &SignerIdentifier) {
+    p.field("Issuer", name::to_string(&sid.issuer));
+    p.field("Serial Number", hex_colons(&sid.serial_number));
+}
+
+// one line per value when it fits, a block otherwise
+pub fn print_attribute(p: &mut Printer, attribute: &Attribute) {
+    let label = oid::describe(&attribute.oid);
+    let decoded = if attribute.oid == oid::CONTENT_TYPE {
+        attribute.decode_values(Oid::take_from).map(|values| {
+            values.iter().for_each(|content_type| p.field(&label, oid::describe(content_type)));
+        })
+    } else if attribute.oid == oid::MESSAGE_DIGEST {
+        attribute.decode_values(OctetString::take_from).map(|values| {
+            values.iter().for_each(|digest| p.field(&label, hex_colons(&digest.to_bytes())));
+        })
+    } else if attribute.oid == oid::SIGNING_TIME {
+        attribute.decode_values(Validity::take_time_from).map(|values| {
+            values.iter().for_each(|(time, _)| p.field(&label, format_time(*time)));
+        })
+    } else if attribute.oid == oid::SMIME_CAPABILITIES {
+        attribute.decode_values(|cons| {
+            cons.take_sequence(|cons| {
+                let mut capabilities = Vec::new();
+                while let Some(capability) = cons.take_opt_sequence(AlgorithmIdentifier::take_content_from)? {
+                    capabilities.push(capability);
+                }
+                Ok(capabilities)
+            })
+        }).map(|values| {
+            for capabilities in values {
+                p.section(&label, |p| capabilities.iter().for_each(|c| p.line(c.to_string())));
+            }
+        })
+    } else if attribute.oid == oid::CMS_ALGORITHM_PROTECTION {
+        attribute.decode_values(CmsAlgorithmProtection::take_from).map(|values| {
+            for protection in values {
+                p.section(&label, |p| {
+                    p.field("Digest Algorithm", &protection.digest_algorithm);
+                    if let Some(alg) = &protection.signature_algorithm {
+                        p.field("Signature Algorithm", alg);
+                    }
+                    if let Some(alg) = &protection.mac_algorithm {
+                        p.field("MAC Algorithm", alg);
+                    }
+                });
+            }
+        })
+    } else if attribute.oid == oid::SIGNING_CERTIFICATE || attribute.oid == oid::SIGNING_CERTIFICATE_V2 {
+        let v2 = attribute.oid == oid::SIGNING_CERTIFICATE_V2;
+        attribute.decode_values(|cons| SigningCertificate::take_from(cons, v2)).map(|values| {
+            for signing_certificate in values {
+                p.section(&label, |p| {
+                    for cert in &signing_certificate.certs {
+                        p.field("Cert Hash", format!("{} {}", oid::describe(&cert.hash_algorithm), hex_colons(&cert.cert_hash)));
+                        if let Some(issuer_serial) = &cert.issuer_serial {
+                            for issuer in &issuer_serial.issuer {
+                                p.field("Issuer", name::to_string(issuer));
+                            }
+                            p.field("Serial Number", hex_colons(&issuer_serial.serial_number));
+                        }
+                    }
+                    for policy in &signing_certificate.policies {
+                        p.field("Policy", oid::describe(policy));
+                    }
+                });
+            }
+        })
+    } else if attribute.oid == oid::COMMITMENT_TYPE_INDICATION {
+        attribute.decode_values(CommitmentTypeIndication::take_from).map(|values| {
+            for indication in values {
+                p.field(&label, oid::describe(&indication.commitment_type));
+            }
+        })
+    } else if attribute.oid == oid::SIGNATURE_TIME_STAMP_TOKEN {
+        let tokens = attribute.values().iter().map(|value| TimeStampToken::from_der(value)).collect::<Result<Vec<_>, _>>();
+        tokens.map(|tokens| {
+            for token in tokens {
+                p.section(&label, |p| {
+                    let tst_info = &token.tst_info;
+                    p.field("Time", format_time(tst_info.gen_time));
+                    p.field("Policy", oid::describe(&tst_info.policy));
+                    p.field("Serial Number", serial(&tst_info.serial_number));
+                    p.field("Imprint", format!("{} {}", tst_info.hash_algorithm, hex_colons(&tst_info.hashed_message)));
+                    if let Some(cert) = token.tsa_certificate() {
+                        p.field("TSA", name::to_string(&cert.tbs_certificate.subject));
+                    }
+                });
+            }
+        })
+    } else {
+        Err(String::new())
+    };
+    // unknown or malformed, the raw values
+    if decoded.is_err() {
+        for value in attribute.values() {
+            p.hex_block(&label, &value);
+        }
+    }
+}
+
+fn print_certificate(p: &mut Printer, cert: &Certificate) {
+    let tbs = &cert.tbs_certificate;
+    p.section("Certificate", |p| {
+        let version = tbs.version.unwrap_or(0);
+        p.field("Version", format!("{} (0x{:x})", version + 1, version));
+        p.field("Serial Number", serial(&tbs.serial_number));
+        p.field("Signature Algorithm", &tbs.signature_algorithm);
+        p.field("Issuer", name::to_string(&tbs.issuer));
+        p.section("Validity", |p| {
+            p.field("Not Before", format_time(tbs.validity.not_before));
+            p.field("Not After", format_time(tbs.validity.not_after));
+        });
+        p.field("Subject", name::to_string(&tbs.subject));
+        print_public_key(p, &tbs.subject_public_key_info);
+        if let Some(extensions) = &tbs.extensions {
+            p.section("X509v3 extensions", |p| extensions.iter().for_each(|e| print_extension(p, e)));
+        }
+        p.field("Signature Algorithm", &cert.signature_algorithm);
+        p.hex_block("Signature Value", &cert.signature_value);
+        p.field("SHA1 Fingerprint", fingerprint(&cert.raw, HashAlg::Sha1));
+        p.field("SHA256 Fingerprint", fingerprint(&cert.raw, HashAlg::Sha256));
+    });
+}
+
+fn print_public_key(p: &mut Printer, spki: &SubjectPublicKeyInfo) {
+    p.section("Subject Public Key Info", |p| {
+        p.field("Public Key Algorithm", &spki.algorithm);
+        match key_size(spki) {
+            Some(bits) => p.field("Public-Key", format!("({} bit)", bits)),
+            None => p.field("Public-Key", format!("({} bytes)", spki.subject_public_key.len())),
+        }
+    });
+}
+
+fn print_extension(p: &mut Printer, extension: &Extension) {
+    let mut label = oid::describe(&extension.extn_id);
+    if extension.critical {
+        label.push_str(" (critical)");
+    }
+    let value = extension.extn_value.as_slice();
+    let id = &extension.extn_id;
+    let decoded = if *id == oid::BASIC_CONSTRAINTS {
+        extensions::basic_constraints(value).map(|bc| match (bc.ca, bc.path_len) {
+            (true, Some(path_len)) => format!("CA:TRUE, pathlen:{}", path_len),
+            (ca, _) => format!("CA:{}", if ca { "TRUE" } else { "FALSE" }),
+        })
+    } else if *id == oid::KEY_USAGE {
+        extensions::key_usage(value).map(|usages| usages.join(", "))
+    } else if *id == oid::EXT_KEY_USAGE {
+        extensions::ext_key_usage(value).map(|purposes| {
+            purposes.iter().map(oid::describe).collect::<Vec<_>>().join(", ")
+        })
+    } else if *id == oid::SUBJECT_KEY_IDENTIFIER {
+        extensions::subject_key_identifier(value).map(|key_id| hex_colons(&key_id))
+    } else if *id == oid::AUTHORITY_KEY_IDENTIFIER {
+        extensions::authority_key_identifier(value).map(|key_id| hex_colons(&key_id))
+    } else if *id == oid::SUBJECT_ALT_NAME {
+        extensions::general_names(value).map(|names| names.join(", "))
+    } else if *id == oid::QC_STATEMENTS {
+        extensions::qc_statements(value).map(|statements| statements.describe())
+    } else {
+        None
+    };
+    match decoded {
+        Some(decoded) => p.section(&label, |p| p.line(decoded)),
+        None => p.hex_block(&label, value),
+    }
+}
+
+fn print_crl(p: &mut Printer, crl: &CertificateList) {
+    p.section("Certificate Revocation List", |p| {
+        p.field("Version", crl.version.map_or(1, |v| v + 1));
+        p.field("Signature Algorithm", &crl.signature_algorithm);
+        p.field("Issuer", name::to_string(&crl.issuer));
+        p.field("Last Update", format_time(crl.this_update));
+        if let Some(next_update) = crl.next_update {
+            p.field("Next Update", format_time(next_update));
+        }
+        if let Some(extensions) = &crl.extensions {
+            p.section("CRL extensions", |p| extensions.iter().for_each(|e| print_extension(p, e)));
+        }
+        if crl.revoked_certificates.is_empty() {
+            p.line("No Revoked Certificates.");
+        } else {
+            p.section("Revoked Certificates", |p| crl.revoked_certificates.iter().for_each(|r| print_revoked(p, r)));
+        }
+        p.hex_block("Signature Value", &crl.signature_value);
+    });
+}
+
+fn print_revoked(p: &mut Printer, revoked: &RevokedCertificate) {
+    p.field("Serial Number", serial(&revoked.serial_number));
+    p.level += 1;
+    p.field("Revocation Date", format_time(revoked.revocation_date));
+    if let Some(extensions) = &revoked.extensions {
+        extensions.iter().for_each(|e| print_extension(p, e));
+    }
+    p.level -= 1;
+}
+
+fn print_enveloped(p: &mut Printer, enveloped: &EnvelopedData) {
+    let label = if enveloped.is_authenticated() { "AuthEnvelopedData" } else { "EnvelopedData" };
+    p.section(label, |p| {
+        p.field("Version", enveloped.version);
+        p.section(&format!("Recipient Infos ({})", enveloped.recipient_infos.len()), |p| {
+            enveloped.recipient_infos.iter().for_each(|r| print_recipient(p, r));
+        });
+        let info = &enveloped.encrypted_content_info;
+        p.section("Encrypted Content Info", |p| {
+            p.field("Content Type", oid::describe(&info.content_type));
+            p.field("Content Encryption Algorithm", &info.content_encryption_algorithm);
+            match &info.encrypted_content {
+                Some(content) => p.field("Encrypted Content", format!("{} bytes", content.len())),
+                None => p.field("Encrypted Content", "detached"),
+            }
+        });
+        if let Some(attributes) = &enveloped.auth_attributes {
+            p.section("Authenticated Attributes", |p| attributes.iter().for_each(|a| print_attribute(p, a)));
+        }
+        if let Some(mac) = &enveloped.mac {
+            p.field("MAC", hex_colons(mac));
+        }
+        if let Some(attributes) = &enveloped.unprotected_attributes {
+            p.section("Unprotected Attributes", |p| attributes.iter().for_each(|a| print_attribute(p, a)));
+        }
+    });
+}
+
+fn print_recipient_identifier(p: &mut Printer, rid: &RecipientIdentifier) {
+    match rid {
+        RecipientIdentifier::IssuerAndSerial(sid) => print_issuer_and_serial(p, sid),
+        RecipientIdentifier::SubjectKeyId(key_id) => p.field("Subject Key Identifier", hex_colons(key_id)),
+    }
+}
+
+fn print_recipient(p: &mut Printer, recipient: &RecipientInfo) {
+    match recipient {
+        RecipientInfo::KeyTrans(ktri) => p.section("Key Transport Recipient", |p| {
+            p.field("Version", ktri.version);
+            print_recipient_identifier(p, &ktri.rid);
+            p.field("Key Encryption Algorithm", &ktri.key_encryption_algorithm);
+            p.field("Encrypted Key", format!("{} bytes", ktri.encrypted_key.len()));
+        }),
+        RecipientInfo::KeyAgree(kari) => p.section("Key Agreement Recipient", |p| {
+            p.field("Version", kari.version);
+            match &kari.originator {
+                OriginatorIdentifierOrKey::IssuerAndSerial(sid) => p.section("Originator", |p| print_issuer_and_serial(p, sid)),
+                OriginatorIdentifierOrKey::SubjectKeyId(key_id) => p.field("Originator Key Identifier", hex_colons(key_id)),
+                OriginatorIdentifierOrKey::OriginatorKey { algorithm, public_key } => {
+                    p.field("Originator Key", format!("{}, {} bytes", algorithm, public_key.len()));
+                },
+            }
+            p.field("Key Encryption Algorithm", &kari.key_encryption_algorithm);
+            for key in &kari.recipient_encrypted_keys {
+                p.section("Recipient Encrypted Key", |p| {
+                    print_recipient_identifier(p, &key.rid);
+                    p.field("Encrypted Key", format!("{} bytes", key.encrypted_key.len()));
+                });
+            }
+        }),
+        RecipientInfo::Kek(kekri) => p.section("KEK Recipient", |p| {
+            p.field("Version", kekri.version);
+            p.field("Key Identifier", hex_colons(&kekri.key_identifier));
+            p.field("Key Encryption Algorithm", &kekri.key_encryption_algorithm);
+        }),
+        RecipientInfo::Password(pwri) => p.section("Password Recipient", |p| {
+            p.field("Version", pwri.version);
+            if let Some(derivation) = &pwri.key_derivation_algorithm {
+                p.field("Key Derivation Algorithm", derivation);
+            }
+            p.field("Key Encryption Algorithm", &pwri.key_encryption_algorithm);
+        }),
+        RecipientInfo::Other { ori_type, .. } => p.field("Other Recipient", oid::describe(ori_type)),
+    }
+}
+
+// name of the algorithm, PSS and key wrap parameters spelled out
+impl fmt::Display for AlgorithmIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", oid::describe(&self.algorithm))?;
+        let parameters = match self.parameters.as_deref() {
+            Some(parameters) => parameters,
+            None => return Ok(()),
+        };
+        // the inner algorithm of PWRI-KEK or the curve of an EC key
+        if let Ok(inner) = Constructed::decode(parameters, Mode::Der, AlgorithmIdentifier::take_from) {
+            return write!(f, " ({})", inner);
+        }
+        if let Ok(inner) = Constructed::decode(parameters, Mode::Der, Oid::take_from) {
+            return write!(f, " ({})", oid::describe(&inner));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Validity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} to {}", format_time(self.not_before), format_time(self.not_after))
+    }
+}
+
+macro_rules! display_with_printer {
+    ($($type:ty => $print:ident),* $(,)?) => {$(
+        impl fmt::Display for $type {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let mut printer = Printer::default();
+                $print(&mut printer, self);
+                f.write_str(&printer.finish())
+            }
+        }
+    )*};
+}
+
+display_with_printer! {
+    Pkcs7 => print_pkcs7,
+    SignedData => print_signed_data,
+    SignerInfo => print_signer_info,
+    Attribute => print_attribute,
+    Certificate => print_certificate,
+    SubjectPublicKeyInfo => print_public_key,
+    Extension => print_extension,
+    CertificateList => print_crl,
+    EnvelopedData => print_enveloped,
+    RecipientInfo => print_recipient,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_pkcs7;
+    use crate::testdata::{self, INT, SIGNED};
+
+    #[test]
+    fn enveloped() {
+        let pkcs7 = parse_pkcs7(include_bytes!("../testdata/kekri.p7m")).unwrap();
+        assert_eq!(pkcs7.to_string(), "\
+EnvelopedData:
+    Version: 2
+    Recipient Infos (1):
+        KEK Recipient:
+            Version: 4
+            Key Identifier: 6b:65:6b
+            Key Encryption Algorithm: id-aes128-wrap
+    Encrypted Content Info:
+        Content Type: pkcs7-data
+        Content Encryption Algorithm: aes-128-cbc
+        Encrypted Content: 16 bytes");
+    }
+
+    #[test]
+    fn certificate() {
+        let cert = testdata::cert(INT);
+        let text = cert.to_string();
+        assert!(text.starts_with("Certificate:\n    Version: 3 (0x2)\n"));
+        assert!(text.contains("\n    Subject: CN=Test Intermediate, O=Acme\n"));
+        assert!(text.contains("\n        Public Key Algorithm: id-ecPublicKey (prime256v1)\n        Public-Key: (256 bit)\n"));
+        assert!(text.contains("\n        basicConstraints (critical):\n            CA:TRUE\n"));
+        assert_eq!(cert.tbs_certificate.validity.to_string(), "2020-01-01 00:00:00 UTC to 2050-01-01 00:00:00 UTC");
+    }
+
+    #[test]
+    fn signer_info() {
+        let pkcs7 = parse_pkcs7(SIGNED).unwrap();
+        let text = pkcs7.signed_data().unwrap().signer_infos[0].to_string();
+        assert!(text.contains("\n    Issuer: CN=Test Intermediate, O=Acme\n"));
+        assert!(text.contains("\n        contentType: pkcs7-data\n"));
+        assert!(text.contains("\n        messageDigest: f5:40:a0:c8:"));
+        assert!(text.contains("\n    Signature Algorithm: rsaEncryption\n"));
+    }
+
+    #[test]
+    fn serials_and_hex() {
+        assert_eq!(serial("01ff"), "01:ff");
+        assert_eq!(serial("not hex"), "not hex");
+        assert_eq!(hex_colons(&[0, 10, 255]), "00:0a:ff");
+    }
+}
@@ -0,0 +1,291 @@
+// machine readable verification outcome, what `verify --format json` prints
+
+use bcder::decode::Constructed;
+use bcder::{Mode, OctetString, Oid};
+use chrono::{SecondsFormat, TimeZone, Utc};
+use serde::Serialize;
+
+use crate::crypto::HashAlg;
+use crate::extensions::QcStatements;
+use crate::timestamp::TimestampCheck;
+use crate::verify::{ChainEntry, RevocationStatus, SignerVerification, VerifyStatus};
+use crate::{name, oid};
+use crate::{Attribute, Certificate, SignedData, Validity};
+
+#[derive(Serialize)]
+pub struct VerificationReport {
+    pub status: VerifyStatus, // all signers together
+    pub exit_code: i32,
+    pub verification_time: String,
+    pub content_type: String,
+    pub detached: bool,
+    pub signers: Vec<SignerReport>,
+}
+
+#[derive(Serialize)]
+pub struct SignerReport {
+    pub identity: SignerIdentity,
+    pub digest_algorithm: String,
+    pub signature_algorithm: String,
+    pub signed_attributes: Vec<AttributeReport>,
+    pub unsigned_attributes: Vec<AttributeReport>,
+    pub signature: SignatureReport,
+    pub chain: Vec<CertificateReport>, // signer first
+    pub timestamp: Option<TimestampReport>,
+    pub status: VerifyStatus,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SignerIdentity {
+    pub issuer: String,
+    pub serial_number: String,
+    pub subject: Option<String>, // None when the certificate was not found
+    pub certificate_sha256: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct AttributeReport {
+    pub oid: String,
+    pub name: Option<String>, // from the OID registry
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SignatureReport {
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CertificateReport {
+    pub subject: String,
+    pub issuer: String,
+    pub serial_number: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub time_valid: bool,
+    pub trust_anchor: bool,
+    pub revocation: &'static str, // good, revoked or unknown
+    pub revoked_at: Option<String>,
+    pub qc_statements: Option<QcStatements>,
+    pub qc_statements_error: Option<String>, // set when the extension is present but malformed
+}
+
+#[derive(Serialize)]
+pub struct TimestampReport {
+    pub valid: bool,
+    pub error: Option<String>,
+    pub gen_time: Option<String>, // None when the token could not be read
+    pub policy: Option<String>,
+    pub serial_number: Option<String>,
+    pub hash_algorithm: Option<String>,
+    pub tsa: Option<String>,
+    pub tsa_trusted: bool,
+}
+
+impl VerificationReport {
+    pub fn new(signed_data: &SignedData, results: &[SignerVerification], time: i64, detached: bool) -> Self {
+        let status = VerifyStatus::combine(results.iter().map(|r| r.status));
+        VerificationReport {
+            status,
+            exit_code: status.exit_code(),
+            verification_time: rfc3339(time),
+            content_type: oid::describe(&signed_data.content_info.content_type),
+            detached,
+            signers: results.iter().map(SignerReport::new).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+impl SignerReport {
+    fn new(result: &SignerVerification) -> Self {
+        let signer_info = result.signer_info;
+        let sid = &signer_info.signer_identifier;
+        let attributes = |attributes: &Option<Vec<Attribute>>| -> Vec<AttributeReport> {
+            attributes.iter().flatten().map(|attribute| AttributeReport {
+                oid: attribute.oid.to_string(),
+                name: oid::name(&attribute.oid),
+                values: attribute_values(attribute),
+            }).collect()
+        };
+
+        SignerReport {
+            identity: SignerIdentity {
+                issuer: name::to_string(&sid.issuer),
+                serial_number: hex::encode(&sid.serial_number),
+                subject: result.signer_cert.map(|cert| name::to_string(&cert.tbs_certificate.subject)),
+                certificate_sha256: result.signer_cert.map(|cert| hex::encode(HashAlg::Sha256.digest(&cert.raw))),
+            },
+            digest_algorithm: oid::describe(&signer_info.digest_algorithm.algorithm),
+            signature_algorithm: oid::describe(&signer_info.signature_algorithm.algorithm),
+            signed_attributes: attributes(&signer_info.auth_attributes),
+            unsigned_attributes: attributes(&signer_info.unauth_attributes),
+            signature: SignatureReport {
+                valid: result.signature.is_ok(),
+                error: result.signature.as_ref().err().cloned(),
+            },
+            chain: result.chain.iter().map(CertificateReport::new).collect(),
+            timestamp: result.timestamp.as_ref().map(TimestampReport::new),
+            status: result.status,
+            warnings: result.warnings.clone(),
+        }
+    }
+}
+
+impl CertificateReport {
+    fn new(entry: &ChainEntry) -> Self {
+        let cert: &Certificate = entry.cert;
+        let tbs = &cert.tbs_certificate;
+        let (revocation, revoked_at) = match entry.revocation {
+            RevocationStatus::Good => ("good", None),
+            RevocationStatus::Revoked { at } => ("revoked", Some(rfc3339(at))),
+            RevocationStatus::Unknown => ("unknown", None),
+        };
+        let (qc_statements, qc_statements_error) = match tbs.qc_statements() {
+            Some(Ok(statements)) => (Some(statements), None),
+            Some(Err(error)) => (None, Some(error)),
+            None => (None, None),
+        };
+        CertificateReport {
+            subject: name::to_string(&tbs.subject),
+            issuer: name::to_string(&tbs.issuer),
+            serial_number: tbs.serial_number.clone(),
+            not_before: rfc3339(tbs.validity.not_before),
+            not_after: rfc3339(tbs.validity.not_after),
+            time_valid: entry.time_valid,
+            trust_anchor: entry.trust_anchor,
+            revocation,
+            revoked_at,
+            qc_statements,
+            qc_statements_error,
+        }
+    }
+}
+
+impl TimestampReport {
+    fn new(timestamp: &Result<TimestampCheck, String>) -> Self {
+        match timestamp {
+            Ok(check) => TimestampReport {
+                valid: check.result.is_ok(),
+                error: check.result.as_ref().err().cloned(),
+                gen_time: Some(rfc3339(check.gen_time)),
+                policy: Some(check.policy.to_string()),
+                serial_number: Some(check.serial_number.clone()),
+                hash_algorithm: Some(oid::describe(&check.hash_algorithm)),
+                tsa: check.tsa.clone(),
+                tsa_trusted: check.tsa_trusted,
+            },
+            Err(e) => TimestampReport {
+                valid: false,
+                error: Some(e.clone()),
+                gen_time: None,
+                policy: None,
+                serial_number: None,
+                hash_algorithm: None,
+                tsa: None,
+                tsa_trusted: false,
+            },
+        }
+    }
+}
+
+// every value of the SET, decoded for the well known attributes and hex DER for the rest
+pub fn attribute_values(attribute: &Attribute) -> Vec<String> {
+    attribute.values().iter().map(|value| describe_value(&attribute.oid, value)).collect()
+}
+
+fn describe_value(attribute_type: &Oid, value: &[u8]) -> String {
+    let decoded = if *attribute_type == oid::CONTENT_TYPE {
+        Constructed::decode(value, Mode::Der, Oid::take_from).ok().map(|content_type| oid::describe(&content_type))
+    } else if *attribute_type == oid::MESSAGE_DIGEST {
+        Constructed::decode(value, Mode::Der, OctetString::take_from).ok().map(|digest| hex::encode(digest.to_bytes()))
+    } else if *attribute_type == oid::SIGNING_TIME {
+        Constructed::decode(value, Mode::Der, Validity::take_time_from).ok().map(|(time, _)| rfc3339(time))
+    } else {
+        None
+    };
+    decoded.unwrap_or_else(|| hex::encode(value))
+}
+
+pub fn rfc3339(timestamp: i64) -> String {
+    match Utc.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        None => timestamp.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, SIGNED, TIME, TS, TS_UNTRUSTED};
+    use crate::{parse_pkcs7, verify};
+
+    fn report(der: &[u8]) -> serde_json::Value {
+        let pkcs7 = parse_pkcs7(der).unwrap();
+        let signed_data = pkcs7.signed_data().unwrap();
+        let options = testdata::options();
+        let results = verify::verify_signed_data(signed_data, &options).unwrap();
+        serde_json::from_str(&VerificationReport::new(signed_data, &results, TIME, false).to_json()).unwrap()
+    }
+
+    #[test]
+    fn signer() {
+        let report = report(SIGNED);
+        assert_eq!(report["status"], "valid");
+        assert_eq!(report["exit_code"], 0);
+        assert_eq!(report["verification_time"], "2027-01-01T00:00:00Z");
+        assert_eq!(report["content_type"], "pkcs7-data");
+        let signer = &report["signers"][0];
+        assert_eq!(signer["identity"]["subject"], "CN=Alice, emailAddress=alice@example.com");
+        assert_eq!(signer["digest_algorithm"], "sha256");
+        let content_type = signer["signed_attributes"].as_array().unwrap().iter()
+            .find(|attribute| attribute["name"] == "contentType").unwrap();
+        assert_eq!(content_type["values"][0], "pkcs7-data");
+        let chain = signer["chain"].as_array().unwrap();
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain[2]["subject"], "CN=Test Root, O=Acme");
+        assert!(signer["timestamp"].is_null());
+    }
+
+    #[test]
+    fn qc_statements() {
+        let report = report(include_bytes!("../testdata/qualified.p7m"));
+        let chain = &report["signers"][0]["chain"];
+        let statements = &chain[0]["qc_statements"];
+        assert_eq!(statements["compliance"], true);
+        assert_eq!(statements["types"][0], "esign");
+        assert_eq!(statements["limit_value"]["currency"], "EUR");
+        assert!(chain[1]["qc_statements"].is_null());
+        assert!(chain[0]["qc_statements_error"].is_null());
+    }
+
+    #[test]
+    fn qc_statements_malformed() {
+        let mut cert = testdata::cert(include_bytes!("../testdata/qualified.crt"));
+        let extension = cert.tbs_certificate.extensions.as_mut().unwrap().iter_mut()
+            .find(|extension| extension.extn_id == oid::QC_STATEMENTS).unwrap();
+        extension.extn_value = hex::decode("3003020101").unwrap();
+        let entry = ChainEntry { cert: &cert, trust_anchor: false, time_valid: true, revocation: RevocationStatus::Unknown };
+        let report = serde_json::to_value(CertificateReport::new(&entry)).unwrap();
+        assert!(report["qc_statements"].is_null());
+        assert_eq!(report["qc_statements_error"], "malformed qcStatements extension");
+    }
+
+    #[test]
+    fn timestamp() {
+        let timestamp = &report(TS)["signers"][0]["timestamp"];
+        assert_eq!(timestamp["valid"], true);
+        assert_eq!(timestamp["tsa"], "CN=Test TSA, O=Acme");
+        assert_eq!(timestamp["tsa_trusted"], true);
+        assert_eq!(timestamp["policy"], "1.2.3.4.1");
+
+        let timestamp = &report(TS_UNTRUSTED)["signers"][0]["timestamp"];
+        assert_eq!(timestamp["valid"], false);
+        assert_eq!(timestamp["tsa_trusted"], false);
+    }
+}
@@ -0,0 +1,260 @@
+// producing CMS SignedData (RFC 5652 section 5) with the parser's own types
+
+use bcder::encode::PrimitiveContent;
+use bcder::{Oid, OctetString, Tag};
+use chrono::{DateTime, Datelike, Utc};
+use rsa::Pkcs1v15Sign;
+use sha1::Sha1;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+
+use crate::attributes::CmsAlgorithmProtection;
+use crate::crypto::HashAlg;
+use crate::encode::to_der;
+use crate::keys::PrivateKey;
+use crate::oid;
+use crate::{AlgorithmIdentifier, Attribute, Certificate, ContentInfo, SignedData, SignerIdentifier, SignerInfo};
+
+pub struct SignOptions {
+    pub digest_algorithm: HashAlg,
+    pub detached: bool,
+    pub content_type: Oid, // eContentType, id-data unless signing another CMS structure
+    pub signing_time: Option<DateTime<Utc>>, // now when None
+    pub extra_certs: Vec<Vec<u8>>, // DER of intermediates to ship along
+}
+
+impl Default for SignOptions {
+    fn default() -> Self {
+        SignOptions {
+            digest_algorithm: HashAlg::Sha256,
+            detached: false,
+            content_type: oid::owned(oid::DATA),
+            signing_time: None,
+            extra_certs: Vec::new(),
+        }
+    }
+}
+
+// signs content and returns the DER ContentInfo of the SignedData
+pub fn sign(content: &[u8], signer_cert_der: &[u8], key: &PrivateKey, options: &SignOptions) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let signer_cert = Certificate::from_der(signer_cert_der)?;
+    let signer_info = build_signer_info(content, &signer_cert, key, options)?;
+
+    let mut certs = vec![signer_cert];
+    for der in &options.extra_certs {
+        certs.push(Certificate::from_der(der)?);
+    }
+
+    let signed_data = build_signed_data(content, certs, vec![signer_info], options);
+    Ok(signed_data.to_content_info_der())
+}
+
+// SignerInfo with contentType, signingTime, messageDigest and CMSAlgorithmProtection signed attributes
+pub fn build_signer_info(content: &[u8], signer_cert: &Certificate, key: &PrivateKey, options: &SignOptions) -> Result<SignerInfo, Box<dyn std::error::Error>> {
+    if !key.matches(&signer_cert.tbs_certificate.subject_public_key_info) {
+        return Err("the private key does not match the signer certificate".into());
+    }
+    let digest_alg = options.digest_algorithm;
+    let message_digest = digest_alg.digest(content);
+    let signing_time = options.signing_time.unwrap_or_else(Utc::now);
+    let digest_algorithm = AlgorithmIdentifier {
+        algorithm: oid::owned(digest_alg.oid()),
+        parameters: None,
+    };
+    let signature_algorithm = signature_algorithm(key, digest_alg);
+    let algorithm_protection = CmsAlgorithmProtection {
+        digest_algorithm: AlgorithmIdentifier {
+            algorithm: digest_algorithm.algorithm.clone(),
+            parameters: None,
+        },
+        signature_algorithm: Some(AlgorithmIdentifier {
+            algorithm: signature_algorithm.algorithm.clone(),
+            parameters: signature_algorithm.parameters.clone(),
+        }),
+        mac_algorithm: None,
+    };
+
+    let auth_attributes = vec![
+        Attribute {
+            oid: oid::owned(oid::CONTENT_TYPE),
+            value: to_der(options.content_type.encode_ref()),
+        },
+        Attribute {
+            oid: oid::owned(oid::SIGNING_TIME),
+            value: encode_time(&signing_time),
+        },
+        Attribute {
+            oid: oid::owned(oid::MESSAGE_DIGEST),
+            value: to_der(OctetString::encode_slice(&message_digest)),
+        },
+        Attribute {
+            oid: oid::owned(oid::CMS_ALGORITHM_PROTECTION),
+            value: to_der(algorithm_protection.encode_ref()),
+        },
+    ];
+
+    // SET OF in DER is sorted by the encoding of its elements
+    let mut encoded_attrs: Vec<Vec<u8>> = auth_attributes.iter().map(|a| to_der(a.encode_ref())).collect();
+    encoded_attrs.sort();
+    let auth_bytes = encoded_attrs.concat();
+
+    let signer_identifier = SignerIdentifier {
+        issuer: signer_cert.tbs_certificate.issuer.to_vec(),
+        serial_number: hex::decode(&signer_cert.tbs_certificate.serial_number)?,
+    };
+
+    let mut signer_info = SignerInfo {
+        version: 1,
+        signer_identifier,
+        digest_algorithm,
+        auth_attributes: Some(auth_attributes),
+        auth_bytes,
+        signature_algorithm,
+        signature: Vec::new(),
+        unauth_attributes: None,
+    };
+
+    let signed_digest = digest_alg.digest(&signer_info.signed_attrs_der());
+    signer_info.signature = sign_digest(key, digest_alg, &signed_digest)?;
+    Ok(signer_info)
+}
+
+pub fn build_signed_data(content: &[u8], certs: Vec<Certificate>, signer_infos: Vec<SignerInfo>, options: &SignOptions) -> SignedData {
+    // v3 when the encapsulated content is not id-data
+    let version: u8 = if options.content_type == oid::DATA { 1 } else { 3 };
+
+    let mut digest_algorithms: Vec<AlgorithmIdentifier> = Vec::new();
+    for signer_info in &signer_infos {
+        if !digest_algorithms.iter().any(|a| a.algorithm == signer_info.digest_algorithm.algorithm) {
+            digest_algorithms.push(AlgorithmIdentifier {
+                algorithm: signer_info.digest_algorithm.algorithm.clone(),
+                parameters: signer_info.digest_algorithm.parameters.clone(),
+            });
+        }
+    }
+
+    SignedData {
+        version,
+        digest_algorithms,
+        content_info: ContentInfo {
+            content_type: options.content_type.clone(),
+            content: (!options.detached).then(|| content.to_vec()),
+            content_raw: None,
+        },
+        certs,
+        crls: Vec::new(),
+        signer_infos,
+    }
+}
+
+// UTCTime through 2049, GeneralizedTime after (RFC 5652 section 11.3)
+fn encode_time(time: &DateTime<Utc>) -> Vec<u8> {
+    if (1950..2050).contains(&time.year()) {
+        let value = time.format("%y%m%d%H%M%SZ").to_string();
+        to_der(OctetString::encode_slice_as(value.into_bytes(), Tag::UTC_TIME))
+    } else {
+        let value = time.format("%Y%m%d%H%M%SZ").to_string();
+        to_der(OctetString::encode_slice_as(value.into_bytes(), Tag::GENERALIZED_TIME))
+    }
+}
+
+fn signature_algorithm(key: &PrivateKey, digest_alg: HashAlg) -> AlgorithmIdentifier {
+    match key {
+        // rsaEncryption is what most CMS implementations put here
+        PrivateKey::Rsa(_) => AlgorithmIdentifier {
+            algorithm: oid::owned(oid::RSA_ENCRYPTION),
+            parameters: Some(vec![0x05, 0x00]),
+        },
+        PrivateKey::P256(_) | PrivateKey::P384(_) => AlgorithmIdentifier {
+            algorithm: oid::owned(match digest_alg {
+                HashAlg::Sha1 => oid::ECDSA_WITH_SHA1,
+                HashAlg::Sha224 => oid::ECDSA_WITH_SHA224,
+                HashAlg::Sha256 => oid::ECDSA_WITH_SHA256,
+                HashAlg::Sha384 => oid::ECDSA_WITH_SHA384,
+                HashAlg::Sha512 => oid::ECDSA_WITH_SHA512,
+            }),
+            parameters: None,
+        },
+    }
+}
+
+fn sign_digest(key: &PrivateKey, digest_alg: HashAlg, digest: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    use p256::ecdsa::signature::hazmat::PrehashSigner;
+
+    match key {
+        PrivateKey::Rsa(rsa_key) => {
+            let padding = match digest_alg {
+                HashAlg::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+                HashAlg::Sha224 => Pkcs1v15Sign::new::<Sha224>(),
+                HashAlg::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+                HashAlg::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+                HashAlg::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+            };
+            Ok(rsa_key.sign(padding, digest)?)
+        },
+        PrivateKey::P256(secret) => {
+            let signing_key = p256::ecdsa::SigningKey::from(secret);
+            let signature: p256::ecdsa::Signature = signing_key.sign_prehash(digest)?;
+            Ok(signature.to_der().as_bytes().to_vec())
+        },
+        PrivateKey::P384(secret) => {
+            let signing_key = p384::ecdsa::SigningKey::from(secret);
+            let signature: p384::ecdsa::Signature = signing_key.sign_prehash(digest)?;
+            Ok(signature.to_der().as_bytes().to_vec())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, ALICE, ALICE_KEY, EC, EC_KEY, INT, MSG};
+    use crate::parse_pkcs7;
+    use p256::ecdsa::signature::hazmat::PrehashVerifier;
+    use rsa::pkcs1::DecodeRsaPublicKey;
+    use rsa::RsaPublicKey;
+
+    // signs MSG, reads it back and checks the signature with the certificate key
+    fn sign_and_verify(cert: &[u8], key: &[u8], options: SignOptions) {
+        let options = SignOptions { extra_certs: vec![testdata::der(INT)], ..options };
+        let signed = sign(MSG, &testdata::der(cert), &testdata::key(key), &options).unwrap();
+        let pkcs7 = parse_pkcs7(&signed).unwrap();
+        let signed_data = pkcs7.signed_data().unwrap();
+        assert_eq!(signed_data.content_info.content.as_deref(), (!options.detached).then_some(MSG));
+        assert_eq!(signed_data.certs.len(), 2);
+
+        let signer_info = &signed_data.signer_infos[0];
+        let digest = options.digest_algorithm.digest(&signer_info.signed_attrs_der());
+        let public_key = &signed_data.certs[0].tbs_certificate.subject_public_key_info.subject_public_key;
+        match testdata::key(key) {
+            PrivateKey::Rsa(_) => {
+                let padding = match options.digest_algorithm {
+                    HashAlg::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+                    _ => unimplemented!(),
+                };
+                RsaPublicKey::from_pkcs1_der(public_key).unwrap().verify(padding, &digest, &signer_info.signature).unwrap();
+            },
+            PrivateKey::P256(_) => {
+                let signature = p256::ecdsa::Signature::from_der(&signer_info.signature).unwrap();
+                p256::ecdsa::VerifyingKey::from_sec1_bytes(public_key).unwrap().verify_prehash(&digest, &signature).unwrap();
+            },
+            PrivateKey::P384(_) => unimplemented!(),
+        }
+    }
+
+    #[test]
+    fn rsa() {
+        sign_and_verify(ALICE, ALICE_KEY, SignOptions::default());
+    }
+
+    #[test]
+    fn ec_detached() {
+        let options = SignOptions { detached: true, digest_algorithm: HashAlg::Sha384, ..SignOptions::default() };
+        sign_and_verify(EC, EC_KEY, options);
+    }
+
+    #[test]
+    fn key_of_another_certificate() {
+        let error = sign(MSG, &testdata::der(ALICE), &testdata::key(EC_KEY), &SignOptions::default()).unwrap_err();
+        assert_eq!(error.to_string(), "the private key does not match the signer certificate");
+    }
+}
@@ -0,0 +1,175 @@
+// signature verification with the key of a SubjectPublicKeyInfo
+//
+// rsaEncryption / sha*WithRSAEncryption (PKCS#1 v1.5), RSASSA-PSS and
+// ecdsa-with-SHA* on P-256 and P-384
+
+use bcder::decode::Constructed;
+use bcder::{Mode, Oid, Tag};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::{Pkcs1v15Sign, Pss, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+
+use crate::crypto::HashAlg;
+use crate::oid;
+use crate::{AlgorithmIdentifier, SubjectPublicKeyInfo};
+
+#[derive(Debug)]
+pub enum SignatureError {
+    UnsupportedAlgorithm(String),
+    InvalidKey(String),
+    Malformed(String),
+    Invalid,
+}
+
+impl std::fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SignatureError::UnsupportedAlgorithm(alg) => write!(f, "unsupported signature algorithm {}", alg),
+            SignatureError::InvalidKey(e) => write!(f, "invalid public key: {}", e),
+            SignatureError::Malformed(e) => write!(f, "malformed signature: {}", e),
+            SignatureError::Invalid => write!(f, "signature does not match"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+// digest_alg is needed when the algorithm does not name the hash (rsaEncryption in CMS)
+pub fn verify_signature(
+    public_key: &SubjectPublicKeyInfo,
+    algorithm: &AlgorithmIdentifier,
+    digest_alg: Option<HashAlg>,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<(), SignatureError> {
+    let alg = &algorithm.algorithm;
+    let unsupported = || SignatureError::UnsupportedAlgorithm(oid::describe(alg));
+
+    if *alg == oid::RSASSA_PSS {
+        let (hash, salt_len) = decode_pss_params(algorithm.parameters.as_deref())?;
+        let key = rsa_public_key(public_key)?;
+        return verify_rsa_pss(&key, hash, salt_len, &hash.digest(data), signature);
+    }
+
+    if let Some(hash) = rsa_pkcs1_hash(alg) {
+        let hash = hash.or(digest_alg).ok_or_else(unsupported)?;
+        let key = rsa_public_key(public_key)?;
+        let padding = match hash {
+            HashAlg::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+            HashAlg::Sha224 => Pkcs1v15Sign::new::<Sha224>(),
+            HashAlg::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            HashAlg::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            HashAlg::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        };
+        return key.verify(padding, &hash.digest(data), signature).map_err(|_| SignatureError::Invalid);
+    }
+
+    let hash = ecdsa_hash(alg).ok_or_else(unsupported)?;
+    verify_ecdsa(public_key, &hash.digest(data), signature)
+}
+
+// Some(None) for plain rsaEncryption, the hash then comes from elsewhere
+fn rsa_pkcs1_hash(alg: &Oid) -> Option<Option<HashAlg>> {
+    if *alg == oid::RSA_ENCRYPTION { Some(None) }
+    else if *alg == oid::SHA1_WITH_RSA_ENCRYPTION { Some(Some(HashAlg::Sha1)) }
+    else if *alg == oid::SHA224_WITH_RSA_ENCRYPTION { Some(Some(HashAlg::Sha224)) }
+    else if *alg == oid::SHA256_WITH_RSA_ENCRYPTION { Some(Some(HashAlg::Sha256)) }
+    else if *alg == oid::SHA384_WITH_RSA_ENCRYPTION { Some(Some(HashAlg::Sha384)) }
+    else if *alg == oid::SHA512_WITH_RSA_ENCRYPTION { Some(Some(HashAlg::Sha512)) }
+    else { None }
+}
+
+fn ecdsa_hash(alg: &Oid) -> Option<HashAlg> {
+    if *alg == oid::ECDSA_WITH_SHA1 { Some(HashAlg::Sha1) }
+    else if *alg == oid::ECDSA_WITH_SHA224 { Some(HashAlg::Sha224) }
+    else if *alg == oid::ECDSA_WITH_SHA256 { Some(HashAlg::Sha256) }
+    else if *alg == oid::ECDSA_WITH_SHA384 { Some(HashAlg::Sha384) }
+    else if *alg == oid::ECDSA_WITH_SHA512 { Some(HashAlg::Sha512) }
+    else { None }
+}
+
+fn rsa_public_key(public_key: &SubjectPublicKeyInfo) -> Result<RsaPublicKey, SignatureError> {
+    if public_key.algorithm.algorithm != oid::RSA_ENCRYPTION && public_key.algorithm.algorithm != oid::RSASSA_PSS {
+        return Err(SignatureError::InvalidKey(format!("{} is not an RSA key", public_key.algorithm.algorithm)));
+    }
+    RsaPublicKey::from_pkcs1_der(&public_key.subject_public_key)
+        .map_err(|e| SignatureError::InvalidKey(e.to_string()))
+}
+
+fn verify_rsa_pss(key: &RsaPublicKey, hash: HashAlg, salt_len: usize, digest: &[u8], signature: &[u8]) -> Result<(), SignatureError> {
+    let padding = match hash {
+        HashAlg::Sha1 => Pss::new_with_salt::<Sha1>(salt_len),
+        HashAlg::Sha224 => Pss::new_with_salt::<Sha224>(salt_len),
+        HashAlg::Sha256 => Pss::new_with_salt::<Sha256>(salt_len),
+        HashAlg::Sha384 => Pss::new_with_salt::<Sha384>(salt_len),
+        HashAlg::Sha512 => Pss::new_with_salt::<Sha512>(salt_len),
+    };
+    key.verify(padding, digest, signature).map_err(|_| SignatureError::Invalid)
+}
+
+fn verify_ecdsa(public_key: &SubjectPublicKeyInfo, digest: &[u8], signature: &[u8]) -> Result<(), SignatureError> {
+    use p256::ecdsa::signature::hazmat::PrehashVerifier;
+
+    if public_key.algorithm.algorithm != oid::EC_PUBLIC_KEY {
+        return Err(SignatureError::InvalidKey(format!("{} is not an EC key", public_key.algorithm.algorithm)));
+    }
+    // namedCurve OID as the key algorithm parameters
+    let curve = public_key.algorithm.parameters.as_deref().and_then(|params| {
+        Constructed::decode(params, Mode::Der, Oid::take_from).ok()
+    }).ok_or_else(|| SignatureError::InvalidKey("missing named curve".into()))?;
+    let point = &public_key.subject_public_key;
+    let invalid_key = |e: p256::ecdsa::Error| SignatureError::InvalidKey(e.to_string());
+    let malformed = |e: p256::ecdsa::Error| SignatureError::Malformed(e.to_string());
+
+    if curve == oid::SECP256R1 {
+        let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(point).map_err(invalid_key)?;
+        let signature = p256::ecdsa::Signature::from_der(signature).map_err(malformed)?;
+        key.verify_prehash(digest, &signature).map_err(|_| SignatureError::Invalid)
+    } else if curve == oid::SECP384R1 {
+        let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(point).map_err(invalid_key)?;
+        let signature = p384::ecdsa::Signature::from_der(signature).map_err(malformed)?;
+        key.verify_prehash(digest, &signature).map_err(|_| SignatureError::Invalid)
+    } else {
+        Err(SignatureError::UnsupportedAlgorithm(format!("curve {}", curve)))
+    }
+}
+
+// RSASSA-PSS-params, all fields default to SHA-1 with a 20 byte salt
+fn decode_pss_params(params: Option<&[u8]>) -> Result<(HashAlg, usize), SignatureError> {
+    let params = params.unwrap_or_default();
+    if params.is_empty() {
+        return Ok((HashAlg::Sha1, 20));
+    }
+    let (hash, mgf, salt_len) = Constructed::decode(params, Mode::Der, |cons| {
+        cons.take_sequence(|cons| {
+            let hash = cons.take_opt_constructed_if(Tag::CTX_0, AlgorithmIdentifier::take_from)?;
+            let mgf = cons.take_opt_constructed_if(Tag::CTX_1, AlgorithmIdentifier::take_from)?;
+            let salt_len = cons.take_opt_constructed_if(Tag::CTX_2, |cons| cons.take_u64())?;
+            cons.skip_all()?; // trailerField, always 1
+            Ok((hash, mgf, salt_len))
+        })
+    }).map_err(|e| SignatureError::Malformed(e.to_string()))?;
+
+    let hash = match hash {
+        Some(alg) => HashAlg::from_oid(&alg.algorithm)
+            .ok_or_else(|| SignatureError::UnsupportedAlgorithm(alg.algorithm.to_string()))?,
+        None => HashAlg::Sha1,
+    };
+    // the rsa crate uses the message hash for MGF1 as well
+    let mgf_hash = match mgf {
+        Some(mgf) if mgf.algorithm == oid::MGF1 => {
+            let mgf_params = mgf.parameters.as_deref().unwrap_or_default();
+            let mgf_hash = Constructed::decode(mgf_params, Mode::Der, |cons| {
+                AlgorithmIdentifier::take_from(cons)
+            }).map_err(|e| SignatureError::Malformed(e.to_string()))?;
+            HashAlg::from_oid(&mgf_hash.algorithm)
+        },
+        Some(_) => None,
+        None => Some(HashAlg::Sha1),
+    };
+    if mgf_hash != Some(hash) {
+        return Err(SignatureError::UnsupportedAlgorithm("RSASSA-PSS with a different MGF1 hash".into()));
+    }
+    Ok((hash, salt_len.unwrap_or(20) as usize))
+}
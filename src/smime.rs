@@ -0,0 +1,275 @@
+// S/MIME signed messages (RFC 8551, formerly RFC 5751): multipart/signed with a detached signature over
+// the canonical first part, and application/pkcs7-mime signed-data with the content inside
+
+use base64::Engine;
+use serde::Serialize;
+
+use crate::report::VerificationReport;
+use crate::{extensions, name, oid, Certificate};
+
+#[derive(Debug)]
+pub struct SignedMessage {
+    pub from: Vec<String>, // addresses of the From header
+    pub signature: Vec<u8>, // DER of the ContentInfo
+    pub content: Option<Vec<u8>>, // the canonical first part of multipart/signed, None when inside the signature
+    pub micalg: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SmimeReport {
+    pub from: Vec<String>,
+    pub senders: Vec<SenderCheck>, // one per signer with a certificate
+    pub verification: VerificationReport,
+}
+
+#[derive(Serialize)]
+pub struct SenderCheck {
+    pub signer: usize,
+    pub emails: Vec<String>, // of the signer certificate
+    pub matches_from: bool,
+}
+
+// a MIME entity: unfolded headers and the raw body
+struct Entity<'a> {
+    headers: Vec<(String, String)>,
+    body: &'a [u8],
+}
+
+impl SignedMessage {
+    pub fn parse(message: &[u8]) -> Result<Self, String> {
+        let entity = Entity::parse(message);
+        let from = entity.header("from").map(addresses).unwrap_or_default();
+        let (mime_type, params) = entity.content_type();
+        let param = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone());
+
+        match mime_type.as_str() {
+            "multipart/signed" => {
+                let boundary = param("boundary").ok_or("multipart/signed without boundary")?;
+                let protocol = param("protocol").unwrap_or_default().to_ascii_lowercase();
+                if !matches!(protocol.as_str(), "application/pkcs7-signature" | "application/x-pkcs7-signature") {
+                    return Err(format!("multipart/signed protocol {} is not S/MIME", protocol));
+                }
+                let parts = multipart_parts(entity.body, &boundary);
+                let [content, signature] = parts.as_slice() else {
+                    return Err(format!("multipart/signed with {} parts instead of 2", parts.len()));
+                };
+                Ok(SignedMessage {
+                    from,
+                    signature: Entity::parse(signature).decoded_body()?,
+                    content: Some(canonicalize(content)),
+                    micalg: param("micalg"),
+                })
+            },
+            "application/pkcs7-mime" | "application/x-pkcs7-mime" => {
+                match param("smime-type").as_deref() {
+                    None | Some("signed-data") => {},
+                    Some("enveloped-data" | "authEnveloped-data") => return Err("the message is encrypted, decrypt it first".to_string()),
+                    Some(other) => return Err(format!("smime-type {} is not signed-data", other)),
+                }
+                Ok(SignedMessage { from, signature: entity.decoded_body()?, content: None, micalg: None })
+            },
+            other => Err(format!("{} is not an S/MIME signed message", other)),
+        }
+    }
+}
+
+// the rfc822Name entries of subjectAltName, the legacy emailAddress of the subject when there are none
+pub fn certificate_emails(cert: &Certificate) -> Vec<String> {
+    let tbs = &cert.tbs_certificate;
+    let emails: Vec<String> = tbs.find_extension(oid::SUBJECT_ALT_NAME)
+        .and_then(|extension| extensions::general_names(&extension.extn_value))
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|name| name.strip_prefix("email:").map(str::to_string))
+        .collect();
+    if !emails.is_empty() {
+        return emails;
+    }
+    name::entries(&tbs.subject).into_iter()
+        .filter(|(attribute_type, _)| *attribute_type == oid::EMAIL_ADDRESS)
+        .map(|(_, value)| value)
+        .collect()
+}
+
+// RFC 8551 section 3.1: the signer has to be the sender
+pub fn sender_matches(cert: &Certificate, from: &[String]) -> bool {
+    certificate_emails(cert).iter().any(|email| from.iter().any(|address| address.eq_ignore_ascii_case(email)))
+}
+
+impl<'a> Entity<'a> {
+    fn parse(data: &'a [u8]) -> Self {
+        let (head, body) = match find_blank_line(data) {
+            Some((end, body_start)) => (&data[..end], &data[body_start..]),
+            None => (data, &data[data.len()..]),
+        };
+        let mut headers: Vec<(String, String)> = Vec::new();
+        for line in String::from_utf8_lossy(head).lines() {
+            if line.starts_with([' ', '\t']) {
+                // folded, continues the previous header
+                if let Some((_, value)) = headers.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            } else if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+            }
+        }
+        Entity { headers, body }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    // the lowercase type/subtype and the parameters, names lowercase and values unquoted
+    fn content_type(&self) -> (String, Vec<(String, String)>) {
+        let value = self.header("content-type").unwrap_or("text/plain");
+        let mut fields = split_params(value).into_iter();
+        let mime_type = fields.next().unwrap_or_default().to_ascii_lowercase();
+        let params = fields.filter_map(|field| {
+            let (key, value) = field.split_once('=')?;
+            Some((key.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string()))
+        }).collect();
+        (mime_type, params)
+    }
+
+    fn decoded_body(&self) -> Result<Vec<u8>, String> {
+        let encoding = self.header("content-transfer-encoding").unwrap_or("7bit").to_ascii_lowercase();
+        match encoding.as_str() {
+            "base64" => {
+                let text: Vec<u8> = self.body.iter().copied().filter(|c| !c.is_ascii_whitespace()).collect();
+                base64::engine::general_purpose::STANDARD.decode(text).map_err(|e| format!("bad base64 body: {}", e))
+            },
+            "7bit" | "8bit" | "binary" => Ok(self.body.to_vec()),
+            other => Err(format!("unsupported transfer encoding {}", other)),
+        }
+    }
+}
+
+// header end and body start
+fn find_blank_line(data: &[u8]) -> Option<(usize, usize)> {
+    let crlf = data.windows(4).position(|w| w == b"\r\n\r\n").map(|pos| (pos, pos + 4));
+    let lf = data.windows(2).position(|w| w == b"\n\n").map(|pos| (pos, pos + 2));
+    match (crlf, lf) {
+        (Some(crlf), Some(lf)) => Some(if crlf.0 < lf.0 { crlf } else { lf }),
+        (crlf, lf) => crlf.or(lf),
+    }
+}
+
+// ';' separated, quoted strings kept whole
+fn split_params(value: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    for c in value.chars() {
+        match c {
+            ';' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => {
+                quoted ^= c == '"';
+                field.push(c);
+            },
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|field| field.trim().to_string()).filter(|field| !field.is_empty()).collect()
+}
+
+// the body parts between the delimiters (RFC 2046 section 5.1.1); the line break before a delimiter
+// belongs to the delimiter, not to the part
+fn multipart_parts<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let starts: Vec<usize> = (0..body.len())
+        .filter(|&pos| (pos == 0 || body[pos - 1] == b'\n') && body[pos..].starts_with(&delimiter))
+        .collect();
+    let mut parts = Vec::new();
+    for pair in starts.windows(2) {
+        let Some(line_end) = body[pair[0]..].iter().position(|c| *c == b'\n') else {
+            break;
+        };
+        let start = pair[0] + line_end + 1;
+        let mut end = pair[1] - 1; // the \n of the line break
+        if end > start && body[end - 1] == b'\r' {
+            end -= 1;
+        }
+        parts.push(&body[start..end.max(start)]);
+    }
+    parts
+}
+
+// the canonical form for signing: CRLF line endings
+fn canonicalize(part: &[u8]) -> Vec<u8> {
+    let mut canonical = Vec::with_capacity(part.len());
+    for (i, byte) in part.iter().enumerate() {
+        if *byte == b'\n' && (i == 0 || part[i - 1] != b'\r') {
+            canonical.push(b'\r');
+        }
+        canonical.push(*byte);
+    }
+    canonical
+}
+
+// "Name <a@b>, c@d" -> [a@b, c@d]
+fn addresses(value: &str) -> Vec<String> {
+    value.split(',').filter_map(|mailbox| {
+        let address = match (mailbox.rfind('<'), mailbox.rfind('>')) {
+            (Some(open), Some(close)) if open < close => &mailbox[open + 1..close],
+            _ => mailbox,
+        };
+        let address = address.trim();
+        address.contains('@').then(|| address.to_string())
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_pkcs7;
+    use crate::testdata::{self, ALICE, EC};
+    use crate::verify::{self, VerifyOptions, VerifyStatus};
+
+    const CLEAR_SIGNED: &[u8] = include_bytes!("../testdata/smime.eml");
+    const OPAQUE: &[u8] = include_bytes!("../testdata/smime_opaque.eml");
+
+    fn verify(message: &SignedMessage) -> VerifyStatus {
+        let pkcs7 = parse_pkcs7(&message.signature).unwrap();
+        let options = VerifyOptions { detached_content: message.content.clone(), ..testdata::options() };
+        verify::verify_signed_data(pkcs7.signed_data().unwrap(), &options).unwrap()[0].status
+    }
+
+    #[test]
+    fn clear_signed() {
+        let message = SignedMessage::parse(CLEAR_SIGNED).unwrap();
+        assert_eq!(message.from, ["alice@example.com"]);
+        assert_eq!(message.micalg.as_deref(), Some("sha-256"));
+        assert_eq!(message.content.as_deref(), Some(&b"hello signed\r\n"[..]));
+        assert_eq!(verify(&message), VerifyStatus::Valid);
+
+        let tampered = String::from_utf8_lossy(CLEAR_SIGNED).replacen("hello signed", "hello forged", 1);
+        assert_eq!(verify(&SignedMessage::parse(tampered.as_bytes()).unwrap()), VerifyStatus::InvalidSignature);
+    }
+
+    #[test]
+    fn opaque() {
+        let message = SignedMessage::parse(OPAQUE).unwrap();
+        assert_eq!(message.from, ["alice@example.com"]);
+        assert!(message.content.is_none());
+        assert_eq!(verify(&message), VerifyStatus::Valid);
+
+        let encrypted = String::from_utf8_lossy(OPAQUE).replacen("smime-type=signed-data", "smime-type=enveloped-data", 1);
+        assert_eq!(SignedMessage::parse(encrypted.as_bytes()).unwrap_err(), "the message is encrypted, decrypt it first");
+    }
+
+    #[test]
+    fn sender() {
+        let alice = testdata::cert(ALICE);
+        assert_eq!(certificate_emails(&alice), ["alice@example.com"]);
+        assert!(sender_matches(&alice, &["Alice@Example.com".to_string()]));
+        assert!(!sender_matches(&alice, &["mallory@example.com".to_string()]));
+        assert!(certificate_emails(&testdata::cert(EC)).is_empty());
+
+        let forged = String::from_utf8_lossy(CLEAR_SIGNED).replacen("<alice@example.com>", "<mallory@example.com>", 1);
+        let message = SignedMessage::parse(forged.as_bytes()).unwrap();
+        assert_eq!(verify(&message), VerifyStatus::Valid);
+        assert!(!sender_matches(&alice, &message.from));
+    }
+}
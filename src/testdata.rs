@@ -0,0 +1,50 @@
+// the fixtures of testdata/, made by testdata/make.py
+
+use bcder::decode::Constructed;
+use bcder::Mode;
+
+use crate::enveloped::DecryptionKey;
+use crate::keys::PrivateKey;
+use crate::trust::{AnchorConstraints, TrustStore};
+use crate::verify::VerifyOptions;
+use crate::Certificate;
+
+pub const TIME: i64 = 1798761600; // 2027-01-01T00:00:00Z, inside the validity of every certificate
+
+pub const MSG: &[u8] = include_bytes!("../testdata/msg.txt");
+pub const ROOT: &[u8] = include_bytes!("../testdata/root.crt");
+pub const INT: &[u8] = include_bytes!("../testdata/int.crt");
+pub const ALICE: &[u8] = include_bytes!("../testdata/alice.crt");
+pub const ALICE_KEY: &[u8] = include_bytes!("../testdata/alice.key");
+pub const EC: &[u8] = include_bytes!("../testdata/ec.crt");
+pub const EC_KEY: &[u8] = include_bytes!("../testdata/ec.key");
+pub const BOB: &[u8] = include_bytes!("../testdata/bob.crt");
+pub const TSA: &[u8] = include_bytes!("../testdata/tsa.crt");
+pub const SIGNED: &[u8] = include_bytes!("../testdata/signed.p7m");
+pub const CADES: &[u8] = include_bytes!("../testdata/cades.p7m"); // with a signingCertificateV2
+pub const TS: &[u8] = include_bytes!("../testdata/ts.p7m"); // signed.p7m time-stamped by tsa.crt
+pub const TS_UNTRUSTED: &[u8] = include_bytes!("../testdata/ts_untrusted.p7m"); // by a TSA under another root
+
+// a PEM certificate
+pub fn cert(pem: &[u8]) -> Certificate {
+    Constructed::decode(der(pem).as_slice(), Mode::Der, Certificate::take_from).unwrap()
+}
+
+pub fn der(pem: &[u8]) -> Vec<u8> {
+    pem::parse(pem).unwrap().into_contents()
+}
+
+pub fn key(pem: &[u8]) -> PrivateKey {
+    PrivateKey::from_pem(pem).unwrap()
+}
+
+pub fn private_key(pem: &[u8]) -> DecryptionKey {
+    DecryptionKey::Private(key(pem))
+}
+
+// the root as the only anchor, at TIME
+pub fn options() -> VerifyOptions {
+    let mut trust_store = TrustStore::default();
+    trust_store.add(cert(ROOT), AnchorConstraints::default());
+    VerifyOptions { trust_store, time: Some(TIME), ..VerifyOptions::default() }
+}
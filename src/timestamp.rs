@@ -0,0 +1,213 @@
+// RFC 3161 time-stamp tokens in the signatureTimeStampToken unsigned attribute
+
+use bcder::decode::{self, Constructed, DecodeError};
+use bcder::{Mode, OctetString, Oid, Tag};
+
+use crate::crypto::HashAlg;
+use crate::verify::{VerifyOptions, VerifyStatus};
+use crate::{extensions, name, oid, parse_pkcs7, verify};
+use crate::{AlgorithmIdentifier, Attribute, Certificate, Pkcs7Content, SignedData, SignerInfo, Validity};
+
+pub struct TstInfo {
+    pub version: u8,
+    pub policy: Oid,
+    pub hash_algorithm: AlgorithmIdentifier, // messageImprint
+    pub hashed_message: Vec<u8>,
+    pub serial_number: String, // hex
+    pub gen_time: i64,
+    pub ordering: bool,
+    pub nonce: Option<String>, // hex
+}
+
+pub struct TimeStampToken {
+    pub signed_data: SignedData,
+    pub tst_info: TstInfo,
+}
+
+// what a signer's timestamp says and whether it holds
+pub struct TimestampCheck {
+    pub gen_time: i64,
+    pub policy: Oid,
+    pub serial_number: String,
+    pub hash_algorithm: Oid,
+    pub tsa: Option<String>, // subject of the TSA certificate, when shipped in the token
+    pub result: Result<(), String>,
+    pub tsa_trusted: bool, // the TSA chain was checked and reaches a trust anchor
+}
+
+impl TstInfo {
+    pub fn take_from<S: decode::Source>(cons: &mut Constructed<S>) -> Result<Self, DecodeError<S::Error>> {
+        cons.take_sequence(|cons| {
+            let version = cons.take_primitive_if(Tag::INTEGER, |content| content.to_u8())?;
+            let policy = Oid::take_from(cons)?;
+            let (hash_algorithm, hashed_message) = cons.take_sequence(|cons| {
+                let algorithm = AlgorithmIdentifier::take_from(cons)?;
+                let hashed = OctetString::take_from(cons)?.to_bytes().to_vec();
+                Ok((algorithm, hashed))
+            })?;
+            let serial_number = cons.take_primitive_if(Tag::INTEGER, |content| {
+                Ok(hex::encode(content.take_all()?))
+            })?;
+            let (gen_time, _) = Validity::take_time_from(cons)?;
+            cons.take_opt_sequence(|cons| cons.skip_all())?; // accuracy
+            let ordering = cons.take_opt_bool()?.unwrap_or(false);
+            let nonce = cons.take_opt_primitive_if(Tag::INTEGER, |content| {
+                Ok(hex::encode(content.take_all()?))
+            })?;
+            cons.skip_all()?; // tsa, extensions
+            Ok(TstInfo {
+                version,
+                policy,
+                hash_algorithm,
+                hashed_message,
+                serial_number,
+                gen_time,
+                ordering,
+                nonce,
+            })
+        })
+    }
+}
+
+impl TimeStampToken {
+    // a ContentInfo with SignedData over a TSTInfo
+    pub fn from_der(der: &[u8]) -> Result<Self, String> {
+        let pkcs7 = parse_pkcs7(der).map_err(|e| e.to_string())?;
+        let signed_data = match pkcs7.content {
+            Pkcs7Content::SignedData(signed_data) => signed_data,
+            _ => return Err("time-stamp token is not SignedData".to_string()),
+        };
+        if signed_data.content_info.content_type != oid::TST_INFO {
+            return Err(format!("time-stamp token content is {}, not TSTInfo", signed_data.content_info.content_type));
+        }
+        let content = signed_data.content_info.content.as_deref().ok_or("time-stamp token without TSTInfo")?;
+        let tst_info = Constructed::decode(content, Mode::Der, TstInfo::take_from)
+            .map_err(|e| format!("malformed TSTInfo: {}", e))?;
+        Ok(TimeStampToken { signed_data, tst_info })
+    }
+
+    pub fn from_signer_info(signer_info: &SignerInfo) -> Option<Result<Self, String>> {
+        let attribute = signer_info.unsigned_attribute(&oid::SIGNATURE_TIME_STAMP_TOKEN)?;
+        Some(first_value(attribute).and_then(|der| TimeStampToken::from_der(&der)))
+    }
+
+    // the imprint has to be the hash of the data, the TSA signature has to verify and its certificate
+    // has to be for time-stamping; whether the TSA is trusted is check_trust
+    pub fn verify(&self, data: &[u8]) -> Result<(), String> {
+        let tst_info = &self.tst_info;
+        let hash = HashAlg::from_oid(&tst_info.hash_algorithm.algorithm)
+            .ok_or_else(|| format!("unsupported imprint hash {}", oid::describe(&tst_info.hash_algorithm.algorithm)))?;
+        if hash.digest(data) != tst_info.hashed_message {
+            return Err("time-stamp imprint does not match the signature".to_string());
+        }
+
+        let signer_info = self.signed_data.signer_infos.first().ok_or("time-stamp token without signer")?;
+        let cert = self.tsa_certificate().ok_or("TSA certificate not in the token")?;
+        check_tsa_certificate(cert)?;
+        let content = self.signed_data.content_info.content.as_deref().unwrap_or_default();
+        verify::verify_signer(signer_info, cert, content, &self.signed_data.content_info.content_type)
+            .map_err(|e| format!("TSA signature: {}", e))
+    }
+
+    // the TSA certificate chain up to the trust store of the signers, at the verification time; the
+    // anchor is judged at the time of the token, a service withdrawn since does not void it
+    pub fn check_trust(&self, options: &VerifyOptions) -> Result<(), String> {
+        if !options.check_chain {
+            return Err("the TSA certificate chain was not checked".to_string());
+        }
+        let cert = self.tsa_certificate().ok_or("TSA certificate not in the token")?;
+        let mut warnings = Vec::new();
+        match verify::certificate_status(cert, &self.signed_data, options, self.tst_info.gen_time, &mut warnings) {
+            VerifyStatus::Valid => Ok(()),
+            _ => Err(format!("TSA certificate: {}", warnings.join(", "))),
+        }
+    }
+
+    pub fn tsa_certificate(&self) -> Option<&Certificate> {
+        let signer_info = self.signed_data.signer_infos.first()?;
+        verify::find_signer_cert(signer_info, self.signed_data.certs.iter())
+    }
+
+    // the timestamp of a signer: covers its signature value (RFC 3161 appendix A)
+    // without chain checks only the token itself is, and the TSA is not trusted
+    pub fn check(signer_info: &SignerInfo, options: &VerifyOptions) -> Option<Result<TimestampCheck, String>> {
+        let token = match TimeStampToken::from_signer_info(signer_info)? {
+            Ok(token) => token,
+            Err(e) => return Some(Err(e)),
+        };
+        let tst_info = &token.tst_info;
+        let result = token.verify(&signer_info.signature)
+            .and_then(|()| if options.check_chain { token.check_trust(options) } else { Ok(()) });
+        Some(Ok(TimestampCheck {
+            gen_time: tst_info.gen_time,
+            policy: tst_info.policy.clone(),
+            serial_number: tst_info.serial_number.clone(),
+            hash_algorithm: tst_info.hash_algorithm.algorithm.clone(),
+            tsa: token.tsa_certificate().map(|cert| name::to_string(&cert.tbs_certificate.subject)),
+            tsa_trusted: options.check_chain && result.is_ok(),
+            result,
+        }))
+    }
+}
+
+// RFC 3161 section 2.3: a critical extendedKeyUsage with timeStamping as its only purpose
+pub fn check_tsa_certificate(cert: &Certificate) -> Result<(), String> {
+    let extension = cert.tbs_certificate.find_extension(oid::EXT_KEY_USAGE)
+        .ok_or("the TSA certificate has no extended key usage")?;
+    if !extension.critical {
+        return Err("the TSA certificate extended key usage is not critical".to_string());
+    }
+    let purposes = extensions::ext_key_usage(&extension.extn_value).ok_or("malformed TSA certificate extended key usage")?;
+    if purposes != [oid::KP_TIME_STAMPING] {
+        return Err("the TSA certificate is not for time-stamping only".to_string());
+    }
+    Ok(())
+}
+
+// DER of the first value in the attribute SET
+fn first_value(attribute: &Attribute) -> Result<Vec<u8>, String> {
+    attribute.values().into_iter().next().ok_or_else(|| "empty time-stamp attribute".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, ALICE, TS, TS_UNTRUSTED, TSA};
+
+    fn check(der: &[u8], options: &VerifyOptions) -> TimestampCheck {
+        let pkcs7 = parse_pkcs7(der).unwrap();
+        let signer_info = &pkcs7.signed_data().unwrap().signer_infos[0];
+        TimeStampToken::check(signer_info, options).unwrap().unwrap()
+    }
+
+    #[test]
+    fn tsa_certificate_purpose() {
+        assert!(check_tsa_certificate(&testdata::cert(TSA)).is_ok());
+        assert_eq!(check_tsa_certificate(&testdata::cert(ALICE)).unwrap_err(),
+                   "the TSA certificate extended key usage is not critical");
+    }
+
+    #[test]
+    fn trusted_tsa() {
+        let check = check(TS, &testdata::options());
+        assert_eq!(check.result, Ok(()));
+        assert!(check.tsa_trusted);
+        assert_eq!(check.tsa.as_deref(), Some("CN=Test TSA, O=Acme"));
+        assert_eq!(check.hash_algorithm, oid::SHA256);
+    }
+
+    #[test]
+    fn untrusted_tsa() {
+        let check = check(TS_UNTRUSTED, &testdata::options());
+        assert!(check.result.unwrap_err().starts_with("TSA certificate: chain does not end at a trust anchor"));
+        assert!(!check.tsa_trusted);
+    }
+
+    #[test]
+    fn tsa_chain_not_checked() {
+        let options = VerifyOptions { check_chain: false, ..testdata::options() };
+        let check = check(TS_UNTRUSTED, &options);
+        assert_eq!(check.result, Ok(()));
+        assert!(!check.tsa_trusted);
+    }
+}
@@ -0,0 +1,267 @@
+// trust anchors: roots from PEM bundles, OpenSSL hashed directories, p7b and DER files, one per key,
+// each with the constraints it is trusted under
+
+use std::fs;
+use std::path::Path;
+
+use bcder::decode::{Constructed, DecodeError};
+use bcder::{Mode, OctetString, Oid, Tag};
+use bytes::Bytes;
+
+use crate::certs::CertBundle;
+use crate::path::{self, NameSubtree};
+use crate::{extensions, oid};
+use crate::Certificate;
+
+#[derive(Default)]
+pub struct TrustStore {
+    pub anchors: Vec<TrustAnchor>,
+    pub crls: Vec<Vec<u8>>, // DER, the ones shipped along with the roots
+}
+
+pub struct TrustAnchor {
+    pub cert: Certificate,
+    pub constraints: AnchorConstraints,
+}
+
+// what a chain ending at the anchor is restricted to, nothing by default
+#[derive(Debug, Clone, Default)]
+pub struct AnchorConstraints {
+    pub allowed_ekus: Option<Vec<Oid>>, // the signer needs one of them, anyExtendedKeyUsage allows all
+    pub rejected_ekus: Vec<Oid>,
+    pub permitted_subtrees: Vec<NameSubtree>, // the names of every certificate below the anchor
+    pub excluded_subtrees: Vec<NameSubtree>,
+    pub not_before: Option<i64>, // UNIX epoch seconds, in place of the certificate validity
+    pub not_after: Option<i64>,
+}
+
+impl TrustStore {
+    // a file, or every file of a directory that holds certificates (hashed directories hold CRLs
+    // and links too, unreadable files are skipped there)
+    pub fn load(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if !Path::new(path).is_dir() {
+            return self.load_file(path);
+        }
+        let mut files: Vec<_> = fs::read_dir(path)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        for file in files {
+            if let Some(file) = file.to_str() {
+                let _ = self.load_file(file);
+            }
+        }
+        Ok(())
+    }
+
+    fn load_file(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut bundle = CertBundle::default();
+        bundle.load(path)?;
+        // OpenSSL TRUSTED CERTIFICATE blocks carry the purposes the root is trusted for
+        let data = fs::read(path)?;
+        let mut trusted = Vec::new();
+        if crate::keys::is_pem(&data) {
+            for block in pem::parse_many(&data)? {
+                if block.tag() == "TRUSTED CERTIFICATE" {
+                    trusted.push(trusted_certificate(block.contents())?);
+                }
+            }
+        }
+        for cert in bundle.certs {
+            let constraints = trusted.iter()
+                .find(|(trusted_cert, _)| trusted_cert.raw == cert.raw)
+                .map(|(_, constraints)| constraints.clone())
+                .unwrap_or_default();
+            self.add(cert, constraints);
+        }
+        self.crls.extend(bundle.crls);
+        Ok(())
+    }
+
+    // false when an anchor with the same key and trusted period is already there, the first one wins;
+    // a key trusted for several periods, as in a trusted list history, is an anchor for each
+    pub fn add(&mut self, cert: Certificate, constraints: AnchorConstraints) -> bool {
+        let key = &cert.tbs_certificate.subject_public_key_info.raw;
+        if self.anchors.iter().any(|anchor| {
+            anchor.cert.tbs_certificate.subject_public_key_info.raw == key
+                && (anchor.constraints.not_before, anchor.constraints.not_after) == (constraints.not_before, constraints.not_after)
+        }) {
+            return false;
+        }
+        self.anchors.push(TrustAnchor { cert, constraints });
+        true
+    }
+
+    // the anchors of another store, the ones already there win
+    pub fn merge(&mut self, other: TrustStore) {
+        for anchor in other.anchors {
+            self.add(anchor.cert, anchor.constraints);
+        }
+        self.crls.extend(other.crls);
+    }
+
+    // same subject and key as one of the anchors
+    pub fn find(&self, cert: &Certificate) -> Option<&TrustAnchor> {
+        self.anchors.iter().find(|anchor| {
+            anchor.cert.tbs_certificate.subject == cert.tbs_certificate.subject
+                && anchor.cert.tbs_certificate.subject_public_key_info.raw == cert.tbs_certificate.subject_public_key_info.raw
+        })
+    }
+
+    // the anchor of the certificate trusted at the time, or the first one when none is
+    pub fn find_at(&self, cert: &Certificate, time: i64) -> Option<&TrustAnchor> {
+        let mut anchors = self.anchors.iter().filter(|anchor| {
+            anchor.cert.tbs_certificate.subject == cert.tbs_certificate.subject
+                && anchor.cert.tbs_certificate.subject_public_key_info.raw == cert.tbs_certificate.subject_public_key_info.raw
+        });
+        let first = anchors.clone().next();
+        anchors.find(|anchor| anchor.valid_at(time)).or(first)
+    }
+
+    pub fn certs(&self) -> impl Iterator<Item = &Certificate> {
+        self.anchors.iter().map(|anchor| &anchor.cert)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+}
+
+impl TrustAnchor {
+    pub fn valid_at(&self, time: i64) -> bool {
+        let validity = &self.cert.tbs_certificate.validity;
+        let not_before = self.constraints.not_before.unwrap_or(validity.not_before);
+        let not_after = self.constraints.not_after.unwrap_or(validity.not_after);
+        not_before <= time && time <= not_after
+    }
+}
+
+impl AnchorConstraints {
+    // the certificates below the anchor, signer first, against the constraints; what they break
+    pub fn check(&self, chain: &[&Certificate]) -> Vec<String> {
+        let mut errors = Vec::new();
+        if let Some(signer) = chain.first() {
+            if let Err(e) = self.check_ekus(signer) {
+                errors.push(e);
+            }
+        }
+        for cert in chain {
+            errors.extend(path::check_names(cert, &self.permitted_subtrees, &self.excluded_subtrees, "the trust anchor"));
+        }
+        errors
+    }
+
+    fn check_ekus(&self, signer: &Certificate) -> Result<(), String> {
+        if self.allowed_ekus.is_none() && self.rejected_ekus.is_empty() {
+            return Ok(());
+        }
+        let purposes = signer.tbs_certificate.find_extension(oid::EXT_KEY_USAGE)
+            .and_then(|extension| extensions::ext_key_usage(&extension.extn_value))
+            .ok_or("the signer certificate has no extended key usage, the trust anchor restricts them")?;
+        if let Some(rejected) = purposes.iter().find(|purpose| self.rejected_ekus.contains(purpose)) {
+            return Err(format!("the trust anchor is not trusted for {}", oid::describe(rejected)));
+        }
+        match &self.allowed_ekus {
+            Some(allowed) if !allowed.iter().any(|eku| *eku == oid::ANY_EXTENDED_KEY_USAGE || purposes.contains(eku)) => {
+                let allowed: Vec<String> = allowed.iter().map(oid::describe).collect();
+                Err(format!("the trust anchor is only trusted for {}", allowed.join(", ")))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+// OpenSSL "TRUSTED CERTIFICATE": the certificate, then X509_CERT_AUX { trust SEQUENCE OF OID OPTIONAL,
+// reject [0] SEQUENCE OF OID OPTIONAL, alias UTF8String OPTIONAL, keyid OCTET STRING OPTIONAL, other [1] }
+pub fn trusted_certificate(der: &[u8]) -> Result<(Certificate, AnchorConstraints), String> {
+    Constructed::decode(Bytes::copy_from_slice(der), Mode::Ber, |cons| {
+        let cert = Certificate::take_from(cons)?;
+        let mut constraints = AnchorConstraints::default();
+        cons.take_opt_sequence(|cons| {
+            constraints.allowed_ekus = cons.take_opt_sequence(take_oids)?;
+            constraints.rejected_ekus = cons.take_opt_constructed_if(Tag::CTX_0, take_oids)?.unwrap_or_default();
+            cons.take_opt_value_if(Tag::UTF8_STRING, |content| content.as_primitive()?.skip_all())?;
+            OctetString::take_opt_from(cons)?;
+            cons.take_opt_constructed_if(Tag::CTX_1, |cons| cons.skip_all())?;
+            Ok(())
+        })?;
+        Ok((cert, constraints))
+    }).map_err(|err: DecodeError<_>| format!("Error decoding trusted certificate: {}", err))
+}
+
+fn take_oids<S: bcder::decode::Source>(cons: &mut Constructed<S>) -> Result<Vec<Oid>, DecodeError<S::Error>> {
+    let mut oids = Vec::new();
+    while let Some(oid) = Oid::take_opt_from(cons)? {
+        oids.push(oid);
+    }
+    Ok(oids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{name, parse_pkcs7};
+    use crate::testdata::{self, ALICE, ROOT, SIGNED, TIME};
+    use crate::verify::{self, VerifyOptions, VerifyStatus};
+
+    fn load(names: &[&str]) -> TrustStore {
+        let mut trust_store = TrustStore::default();
+        for name in names {
+            trust_store.load(&format!("{}/testdata/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+        }
+        trust_store
+    }
+
+    fn subjects(trust_store: &TrustStore) -> Vec<String> {
+        trust_store.certs().map(|cert| name::to_string(&cert.tbs_certificate.subject)).collect()
+    }
+
+    fn status(trust_store: TrustStore) -> VerifyStatus {
+        let pkcs7 = parse_pkcs7(SIGNED).unwrap();
+        let options = VerifyOptions { trust_store, time: Some(TIME), ..VerifyOptions::default() };
+        verify::verify_signed_data(pkcs7.signed_data().unwrap(), &options).unwrap()[0].status
+    }
+
+    #[test]
+    fn sources() {
+        let roots = ["CN=Test Root, O=Acme", "CN=Other Root, O=Elsewhere"];
+        assert_eq!(subjects(&load(&["roots.pem"])), roots);
+        assert_eq!(subjects(&load(&["roots.p7b"])), roots);
+        // the same keys twice are one anchor each
+        assert_eq!(subjects(&load(&["roots.pem", "roots.p7b", "root.crt"])), roots);
+
+        let hashed = load(&["hashed"]);
+        assert_eq!(subjects(&hashed), ["CN=Test Root, O=Acme"]);
+        assert_eq!(hashed.crls, [include_bytes!("../testdata/root.crl")]);
+        assert!(hashed.find(&testdata::cert(ROOT)).is_some());
+        assert!(hashed.find(&testdata::cert(ALICE)).is_none());
+        assert_eq!(status(hashed), VerifyStatus::Valid);
+    }
+
+    #[test]
+    fn trusted_certificate_purposes() {
+        let rejected = load(&["root_no_email.pem"]);
+        let constraints = &rejected.anchors[0].constraints;
+        assert_eq!(constraints.rejected_ekus, [oid::KP_EMAIL_PROTECTION]);
+        assert!(constraints.allowed_ekus.is_none());
+        assert_eq!(constraints.check(&[&testdata::cert(ALICE)]), ["the trust anchor is not trusted for emailProtection"]);
+        assert_eq!(status(rejected), VerifyStatus::Untrusted);
+
+        let code_signing = load(&["root_code_signing.pem"]);
+        let constraints = &code_signing.anchors[0].constraints;
+        assert_eq!(constraints.allowed_ekus.as_deref(), Some(&[oid::owned(oid::KP_CODE_SIGNING)][..]));
+        assert_eq!(constraints.check(&[&testdata::cert(ALICE)]), ["the trust anchor is only trusted for codeSigning"]);
+        assert_eq!(status(code_signing), VerifyStatus::Untrusted);
+    }
+
+    #[test]
+    fn validity_override() {
+        let mut trust_store = TrustStore::default();
+        let constraints = AnchorConstraints { not_after: Some(TIME - 1), ..AnchorConstraints::default() };
+        trust_store.add(testdata::cert(ROOT), constraints);
+        assert!(trust_store.anchors[0].valid_at(TIME - 1));
+        assert!(!trust_store.anchors[0].valid_at(TIME));
+        assert_eq!(status(trust_store), VerifyStatus::Untrusted);
+    }
+}
@@ -0,0 +1,291 @@
+// EU Trusted Lists (ETSI TS 119 612): the CA/QC services of a locally stored TSL and their status
+// history, as a trust store for a given time. The XML signature of the list is not checked, the
+// command line only takes lists with --insecure-unsigned-tsl.
+
+use std::fs;
+
+use base64::Engine;
+use chrono::DateTime;
+use roxmltree::{Document, Node};
+use serde::Serialize;
+
+use crate::trust::{AnchorConstraints, TrustStore};
+use crate::Certificate;
+
+pub const SERVICE_TYPE_CA_QC: &str = "http://uri.etsi.org/TrstSvc/Svctype/CA/QC";
+
+// the statuses under which a service is trusted, eIDAS (TS 119 612 v2) and Directive 1999/93/EC ones
+pub const TRUSTED_STATUSES: [&str; 5] = [
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/granted",
+    "http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/recognisedatnationallevel",
+    "http://uri.etsi.org/TrstSvc/eSigDir-1999-93-EC-TrustedList/Svcstatus/undersupervision",
+    "http://uri.etsi.org/TrstSvc/eSigDir-1999-93-EC-TrustedList/Svcstatus/supervisionincessation",
+    "http://uri.etsi.org/TrstSvc/eSigDir-1999-93-EC-TrustedList/Svcstatus/accredited",
+];
+
+#[derive(Debug)]
+pub struct TrustedList {
+    pub territory: Option<String>,
+    pub sequence_number: Option<u64>,
+    pub issue_date: Option<i64>,
+    pub next_update: Option<i64>, // absent for a closed list
+    pub services: Vec<TrustService>,
+}
+
+#[derive(Debug)]
+pub struct TrustService {
+    pub provider: String,
+    pub name: String,
+    pub service_type: String,
+    pub certs: Vec<Certificate>, // the X509Certificate digital identities of the current information
+    pub history: Vec<ServiceStatus>, // the current status and the ServiceHistoryInstances, oldest first
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub service_type: String,
+    pub status: String,
+    pub start: i64,
+    #[serde(skip)]
+    pub identities: Vec<Vec<u8>>, // DER of its X509Certificate digital identities, empty when named by SKI or subject only
+}
+
+impl TrustedList {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let xml = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Self::parse(&xml)
+    }
+
+    // elements are matched by local name, lists use several namespace prefixes
+    pub fn parse(xml: &str) -> Result<Self, String> {
+        let document = Document::parse(xml).map_err(|e| format!("Error decoding trusted list: {}", e))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "TrustServiceStatusList" {
+            return Err(format!("not a trusted list, root element is {}", root.tag_name().name()));
+        }
+        let scheme = child(root, "SchemeInformation").ok_or("trusted list without SchemeInformation")?;
+        let mut services = Vec::new();
+        for provider in descendants(root, "TrustServiceProvider") {
+            let provider_name = child(provider, "TSPInformation")
+                .and_then(|information| child(information, "TSPName"))
+                .and_then(localized_name)
+                .unwrap_or_default();
+            for service in descendants(provider, "TSPService") {
+                services.push(TrustService::parse(service, &provider_name)?);
+            }
+        }
+        Ok(TrustedList {
+            territory: child_text(scheme, "SchemeTerritory"),
+            sequence_number: child_text(scheme, "TSLSequenceNumber").and_then(|number| number.parse().ok()),
+            issue_date: child_text(scheme, "ListIssueDateTime").map(|time| parse_date_time(&time)).transpose()?,
+            next_update: child(scheme, "NextUpdate")
+                .and_then(|next_update| child_text(next_update, "dateTime"))
+                .map(|time| parse_date_time(&time))
+                .transpose()?,
+            services,
+        })
+    }
+
+    // every period in which a CA/QC service was trusted, each certificate an anchor for that period
+    // only, so that a later withdrawal does not affect signatures proven older and the other way round;
+    // the certificates are the ones listed with that status, the current ones when it lists none
+    pub fn trust_store(&self) -> TrustStore {
+        let mut store = TrustStore::default();
+        for service in &self.services {
+            for (i, status) in service.history.iter().enumerate() {
+                if status.service_type != SERVICE_TYPE_CA_QC || !status.trusted() {
+                    continue;
+                }
+                let until = service.history.get(i + 1).map(|next| next.start);
+                let identities: Vec<&[u8]> = if status.identities.is_empty() {
+                    service.certs.iter().map(|cert| cert.raw.as_ref()).collect()
+                } else {
+                    status.identities.iter().map(Vec::as_slice).collect()
+                };
+                for der in identities {
+                    // certificates are not Clone, the store gets its own copy
+                    let Ok(cert) = Certificate::from_der(der) else {
+                        continue;
+                    };
+                    let constraints = AnchorConstraints {
+                        not_before: Some(status.start),
+                        not_after: until.map(|until| until - 1),
+                        ..AnchorConstraints::default()
+                    };
+                    store.add(cert, constraints);
+                }
+            }
+        }
+        store
+    }
+}
+
+impl TrustService {
+    fn parse(service: Node, provider: &str) -> Result<Self, String> {
+        let information = child(service, "ServiceInformation").ok_or("TSPService without ServiceInformation")?;
+        let name = child(information, "ServiceName").and_then(localized_name).unwrap_or_default();
+        let current = ServiceStatus::parse(information, &name)?;
+        let certs = current.identities.iter()
+            .map(|der| Certificate::from_der(der).map_err(|e| format!("{}: {}", name, e)))
+            .collect::<Result<_, _>>()?;
+
+        let mut history = vec![current];
+        if let Some(service_history) = child(service, "ServiceHistory") {
+            for instance in children(service_history, "ServiceHistoryInstance") {
+                history.push(ServiceStatus::parse(instance, &name)?);
+            }
+        }
+        history.sort_by_key(|status| status.start);
+        Ok(TrustService {
+            provider: provider.to_string(),
+            name,
+            service_type: history.last().map(|status| status.service_type.clone()).unwrap_or_default(),
+            certs,
+            history,
+        })
+    }
+
+    // the status in force at the time and when it ended, None before the first one
+    pub fn status_at(&self, time: i64) -> Option<(&ServiceStatus, Option<i64>)> {
+        let index = self.history.iter().rposition(|status| status.start <= time)?;
+        Some((&self.history[index], self.history.get(index + 1).map(|next| next.start)))
+    }
+}
+
+impl ServiceStatus {
+    // ServiceInformation or ServiceHistoryInstance, name is the service one for errors
+    fn parse(node: Node, name: &str) -> Result<Self, String> {
+        let status = child_text(node, "ServiceStatus").ok_or("service without ServiceStatus")?;
+        let start = child_text(node, "StatusStartingTime").ok_or("service without StatusStartingTime")?;
+        let mut identities = Vec::new();
+        if let Some(identity) = child(node, "ServiceDigitalIdentity") {
+            for cert in descendants(identity, "X509Certificate") {
+                identities.push(base64::engine::general_purpose::STANDARD
+                    .decode(cert.text().unwrap_or_default().split_whitespace().collect::<String>())
+                    .map_err(|e| format!("{}: bad X509Certificate base64, {}", name, e))?);
+            }
+        }
+        Ok(ServiceStatus {
+            service_type: child_text(node, "ServiceTypeIdentifier").unwrap_or_default(),
+            status,
+            start: parse_date_time(&start)?,
+            identities,
+        })
+    }
+
+    pub fn trusted(&self) -> bool {
+        TRUSTED_STATUSES.contains(&self.status.as_str())
+    }
+}
+
+// CA/QC for a service type URI, granted for a status one
+pub fn short_uri(uri: &str) -> &str {
+    uri.rsplit_once("/Svctype/").or_else(|| uri.rsplit_once("/Svcstatus/")).map_or(uri, |(_, short)| short)
+}
+
+fn children<'a, 'input>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children().filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &'a str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+fn descendants<'a, 'input>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.descendants().filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn child_text(node: Node, name: &str) -> Option<String> {
+    child(node, name).and_then(|child| child.text()).map(|text| text.trim().to_string())
+}
+
+// the English Name of a multilingual list, otherwise the first
+fn localized_name(node: Node) -> Option<String> {
+    let names: Vec<Node> = children(node, "Name").collect();
+    let english = names.iter().find(|name| {
+        name.attributes().any(|attribute| attribute.name() == "lang" && attribute.value().eq_ignore_ascii_case("en"))
+    });
+    english.or(names.first()).and_then(|name| name.text()).map(|text| text.trim().to_string())
+}
+
+fn parse_date_time(value: &str) -> Result<i64, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.timestamp())
+        .map_err(|e| format!("bad xsd:dateTime {}: {}", value, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdata::{self, TIME};
+    use crate::verify::{self, VerifyOptions, VerifyStatus};
+    use crate::{name, parse_pkcs7};
+
+    fn trusted_list() -> TrustedList {
+        TrustedList::load(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tsl.xml")).unwrap()
+    }
+
+    // the anchors trusted at the time
+    fn subjects(time: i64) -> Vec<String> {
+        let trust_store = trusted_list().trust_store();
+        trust_store.anchors.iter()
+            .filter(|anchor| anchor.valid_at(time))
+            .map(|anchor| name::to_string(&anchor.cert.tbs_certificate.subject))
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        let list = trusted_list();
+        assert_eq!(list.territory.as_deref(), Some("XX"));
+        assert_eq!(list.sequence_number, Some(42));
+        assert_eq!(list.issue_date, Some(1790812800)); // 2026-10-01
+        assert_eq!(list.next_update, Some(1806537600)); // 2027-04-01
+        let names: Vec<_> = list.services.iter().map(|service| service.name.as_str()).collect();
+        assert_eq!(names, ["Acme Root CA", "Acme Issuing CA", "Acme TSA"]);
+        let service = &list.services[0];
+        assert_eq!(service.provider, "Acme Trust");
+        assert_eq!(short_uri(&service.service_type), "CA/QC");
+        let statuses: Vec<_> = service.history.iter().map(|status| short_uri(&status.status)).collect();
+        assert_eq!(statuses, ["granted", "withdrawn"]);
+        assert!(service.history[0].trusted() && !service.history[1].trusted());
+    }
+
+    #[test]
+    fn status_at() {
+        let service = &trusted_list().services[0];
+        assert!(service.status_at(1577836800 - 1).is_none()); // before 2020
+        let (status, until) = service.status_at(TIME).unwrap();
+        assert_eq!(short_uri(&status.status), "granted");
+        assert_eq!(until, Some(1811808000)); // 2027-06-01
+        let (status, until) = service.status_at(1811808000).unwrap();
+        assert_eq!(short_uri(&status.status), "withdrawn");
+        assert_eq!(until, None);
+    }
+
+    #[test]
+    fn trust_store_periods() {
+        // the history identity of the issuing CA until 2028, root2 after; never the TSA
+        assert_eq!(subjects(TIME), ["CN=Test Root, O=Acme", "CN=Test Intermediate, O=Acme"]);
+        assert_eq!(subjects(1811808000), ["CN=Test Intermediate, O=Acme"]); // root withdrawn
+        assert_eq!(subjects(1843430400), ["CN=Other Root, O=Elsewhere"]); // 2028-06-01
+        let trust_store = trusted_list().trust_store();
+        assert_eq!(trust_store.anchors.len(), 3);
+        let constraints = &trust_store.anchors[0].constraints;
+        assert_eq!((constraints.not_before, constraints.not_after), (Some(1577836800), Some(1811808000 - 1)));
+    }
+
+    #[test]
+    fn status_at_signing_time() {
+        // the root is withdrawn in December 2026, after the time-stamp of cades_t.p7m and before TIME
+        let list = TrustedList::load(concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/tsl_withdrawn.xml")).unwrap();
+        let status = |der: &[u8]| {
+            let pkcs7 = parse_pkcs7(der).unwrap();
+            let options = VerifyOptions { trust_store: list.trust_store(), time: Some(TIME), ..VerifyOptions::default() };
+            let results = verify::verify_signed_data(pkcs7.signed_data().unwrap(), &options).unwrap();
+            VerifyStatus::combine(results.iter().map(|result| result.status))
+        };
+        assert_eq!(status(include_bytes!("../testdata/cades_t.p7m")), VerifyStatus::Valid);
+        assert_eq!(status(testdata::CADES), VerifyStatus::Untrusted);
+    }
+}
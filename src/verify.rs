@@ -0,0 +1,687 @@
+// verification of SignedData signers: signature, certificate chain, revocation
+
+use bcder::Oid;
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::crl::CertificateList;
+use crate::crypto::HashAlg;
+use crate::ltv::LtvData;
+use crate::ocsp::{BasicOcspResponse, CertStatus};
+use crate::path::{self, PolicyOptions};
+use crate::{extensions, oid};
+use crate::signature;
+use crate::timestamp::{TimeStampToken, TimestampCheck};
+use crate::trust::TrustStore;
+use crate::{certs, Certificate, SignedData, SignerInfo};
+
+const MAX_CHAIN_LEN: usize = 10;
+
+pub struct VerifyOptions {
+    pub trust_store: TrustStore,
+    pub extra_certs: Vec<Certificate>, // intermediates not shipped in the SignedData
+    pub crls: Vec<Vec<u8>>, // DER, in addition to the ones in the SignedData
+    pub time: Option<i64>, // UNIX epoch seconds, now when None
+    pub detached_content: Option<Vec<u8>>,
+    pub check_chain: bool, // false only checks the signatures
+    pub require_algorithm_protection: bool, // signers without CMSAlgorithmProtection fail
+    pub ltv: LtvData, // certificates, CRLs and OCSP responses embedded by CAdES-LT signers
+    pub policy: PolicyOptions, // the certificate policies the chain has to be valid for
+    pub signer_policy: SignerPolicy,
+}
+
+// what the signer certificate has to be for, nothing by default
+#[derive(Debug, Clone, Default)]
+pub struct SignerPolicy {
+    pub key_usage: Vec<Vec<&'static str>>, // one KeyUsage bit of each entry has to be set, as extensions::KEY_USAGE_NAMES
+    pub ekus: Vec<Oid>, // one of them, or anyExtendedKeyUsage, has to be in the extendedKeyUsage
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions {
+            trust_store: TrustStore::default(),
+            extra_certs: Vec::new(),
+            crls: Vec::new(),
+            time: None,
+            detached_content: None,
+            check_chain: true,
+            require_algorithm_protection: false,
+            ltv: LtvData::default(),
+            policy: PolicyOptions::default(),
+            signer_policy: SignerPolicy::default(),
+        }
+    }
+}
+
+impl SignerPolicy {
+    // an absent extension does not restrict the use (RFC 5280 sections 4.2.1.3 and 4.2.1.12)
+    pub fn check(&self, cert: &Certificate) -> Vec<String> {
+        let tbs = &cert.tbs_certificate;
+        let mut errors = Vec::new();
+        let key_usage = match tbs.find_extension(oid::KEY_USAGE) {
+            Some(extension) if !self.key_usage.is_empty() => Some(extensions::key_usage(&extension.extn_value)),
+            _ => None,
+        };
+        match key_usage {
+            Some(None) => errors.push("the signer certificate keyUsage cannot be decoded".to_string()),
+            Some(Some(usages)) => {
+                let missing: Vec<String> = self.key_usage.iter()
+                    .filter(|any_of| !any_of.iter().any(|usage| usages.contains(usage)))
+                    .map(|any_of| any_of.join(" or "))
+                    .collect();
+                if !missing.is_empty() {
+                    errors.push(format!(
+                        "the signer certificate keyUsage does not allow {} (it has {})",
+                        missing.join(", "),
+                        if usages.is_empty() { "none".to_string() } else { usages.join(", ") },
+                    ));
+                }
+            },
+            None => {},
+        }
+
+        let purposes = match tbs.find_extension(oid::EXT_KEY_USAGE) {
+            Some(extension) if !self.ekus.is_empty() => Some(extensions::ext_key_usage(&extension.extn_value)),
+            _ => None,
+        };
+        match purposes {
+            Some(None) => errors.push("the signer certificate extendedKeyUsage cannot be decoded".to_string()),
+            Some(Some(purposes)) => {
+                let allowed = purposes.iter().any(|purpose| *purpose == oid::ANY_EXTENDED_KEY_USAGE || self.ekus.contains(purpose));
+                if !allowed {
+                    let describe = |oids: &[Oid]| oids.iter().map(oid::describe).collect::<Vec<_>>().join(", ");
+                    errors.push(format!(
+                        "the signer certificate extendedKeyUsage has none of {} (it has {})",
+                        describe(&self.ekus),
+                        describe(&purposes),
+                    ));
+                }
+            },
+            None => {},
+        }
+        errors
+    }
+}
+
+// worst outcome first: a bad signature makes the chain irrelevant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyStatus {
+    Valid,
+    InvalidSignature,
+    Untrusted,
+    Revoked,
+}
+
+impl VerifyStatus {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            VerifyStatus::Valid => 0,
+            VerifyStatus::InvalidSignature => 1,
+            VerifyStatus::Untrusted => 2,
+            VerifyStatus::Revoked => 3,
+        }
+    }
+
+    // the status of several signers together; a revocation stands whatever the chain ends at
+    pub fn combine(statuses: impl IntoIterator<Item = VerifyStatus>) -> VerifyStatus {
+        let statuses: Vec<VerifyStatus> = statuses.into_iter().collect();
+        [VerifyStatus::InvalidSignature, VerifyStatus::Revoked, VerifyStatus::Untrusted]
+            .into_iter()
+            .find(|status| statuses.contains(status))
+            .unwrap_or(VerifyStatus::Valid)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevocationStatus {
+    Good,
+    Revoked { at: i64 },
+    Unknown, // no CRL or OCSP response from the issuer
+}
+
+pub struct ChainEntry<'a> {
+    pub cert: &'a Certificate,
+    pub trust_anchor: bool,
+    pub time_valid: bool,
+    pub revocation: RevocationStatus,
+}
+
+pub struct SignerVerification<'a> {
+    pub signer_info: &'a SignerInfo,
+    pub signer_cert: Option<&'a Certificate>,
+    pub signature: Result<(), String>,
+    pub chain: Vec<ChainEntry<'a>>, // signer first, empty when the chain was not checked
+    pub status: VerifyStatus,
+    pub timestamp: Option<Result<TimestampCheck, String>>, // signatureTimeStampToken, when present
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum VerifyError {
+    NoSigners,
+    NoContent,
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            VerifyError::NoSigners => write!(f, "no signers, nothing to verify"),
+            VerifyError::NoContent => write!(f, "content is detached, it has to be given separately"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+pub fn verify_signed_data<'a>(signed_data: &'a SignedData, options: &'a VerifyOptions) -> Result<Vec<SignerVerification<'a>>, VerifyError> {
+    if signed_data.signer_infos.is_empty() {
+        return Err(VerifyError::NoSigners);
+    }
+    let content = options.detached_content.as_deref()
+        .or(signed_data.content_info.content.as_deref())
+        .ok_or(VerifyError::NoContent)?;
+    let time = options.time.unwrap_or_else(|| Utc::now().timestamp());
+
+    let pool: Vec<&Certificate> = signed_data.certs.iter().chain(&options.extra_certs).chain(&options.ltv.certs).collect();
+    let crls = parse_crls(signed_data, options);
+    let crls: Vec<&CertificateList> = crls.iter().chain(&options.ltv.crls).collect();
+    let revocation = RevocationSources { crls: &crls, ocsp_responses: &options.ltv.ocsp_responses };
+
+    let mut results = Vec::new();
+    for signer_info in &signed_data.signer_infos {
+        let mut warnings = Vec::new();
+        let signer_cert = find_signer_cert(signer_info, pool.iter().copied().chain(options.trust_store.certs()));
+
+        let signature = match signer_cert {
+            Some(_) if options.require_algorithm_protection && signer_info.cms_algorithm_protection().is_none() => {
+                Err("CMSAlgorithmProtection attribute missing".to_string())
+            },
+            Some(cert) => verify_signer(signer_info, cert, content, &signed_data.content_info.content_type),
+            None => Err("signer certificate not found".to_string()),
+        };
+
+        // a trusted timestamp proves when the signature existed, the anchors are judged at that time
+        let timestamp = TimeStampToken::check(signer_info, options);
+        let signing_time = match &timestamp {
+            Some(Ok(check)) if check.tsa_trusted => check.gen_time,
+            _ => time,
+        };
+
+        let mut chain = Vec::new();
+        let mut status = match (&signature, signer_cert) {
+            (Ok(()), Some(cert)) if options.check_chain => {
+                chain = build_chain(cert, &pool, &options.trust_store, time, signing_time, &revocation);
+                chain_status(&chain, &options.trust_store, &options.policy, &mut warnings)
+            },
+            (Ok(()), _) => VerifyStatus::Valid,
+            (Err(_), _) => VerifyStatus::InvalidSignature,
+        };
+        // a valid signature by a certificate that is not for this use is not trusted
+        if let (Ok(()), Some(cert)) = (&signature, signer_cert) {
+            let errors = options.signer_policy.check(cert);
+            if !errors.is_empty() {
+                warnings.extend(errors);
+                status = VerifyStatus::combine([status, VerifyStatus::Untrusted]);
+            }
+        }
+
+        // a bad timestamp does not make the signature itself invalid
+        match &timestamp {
+            Some(Ok(TimestampCheck { result: Err(e), .. })) | Some(Err(e)) => warnings.push(format!("timestamp: {}", e)),
+            _ => {},
+        }
+
+        results.push(SignerVerification {
+            signer_info,
+            signer_cert,
+            signature,
+            chain,
+            status,
+            timestamp,
+            warnings,
+        });
+    }
+    Ok(results)
+}
+
+// the chain of a certificate that signs something other than the content, a TSA, up to the same trust
+// store with the anchors judged at signing_time; the certificate policies asked of the signers do not apply to it
+pub fn certificate_status(
+    cert: &Certificate,
+    signed_data: &SignedData,
+    options: &VerifyOptions,
+    signing_time: i64,
+    warnings: &mut Vec<String>,
+) -> VerifyStatus {
+    let time = options.time.unwrap_or_else(|| Utc::now().timestamp());
+    let pool: Vec<&Certificate> = signed_data.certs.iter().chain(&options.extra_certs).chain(&options.ltv.certs).collect();
+    let crls = parse_crls(signed_data, options);
+    let crls: Vec<&CertificateList> = crls.iter().chain(&options.ltv.crls).collect();
+    let revocation = RevocationSources { crls: &crls, ocsp_responses: &options.ltv.ocsp_responses };
+    let chain = build_chain(cert, &pool, &options.trust_store, time, signing_time, &revocation);
+    chain_status(&chain, &options.trust_store, &PolicyOptions::default(), warnings)
+}
+
+// the CRLs of the SignedData and the extra ones
+fn parse_crls(signed_data: &SignedData, options: &VerifyOptions) -> Vec<CertificateList> {
+    certs::split_crls(&signed_data.crls).iter().chain(&options.crls)
+        .filter_map(|der| CertificateList::from_der(der).ok()) // other revocation info formats are skipped
+        .collect()
+}
+
+// the certificate named by the sid; when it shares its key with others (renewals, cross certificates)
+// the ESS signingCertificate binding picks the one the signer actually signed for
+pub fn find_signer_cert<'a>(signer_info: &SignerInfo, certs: impl Iterator<Item = &'a Certificate>) -> Option<&'a Certificate> {
+    let sid = &signer_info.signer_identifier;
+    let certs: Vec<&Certificate> = certs.collect();
+    let by_sid = certs.iter().copied().find(|cert| {
+        let tbs = &cert.tbs_certificate;
+        tbs.issuer.as_ref() == sid.issuer.as_slice()
+            && hex::decode(&tbs.serial_number).is_ok_and(|serial| serial == sid.serial_number)
+    })?;
+    let key = &by_sid.tbs_certificate.subject_public_key_info.raw;
+    certs.iter().copied()
+        .filter(|cert| cert.tbs_certificate.subject_public_key_info.raw == key)
+        .find(|cert| check_signing_certificate(signer_info, cert) == Some(Ok(())))
+        .or(Some(by_sid))
+}
+
+// signed attributes when present (RFC 5652 section 5.4), otherwise the content itself
+pub fn verify_signer(signer_info: &SignerInfo, cert: &Certificate, content: &[u8], content_type: &Oid) -> Result<(), String> {
+    let digest_alg = HashAlg::from_oid(&signer_info.digest_algorithm.algorithm)
+        .ok_or_else(|| format!("unsupported digest algorithm {}", oid::describe(&signer_info.digest_algorithm.algorithm)))?;
+
+    let signed_data = match &signer_info.auth_attributes {
+        Some(_) => {
+            check_algorithm_protection(signer_info)?;
+            check_signing_certificate(signer_info, cert).unwrap_or(Ok(()))?;
+
+            // both have to be single valued (RFC 5652 section 11)
+            let message_digest = signer_info.message_digest().ok_or("messageDigest attribute missing")??;
+            let [message_digest] = message_digest.as_slice() else {
+                return Err("messageDigest attribute with several values".to_string());
+            };
+            if *message_digest != digest_alg.digest(content) {
+                return Err("message digest does not match the content".to_string());
+            }
+
+            let signed_content_type = signer_info.content_type().ok_or("contentType attribute missing")??;
+            let [signed_content_type] = signed_content_type.as_slice() else {
+                return Err("contentType attribute with several values".to_string());
+            };
+            if signed_content_type != content_type {
+                return Err("signed contentType differs from the encapsulated one".to_string());
+            }
+            signer_info.signed_attrs_der()
+        },
+        None => content.to_vec(),
+    };
+
+    signature::verify_signature(
+        &cert.tbs_certificate.subject_public_key_info,
+        &signer_info.signature_algorithm,
+        Some(digest_alg),
+        &signed_data,
+        &signer_info.signature,
+    ).map_err(|e| e.to_string())
+}
+
+// RFC 6211: when present, the protected algorithms have to be exactly the ones of the SignerInfo,
+// against algorithm substitution
+pub fn check_algorithm_protection(signer_info: &SignerInfo) -> Result<(), String> {
+    let protection = match signer_info.cms_algorithm_protection() {
+        Some(protection) => protection?,
+        None => return Ok(()),
+    };
+    let [protection] = protection.as_slice() else {
+        return Err("CMSAlgorithmProtection attribute with several values".to_string());
+    };
+    if protection.digest_algorithm != signer_info.digest_algorithm {
+        return Err(format!(
+            "CMSAlgorithmProtection digest algorithm {} differs from the SignerInfo one {}",
+            protection.digest_algorithm, signer_info.digest_algorithm,
+        ));
+    }
+    match &protection.signature_algorithm {
+        Some(alg) if *alg == signer_info.signature_algorithm => Ok(()),
+        Some(alg) => Err(format!(
+            "CMSAlgorithmProtection signature algorithm {} differs from the SignerInfo one {}",
+            alg, signer_info.signature_algorithm,
+        )),
+        None => Err("CMSAlgorithmProtection protects a MAC algorithm, not a signature one".to_string()),
+    }
+}
+
+// ESS signingCertificate (RFC 2634) and signingCertificateV2 (RFC 5035): the first ESSCertID is the
+// hash of the signer certificate, with its issuer and serial when present; None without either attribute
+pub fn check_signing_certificate(signer_info: &SignerInfo, cert: &Certificate) -> Option<Result<(), String>> {
+    let v1 = signer_info.signing_certificate();
+    let v2 = signer_info.signing_certificate_v2();
+    if v1.is_none() && v2.is_none() {
+        return None;
+    }
+    let check = || {
+        for attribute in v1.into_iter().chain(v2) {
+            let values = attribute?;
+            let [signing_certificate] = values.as_slice() else {
+                return Err("signingCertificate attribute with several values".to_string());
+            };
+            let ess_cert_id = signing_certificate.certs.first().ok_or("signingCertificate without certificates")?;
+            let hash = HashAlg::from_oid(&ess_cert_id.hash_algorithm)
+                .ok_or_else(|| format!("unsupported signingCertificate hash {}", oid::describe(&ess_cert_id.hash_algorithm)))?;
+            if hash.digest(&cert.raw) != ess_cert_id.cert_hash {
+                return Err("signingCertificate hash does not match the signer certificate".to_string());
+            }
+            if let Some(issuer_serial) = &ess_cert_id.issuer_serial {
+                let tbs = &cert.tbs_certificate;
+                let same_issuer = issuer_serial.issuer.contains(&tbs.issuer);
+                let same_serial = hex::decode(&tbs.serial_number).is_ok_and(|serial| serial == issuer_serial.serial_number);
+                if !same_issuer || !same_serial {
+                    return Err("signingCertificate issuerSerial does not match the signer certificate".to_string());
+                }
+            }
+        }
+        Ok(())
+    };
+    Some(check())
+}
+
+// from the signer up to a trust anchor, or as far as issuers can be found; the certificates are
+// checked at the verification time, the anchor at the signing time
+fn build_chain<'a>(
+    signer: &'a Certificate,
+    pool: &[&'a Certificate],
+    trust_store: &'a TrustStore,
+    time: i64,
+    signing_time: i64,
+    revocation: &RevocationSources,
+) -> Vec<ChainEntry<'a>> {
+    let mut chain: Vec<&Certificate> = vec![signer];
+    while chain.len() < MAX_CHAIN_LEN {
+        let current = chain[chain.len() - 1];
+        if trust_store.find(current).is_some() {
+            break;
+        }
+        let issuer = trust_store.certs().chain(pool.iter().copied()).find(|candidate| {
+            candidate.tbs_certificate.subject == current.tbs_certificate.issuer
+                && !chain.iter().any(|c| std::ptr::eq(*c, *candidate))
+                && current.verify_signed_by(candidate).is_ok()
+        });
+        match issuer {
+            Some(issuer) => chain.push(issuer),
+            None => break,
+        }
+    }
+
+    chain.iter().enumerate().map(|(i, cert)| {
+        let validity = &cert.tbs_certificate.validity;
+        let anchor = trust_store.find_at(cert, signing_time);
+        let trust_anchor = anchor.is_some();
+        let revocation = match chain.get(i + 1) {
+            Some(issuer) if !trust_anchor => revocation.status(cert, issuer, time),
+            _ => RevocationStatus::Unknown,
+        };
+        ChainEntry {
+            cert,
+            trust_anchor,
+            // the store can override the validity of an anchor
+            time_valid: anchor.map_or(validity.not_before <= time && time <= validity.not_after, |anchor| anchor.valid_at(signing_time)),
+            revocation,
+        }
+    }).collect()
+}
+
+fn chain_status(chain: &[ChainEntry], trust_store: &TrustStore, policy: &PolicyOptions, warnings: &mut Vec<String>) -> VerifyStatus {
+    let mut status = VerifyStatus::Valid;
+    match chain.last().and_then(|entry| trust_store.find(entry.cert)) {
+        Some(anchor) => {
+            let below: Vec<&Certificate> = chain.iter().filter(|entry| !entry.trust_anchor).map(|entry| entry.cert).collect();
+            // RFC 5280 path processing goes from the anchor down
+            let path: Vec<&Certificate> = below.iter().rev().copied().collect();
+            let mut errors = anchor.constraints.check(&below);
+            errors.extend(path::check_name_constraints(&path));
+            if let Err(e) = path::check_policies(&path, policy) {
+                errors.push(e);
+            }
+            for error in errors {
+                warnings.push(error);
+                status = VerifyStatus::Untrusted;
+            }
+        },
+        None => {
+            warnings.push("chain does not end at a trust anchor".to_string());
+            status = VerifyStatus::Untrusted;
+        },
+    }
+    for (i, entry) in chain.iter().enumerate() {
+        let serial = &entry.cert.tbs_certificate.serial_number;
+        if !entry.time_valid {
+            warnings.push(format!("certificate {} is not valid at the verification time", serial));
+            status = VerifyStatus::Untrusted;
+        }
+        // issuers below the anchor must be CAs
+        if i > 0 && !entry.trust_anchor && !is_ca(entry.cert) {
+            warnings.push(format!("certificate {} is not a CA", serial));
+            status = VerifyStatus::Untrusted;
+        }
+    }
+    for entry in chain {
+        match entry.revocation {
+            RevocationStatus::Revoked { .. } => {
+                warnings.push(format!("certificate {} is revoked", entry.cert.tbs_certificate.serial_number));
+                status = VerifyStatus::combine([status, VerifyStatus::Revoked]);
+            },
+            RevocationStatus::Unknown if !entry.trust_anchor => {
+                warnings.push(format!("no revocation information for certificate {}", entry.cert.tbs_certificate.serial_number));
+            },
+            _ => {},
+        }
+    }
+    status
+}
+
+// basicConstraints cA, DEFAULT FALSE
+fn is_ca(cert: &Certificate) -> bool {
+    cert.tbs_certificate.find_extension(oid::BASIC_CONSTRAINTS)
+        .and_then(|extension| extensions::basic_constraints(&extension.extn_value))
+        .is_some_and(|constraints| constraints.ca)
+}
+
+// what the revocation of a certificate is checked against, all offline
+struct RevocationSources<'a> {
+    crls: &'a [&'a CertificateList],
+    ocsp_responses: &'a [BasicOcspResponse],
+}
+
+impl RevocationSources<'_> {
+    // a revocation counts whenever it was published, "not revoked" only from a CRL or response that
+    // covers the time (RFC 5280 section 6.3.3 (a))
+    fn status(&self, cert: &Certificate, issuer: &Certificate, time: i64) -> RevocationStatus {
+        let mut status = RevocationStatus::Unknown;
+        let crl_issuer = can_sign_crls(issuer);
+        for crl in self.crls {
+            if !crl_issuer || crl.issuer != cert.tbs_certificate.issuer || crl.verify_signed_by(issuer).is_err() {
+                continue;
+            }
+            match crl.find(&cert.tbs_certificate.serial_number) {
+                Some(entry) if entry.revocation_date <= time => return RevocationStatus::Revoked { at: entry.revocation_date },
+                _ if covers(crl.this_update, crl.next_update, time) => status = RevocationStatus::Good,
+                _ => {},
+            }
+        }
+        // only responses signed by the issuer or its delegated responder count
+        for response in self.ocsp_responses {
+            let Some(single) = response.find(cert, issuer) else {
+                continue;
+            };
+            if response.responder(issuer).is_none_or(|responder| response.verify_signed_by(responder).is_err()) {
+                continue;
+            }
+            match single.status {
+                CertStatus::Revoked { at } if at <= time => return RevocationStatus::Revoked { at },
+                CertStatus::Revoked { .. } | CertStatus::Good if covers(single.this_update, single.next_update, time) => {
+                    status = RevocationStatus::Good;
+                },
+                _ => {},
+            }
+        }
+        status
+    }
+}
+
+// thisUpdate up to nextUpdate; without a nextUpdate there is always newer information (RFC 6960 section 4.2.2.1)
+fn covers(this_update: i64, next_update: Option<i64>, time: i64) -> bool {
+    this_update <= time && next_update.is_some_and(|next_update| time <= next_update)
+}
+
+// the issuer keyUsage has to allow cRLSign, a certificate without keyUsage is not restricted
+fn can_sign_crls(issuer: &Certificate) -> bool {
+    match issuer.tbs_certificate.find_extension(oid::KEY_USAGE) {
+        Some(extension) => extensions::key_usage(&extension.extn_value).is_some_and(|usages| usages.contains(&"cRLSign")),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sign::{sign, SignOptions};
+    use crate::testdata::{self, ALICE, ALICE_KEY, BOB, CADES, EC, INT, MSG, SIGNED, TIME, TSA};
+    use crate::{parse_pkcs7, AlgorithmIdentifier, Pkcs7Content};
+
+    // SignedData of our own signer, which always adds CMSAlgorithmProtection
+    fn signed_data() -> SignedData {
+        let options = SignOptions { extra_certs: vec![testdata::der(INT)], ..SignOptions::default() };
+        let der = sign(MSG, &testdata::der(ALICE), &testdata::key(ALICE_KEY), &options).unwrap();
+        match parse_pkcs7(&der).unwrap().content {
+            Pkcs7Content::SignedData(signed_data) => signed_data,
+            _ => unreachable!(),
+        }
+    }
+
+    fn status(signed_data: &SignedData, options: &VerifyOptions) -> VerifyStatus {
+        VerifyStatus::combine(verify_signed_data(signed_data, options).unwrap().iter().map(|result| result.status))
+    }
+
+    #[test]
+    fn combine_ranks_revoked_above_untrusted() {
+        use VerifyStatus::*;
+        assert_eq!(VerifyStatus::combine([]), Valid);
+        assert_eq!(VerifyStatus::combine([Valid, Untrusted]), Untrusted);
+        assert_eq!(VerifyStatus::combine([Untrusted, Revoked]), Revoked);
+        assert_eq!(VerifyStatus::combine([Revoked, Untrusted, Valid]), Revoked);
+        assert_eq!(VerifyStatus::combine([Revoked, InvalidSignature]), InvalidSignature);
+        assert_eq!(VerifyStatus::combine([Untrusted, Revoked]).exit_code(), 3);
+    }
+
+    #[test]
+    fn algorithm_protection() {
+        let signed_data = signed_data();
+        assert!(check_algorithm_protection(&signed_data.signer_infos[0]).is_ok());
+        let options = VerifyOptions { require_algorithm_protection: true, ..testdata::options() };
+        assert_eq!(status(&signed_data, &options), VerifyStatus::Valid);
+        // openssl does not add the attribute
+        let pkcs7 = parse_pkcs7(SIGNED).unwrap();
+        assert_eq!(status(pkcs7.signed_data().unwrap(), &testdata::options()), VerifyStatus::Valid);
+        assert_eq!(status(pkcs7.signed_data().unwrap(), &options), VerifyStatus::InvalidSignature);
+    }
+
+    #[test]
+    fn substituted_algorithms() {
+        let mut signed_data = signed_data();
+        signed_data.signer_infos[0].digest_algorithm = AlgorithmIdentifier { algorithm: oid::owned(oid::SHA384), parameters: None };
+        let error = check_algorithm_protection(&signed_data.signer_infos[0]).unwrap_err();
+        assert!(error.starts_with("CMSAlgorithmProtection digest algorithm sha256 differs"), "{}", error);
+        assert_eq!(status(&signed_data, &testdata::options()), VerifyStatus::InvalidSignature);
+
+        let mut signed_data = self::signed_data();
+        signed_data.signer_infos[0].signature_algorithm.algorithm = oid::owned(oid::SHA512_WITH_RSA_ENCRYPTION);
+        let error = check_algorithm_protection(&signed_data.signer_infos[0]).unwrap_err();
+        assert!(error.starts_with("CMSAlgorithmProtection signature algorithm"), "{}", error);
+        assert_eq!(status(&signed_data, &testdata::options()), VerifyStatus::InvalidSignature);
+    }
+
+    #[test]
+    fn signing_certificate_binding() {
+        let pkcs7 = parse_pkcs7(CADES).unwrap();
+        let signed_data = pkcs7.signed_data().unwrap();
+        let signer_info = &signed_data.signer_infos[0];
+        assert_eq!(check_signing_certificate(signer_info, &testdata::cert(ALICE)), Some(Ok(())));
+        assert_eq!(check_signing_certificate(signer_info, &testdata::cert(BOB)),
+                   Some(Err("signingCertificate hash does not match the signer certificate".to_string())));
+        assert_eq!(verify_signer(signer_info, &testdata::cert(BOB), MSG, &oid::owned(oid::DATA)).unwrap_err(),
+                   "signingCertificate hash does not match the signer certificate");
+        assert_eq!(status(signed_data, &testdata::options()), VerifyStatus::Valid);
+
+        let pkcs7 = parse_pkcs7(SIGNED).unwrap();
+        assert_eq!(check_signing_certificate(&pkcs7.signed_data().unwrap().signer_infos[0], &testdata::cert(ALICE)), None);
+    }
+
+    #[test]
+    fn signer_certificate_by_binding() {
+        // two certificates over alice's key: the sid names the old one, signingCertificateV2 the renewed one
+        let pkcs7 = parse_pkcs7(include_bytes!("../testdata/renewed.p7m")).unwrap();
+        let signed_data = pkcs7.signed_data().unwrap();
+        let signer_info = &signed_data.signer_infos[0];
+        let (old, renewed) = (testdata::cert(ALICE), testdata::cert(include_bytes!("../testdata/alice_renewed.crt")));
+        assert_eq!(hex::encode(&signer_info.signer_identifier.serial_number), old.tbs_certificate.serial_number);
+        let found = find_signer_cert(signer_info, signed_data.certs.iter()).unwrap();
+        assert_eq!(found.tbs_certificate.serial_number, renewed.tbs_certificate.serial_number);
+
+        let options = testdata::options();
+        let result = &verify_signed_data(signed_data, &options).unwrap()[0];
+        assert_eq!(result.status, VerifyStatus::Valid);
+        assert_eq!(result.chain[0].cert.tbs_certificate.serial_number, renewed.tbs_certificate.serial_number);
+    }
+
+    #[test]
+    fn signer_policy_key_usage() {
+        let policy = |key_usage: &[&[&'static str]]| SignerPolicy {
+            key_usage: key_usage.iter().map(|any_of| any_of.to_vec()).collect(),
+            ..SignerPolicy::default()
+        };
+        assert!(policy(&[&["digitalSignature"], &["nonRepudiation"]]).check(&testdata::cert(ALICE)).is_empty());
+        assert_eq!(policy(&[&["digitalSignature"], &["nonRepudiation"]]).check(&testdata::cert(EC)),
+                   ["the signer certificate keyUsage does not allow nonRepudiation (it has digitalSignature, keyAgreement)"]);
+        // one bit of an entry is enough, the qualified certificate has nonRepudiation only
+        let qualified = testdata::cert(include_bytes!("../testdata/qualified.crt"));
+        assert!(policy(&[&["digitalSignature", "nonRepudiation"]]).check(&qualified).is_empty());
+        assert_eq!(policy(&[&["digitalSignature", "keyAgreement"]]).check(&qualified),
+                   ["the signer certificate keyUsage does not allow digitalSignature or keyAgreement (it has nonRepudiation)"]);
+    }
+
+    #[test]
+    fn signer_policy_eku() {
+        let policy = |ekus: &[&str]| SignerPolicy { ekus: ekus.iter().map(|eku| oid::by_name(eku).unwrap()).collect(), ..SignerPolicy::default() };
+        assert!(policy(&["emailProtection"]).check(&testdata::cert(ALICE)).is_empty());
+        assert!(policy(&["codeSigning", "timeStamping"]).check(&testdata::cert(TSA)).is_empty());
+        // no extendedKeyUsage, no restriction
+        assert!(policy(&["documentSigning"]).check(&testdata::cert(include_bytes!("../testdata/carol.crt"))).is_empty());
+        let errors = policy(&["documentSigning"]).check(&testdata::cert(ALICE));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("the signer certificate extendedKeyUsage has none of"), "{}", errors[0]);
+        // the signature stays valid, the certificate is not trusted for it
+        let pkcs7 = parse_pkcs7(SIGNED).unwrap();
+        let options = VerifyOptions { signer_policy: policy(&["codeSigning"]), ..testdata::options() };
+        let results = verify_signed_data(pkcs7.signed_data().unwrap(), &options).unwrap();
+        assert!(results[0].signature.is_ok());
+        assert_eq!(results[0].status, VerifyStatus::Untrusted);
+    }
+
+    #[test]
+    fn stale_crl() {
+        let crl = |der: &[u8]| CertificateList::from_der(der).unwrap();
+        let (fresh, stale) = (crl(include_bytes!("../testdata/int.crl")), crl(include_bytes!("../testdata/int_stale.crl")));
+        let status = |crls: &[&CertificateList], cert: &[u8]| {
+            RevocationSources { crls, ocsp_responses: &[] }.status(&testdata::cert(cert), &testdata::cert(INT), TIME)
+        };
+        assert_eq!(status(&[&fresh], ALICE), RevocationStatus::Good);
+        // past its next update the CRL says nothing about alice
+        assert_eq!(status(&[&stale], ALICE), RevocationStatus::Unknown);
+        assert!(matches!(status(&[&stale, &fresh], BOB), RevocationStatus::Revoked { .. }));
+        assert!(can_sign_crls(&testdata::cert(INT)));
+        assert!(!can_sign_crls(&testdata::cert(ALICE)));
+    }
+}
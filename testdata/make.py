@@ -0,0 +1,654 @@
+#!/usr/bin/env python3
+# test fixtures, made with openssl (3.x) and committed; only missing files are made, so a section
+# added later reuses the keys already there. Delete everything but this script to start over.
+#
+# certificates are valid from 2020 to 2050, tests verify at 2027-01-01T00:00:00Z
+
+import base64
+import hashlib
+import os
+import shutil
+import struct
+import subprocess
+import zipfile
+
+D = os.path.dirname(os.path.abspath(__file__))
+VALIDITY = ['-not_before', '20200101000000Z', '-not_after', '20500101000000Z']
+EXTENSIONS = '''
+[ca]
+basicConstraints=critical,CA:TRUE
+keyUsage=critical,keyCertSign,cRLSign
+subjectKeyIdentifier=hash
+[alice]
+basicConstraints=CA:FALSE
+keyUsage=critical,digitalSignature,nonRepudiation,keyEncipherment
+extendedKeyUsage=emailProtection
+subjectAltName=email:alice@example.com
+[ec]
+basicConstraints=CA:FALSE
+keyUsage=critical,digitalSignature,keyAgreement
+extendedKeyUsage=emailProtection
+[codesign]
+basicConstraints=CA:FALSE
+keyUsage=critical,digitalSignature
+extendedKeyUsage=codeSigning
+[tsa]
+basicConstraints=CA:FALSE
+keyUsage=critical,digitalSignature
+extendedKeyUsage=critical,timeStamping
+'''
+SIGNATURE_TIME_STAMP_TOKEN = '1.2.840.113549.1.9.16.2.14'
+REVOCATION_VALUES = '1.2.840.113549.1.9.16.2.24'
+CERTIFICATE_VALUES = '1.2.840.113549.1.9.16.2.23'
+CERTIFICATE_REFS = '1.2.840.113549.1.9.16.2.21'
+REVOCATION_REFS = '1.2.840.113549.1.9.16.2.22'
+ARCHIVE_TIME_STAMP_V3 = '0.4.0.1733.2.4'
+ATS_HASH_INDEX_V3 = '0.4.0.19122.1.5'
+
+
+# DER as [tag, value] trees, constructed values are lists of children
+def parse(der, i=0):
+    tag, length, j = der[i], der[i + 1], i + 2
+    if length & 0x80:
+        n = length & 0x7f
+        length, j = int.from_bytes(der[j:j + n], 'big'), j + n
+    value = der[j:j + length]
+    if tag & 0x20:
+        children, k = [], 0
+        while k < len(value):
+            child, k = parse(value, k)
+            children.append(child)
+        value = children
+    return [tag, value], j + length
+
+
+def tlv(tag, value):
+    if len(value) < 0x80:
+        length = bytes([len(value)])
+    else:
+        n = (len(value).bit_length() + 7) // 8
+        length = bytes([0x80 | n]) + len(value).to_bytes(n, 'big')
+    return bytes([tag]) + length + value
+
+
+def serialize(node):
+    tag, value = node
+    return tlv(tag, b''.join(serialize(child) for child in value) if isinstance(value, list) else value)
+
+
+def oid(dotted):
+    arcs = [int(arc) for arc in dotted.split('.')]
+    out = bytes([40 * arcs[0] + arcs[1]])
+    for arc in arcs[2:]:
+        encoded = [arc & 0x7f]
+        arc >>= 7
+        while arc:
+            encoded.append(0x80 | arc & 0x7f)
+            arc >>= 7
+        out += bytes(reversed(encoded))
+    return tlv(0x06, out)
+
+
+# the first SignerInfo of a ContentInfo with SignedData
+def signer_info(tree):
+    return tree[1][1][1][0][1][-1][1][0]
+
+
+def signature_value(der):
+    return next(child[1] for child in signer_info(parse(der)[0])[1] if child[0] == 0x04)
+
+
+# the ContentInfo with an unsigned attribute added to the first signer, the SET kept in DER order
+def add_unsigned_attribute(der, attribute_type, value):
+    tree = parse(der)[0]
+    fields = signer_info(tree)[1]
+    if fields[-1][0] != 0xa1:
+        fields.append([0xa1, []])
+    attributes = fields[-1][1]
+    attributes.append(parse(tlv(0x30, oid(attribute_type) + tlv(0x31, value)))[0])
+    attributes.sort(key=serialize)
+    return serialize(tree)
+
+
+def path(name):
+    return os.path.join(D, name)
+
+
+def missing(*names):
+    return not all(os.path.exists(path(name)) for name in names)
+
+
+def openssl(*args, stdin=None):
+    subprocess.run(['openssl', *args], cwd=D, check=True, input=stdin, stdout=subprocess.DEVNULL, stderr=subprocess.DEVNULL)
+
+
+def key(name, algorithm):
+    if missing(name):
+        if algorithm == 'rsa':
+            openssl('genpkey', '-algorithm', 'RSA', '-pkeyopt', 'rsa_keygen_bits:2048', '-out', name)
+        else:
+            openssl('genpkey', '-algorithm', 'EC', '-pkeyopt', 'ec_paramgen_curve:' + algorithm, '-out', name)
+
+
+# name.crt for a new name.key, issued by issuer.crt or self-signed; extensions adds sections to EXTENSIONS
+def cert(name, subject, section, issuer=None, algorithm='rsa', extensions=''):
+    if not missing(name + '.crt'):
+        return
+    key(name + '.key', algorithm)
+    with open(path('ext.cnf'), 'w') as f:
+        f.write(EXTENSIONS + extensions)
+    openssl('req', '-new', '-key', name + '.key', '-subj', subject, '-out', name + '.csr')
+    signer = ['-CA', issuer + '.crt', '-CAkey', issuer + '.key'] if issuer else ['-key', name + '.key']
+    openssl('x509', '-req', '-in', name + '.csr', *signer, '-sha256', *VALIDITY, '-extfile', 'ext.cnf',
+            '-extensions', section, '-out', name + '.crt')
+    os.remove(path(name + '.csr'))
+    os.remove(path('ext.cnf'))
+
+
+def pki():
+    cert('root', '/CN=Test Root/O=Acme', 'ca')
+    cert('int', '/CN=Test Intermediate/O=Acme', 'ca', 'root', 'P-256')
+    cert('alice', '/CN=Alice/emailAddress=alice@example.com', 'alice', 'int')
+    cert('ec', '/CN=Eve Curve/O=Acme', 'ec', 'int', 'P-256')
+    if missing('msg.txt'):
+        with open(path('msg.txt'), 'w') as f:
+            f.write('hello signed\n')
+
+
+# EnvelopedData for each RecipientInfo type and an AES-GCM AuthEnvelopedData, the content is msg.txt
+def enveloped():
+    if missing('ktri.p7m'):
+        openssl('cms', '-encrypt', '-binary', '-aes256', '-in', 'msg.txt', '-recip', 'alice.crt', '-outform', 'DER', '-out', 'ktri.p7m')
+    if missing('kari.p7m'):
+        openssl('cms', '-encrypt', '-binary', '-aes128', '-in', 'msg.txt', '-recip', 'ec.crt', '-outform', 'DER', '-out', 'kari.p7m')
+    if missing('kekri.p7m'):
+        openssl('cms', '-encrypt', '-binary', '-aes128', '-in', 'msg.txt', '-secretkey', '000102030405060708090a0b0c0d0e0f',
+                '-secretkeyid', '6b656b', '-outform', 'DER', '-out', 'kekri.p7m')
+    if missing('auth.p7m'):
+        openssl('cms', '-encrypt', '-binary', '-aes-128-gcm', '-in', 'msg.txt', '-recip', 'alice.crt', '-outform', 'DER', '-out', 'auth.p7m')
+    # encrypted then signed: alice signs the bare EnvelopedData of ktri.p7m
+    if missing('signed_envelope.p7m'):
+        with open(path('ktri.p7m'), 'rb') as f:
+            enveloped = serialize(parse(f.read())[0][1][1][1][0])
+        with open(path('envelope.der'), 'wb') as f:
+            f.write(enveloped)
+        openssl('cms', '-sign', '-binary', '-nodetach', '-econtent_type', '1.2.840.113549.1.7.3', '-in', 'envelope.der',
+                '-signer', 'alice.crt', '-inkey', 'alice.key', '-certfile', 'int.crt', '-outform', 'DER', '-out', 'signed_envelope.p7m')
+        os.remove(path('envelope.der'))
+    if missing('pwri.p7m'):
+        openssl('cms', '-encrypt', '-binary', '-aes256', '-in', 'msg.txt', '-pwri_password', 'secret', '-outform', 'DER', '-out', 'pwri.p7m')
+
+
+# SignedData made by openssl, attached, with the intermediate; cades.p7m has a signingCertificateV2
+def signed():
+    for name, signer, options in [('signed.p7m', 'alice', []), ('signed_ec.p7m', 'ec', []), ('cades.p7m', 'alice', ['-cades'])]:
+        if missing(name):
+            openssl('cms', '-sign', '-binary', '-nodetach', *options, '-in', 'msg.txt', '-signer', signer + '.crt',
+                    '-inkey', signer + '.key', '-certfile', 'int.crt', '-outform', 'DER', '-out', name)
+
+
+# a DER CRL of issuer, revoking the certificates given, with its next update days from now
+def crl(name, issuer, *revoked, days=9000):
+    if not missing(name):
+        return
+    os.makedirs(path('ca'))
+    open(path('ca/index.txt'), 'w').close()
+    with open(path('ca/crlnumber'), 'w') as f:
+        f.write('01\n')
+    with open(path('ca.cnf'), 'w') as f:
+        f.write('[ca]\ndefault_ca = issuer\n[issuer]\ndatabase = ca/index.txt\ncrlnumber = ca/crlnumber\n'
+                'certificate = {0}.crt\nprivate_key = {0}.key\ndefault_md = sha256\n'.format(issuer))
+    for cert in revoked:
+        openssl('ca', '-config', 'ca.cnf', '-revoke', cert)
+    openssl('ca', '-config', 'ca.cnf', '-gencrl', '-crldays', str(days), '-out', 'crl.pem')
+    openssl('crl', '-in', 'crl.pem', '-outform', 'DER', '-out', name)
+    shutil.rmtree(path('ca'))
+    os.remove(path('ca.cnf'))
+    os.remove(path('crl.pem'))
+
+
+# bob's certificate is revoked by the intermediate CRL; int_stale.crl revokes nothing and is past its
+# next update at the test time
+def revocation():
+    cert('bob', '/CN=Bob/O=Acme', 'alice', 'int')
+    if missing('bob.p7m'):
+        openssl('cms', '-sign', '-binary', '-nodetach', '-in', 'msg.txt', '-signer', 'bob.crt', '-inkey', 'bob.key',
+                '-certfile', 'int.crt', '-outform', 'DER', '-out', 'bob.p7m')
+    crl('int.crl', 'int', 'bob.crt')
+    crl('int_stale.crl', 'int', days=30)
+
+
+# an RFC 3161 token by tsa.crt over data
+def time_stamp(data, tsa):
+    os.makedirs(path('ts'))
+    with open(path('ts/data'), 'wb') as f:
+        f.write(data)
+    with open(path('ts/serial'), 'w') as f:
+        f.write('01\n')
+    with open(path('ts/tsa.cnf'), 'w') as f:
+        f.write('[tsa]\ndefault_tsa = tsa\n[tsa_config]\n')
+        f.write('[tsa]\nserial = ts/serial\nsigner_cert = {0}.crt\nsigner_key = {0}.key\ncerts = {1}\n'
+                'signer_digest = sha256\ndefault_policy = 1.2.3.4.1\ndigests = sha256\ness_cert_id_alg = sha256\n'
+                .format(tsa, 'int.crt' if tsa == 'tsa' else 'root2.crt'))
+    openssl('ts', '-query', '-data', 'ts/data', '-sha256', '-cert', '-out', 'ts/query')
+    openssl('ts', '-reply', '-config', 'ts/tsa.cnf', '-queryfile', 'ts/query', '-token_out', '-out', 'ts/token')
+    with open(path('ts/token'), 'rb') as f:
+        token = f.read()
+    shutil.rmtree(path('ts'))
+    return token
+
+
+# signed.p7m with a signatureTimeStampToken, from a TSA under the root and from one under an unknown root
+def timestamps():
+    cert('tsa', '/CN=Test TSA/O=Acme', 'tsa', 'int')
+    cert('root2', '/CN=Other Root/O=Elsewhere', 'ca')
+    cert('tsa2', '/CN=Other TSA/O=Elsewhere', 'tsa', 'root2')
+    for name, tsa in [('ts.p7m', 'tsa'), ('ts_untrusted.p7m', 'tsa2')]:
+        if missing(name):
+            with open(path('signed.p7m'), 'rb') as f:
+                der = f.read()
+            token = time_stamp(signature_value(der), tsa)
+            with open(path(name), 'wb') as f:
+                f.write(add_unsigned_attribute(der, SIGNATURE_TIME_STAMP_TOKEN, token))
+
+
+# alice's certificate renewed over the same key; renewed.p7m names the old one in its sid and binds the
+# renewed one with its signingCertificateV2, both are in the certificates
+def renewal():
+    if missing('alice_renewed.key'):
+        shutil.copy(path('alice.key'), path('alice_renewed.key'))
+    cert('alice_renewed', '/CN=Alice/emailAddress=alice@example.com', 'alice', 'int')
+    if missing('renewed.p7m'):
+        with open(path('certs.pem'), 'wb') as f:
+            for name in ['int.crt', 'alice.crt']:
+                with open(path(name), 'rb') as cert_file:
+                    f.write(cert_file.read())
+        openssl('cms', '-sign', '-binary', '-nodetach', '-cades', '-in', 'msg.txt', '-signer', 'alice_renewed.crt',
+                '-inkey', 'alice_renewed.key', '-certfile', 'certs.pem', '-outform', 'DER', '-out', 'sign.p7m')
+        openssl('x509', '-in', 'alice.crt', '-outform', 'DER', '-out', 'alice.der')
+        with open(path('sign.p7m'), 'rb') as f:
+            tree = parse(f.read())[0]
+        with open(path('alice.der'), 'rb') as f:
+            alice = parse(f.read())[0]
+        signer_info(tree)[1][1][1][1] = alice[1][0][1][1]
+        with open(path('renewed.p7m'), 'wb') as f:
+            f.write(serialize(tree))
+        for name in ['certs.pem', 'sign.p7m', 'alice.der']:
+            os.remove(path(name))
+
+
+def read(name):
+    with open(path(name), 'rb') as f:
+        return f.read()
+
+
+def pem_der(name):
+    pem = read(name).decode()
+    return base64.b64decode(''.join(line for line in pem.splitlines() if not line.startswith('-----')))
+
+
+def write(name, der):
+    with open(path(name), 'wb') as f:
+        f.write(der)
+
+
+# ATSHashIndexV3 of the SignedData and the data its archive-time-stamp-v3 covers (EN 319 122-1 5.5.2, 5.5.3)
+def archive_time_stamp_data(der, content):
+    sha256 = lambda data: hashlib.sha256(data).digest()
+    tree = parse(der)[0]
+    signed_data = tree[1][1][1][0][1]
+    certificates = next((child[1] for child in signed_data if child[0] == 0xa0), [])
+    fields = signer_info(tree)[1]
+    unsigned = fields[-1][1] if fields[-1][0] == 0xa1 else []
+    values = [sha256(serialize(attribute[1][0]) + serialize(value)) for attribute in unsigned for value in attribute[1][1][1]]
+    hashes = lambda digests: tlv(0x30, b''.join(tlv(0x04, digest) for digest in digests))
+    index = tlv(0x30, hashes(sha256(serialize(cert)) for cert in certificates) + hashes([]) + hashes(values))
+    signed_fields = b''.join(serialize(field) for field in fields if field[0] != 0xa1)
+    return serialize(signed_data[2][1][0]) + sha256(content) + signed_fields + index, index
+
+
+# cades.p7m at each CAdES baseline level, the time-stamps of tsa.crt; B-T also by the untrusted TSA
+def baseline():
+    crl('root.crl', 'root')
+    levels = [('cades_t.p7m', 'cades.p7m'), ('cades_t_untrusted.p7m', 'cades.p7m'), ('cades_lt.p7m', 'cades_t.p7m'),
+              ('cades_lta.p7m', 'cades_lt.p7m')]
+    for name, base in levels:
+        if not missing(name):
+            continue
+        der = read(base)
+        if name.startswith('cades_t'):
+            token = time_stamp(signature_value(der), 'tsa2' if name.endswith('untrusted.p7m') else 'tsa')
+            der = add_unsigned_attribute(der, SIGNATURE_TIME_STAMP_TOKEN, token)
+        elif name == 'cades_lt.p7m':
+            # alice and the intermediate are in the certificates already, their CRLs go in revocation-values
+            crls = tlv(0xa0, tlv(0x30, read('int.crl') + read('root.crl')))
+            der = add_unsigned_attribute(der, REVOCATION_VALUES, tlv(0x30, crls))
+        else:
+            data, index = archive_time_stamp_data(der, read('msg.txt'))
+            token = add_unsigned_attribute(time_stamp(data, 'tsa'), ATS_HASH_INDEX_V3, index)
+            der = add_unsigned_attribute(der, ARCHIVE_TIME_STAMP_V3, token)
+        write(name, der)
+
+
+# alice's signature without the intermediate, which comes in certificate-values with the CRLs in
+# revocation-values, and the complete references to them: SHA-1 for the certificates, SHA-256 for the CRLs
+def long_term():
+    if not missing('ltv.p7m'):
+        return
+    openssl('cms', '-sign', '-binary', '-nodetach', '-cades', '-in', 'msg.txt', '-signer', 'alice.crt', '-inkey', 'alice.key',
+            '-outform', 'DER', '-out', 'ltv.p7m')
+    crls = [read('int.crl'), read('root.crl')]
+    int_der = pem_der('int.crt')
+    sha256 = tlv(0x30, oid('2.16.840.1.101.3.4.2.1'))
+    cert_refs = tlv(0x30, b''.join(tlv(0x30, tlv(0x04, hashlib.sha1(cert).digest())) for cert in [pem_der('alice.crt'), int_der]))
+    crl_ids = b''.join(tlv(0x30, tlv(0x30, sha256 + tlv(0x04, hashlib.sha256(crl).digest()))) for crl in crls)
+    revocation_refs = tlv(0x30, tlv(0x30, tlv(0xa0, tlv(0x30, tlv(0x30, crl_ids)))))
+    der = read('ltv.p7m')
+    der = add_unsigned_attribute(der, CERTIFICATE_VALUES, tlv(0x30, int_der))
+    der = add_unsigned_attribute(der, REVOCATION_VALUES, tlv(0x30, tlv(0xa0, tlv(0x30, b''.join(crls)))))
+    der = add_unsigned_attribute(der, CERTIFICATE_REFS, cert_refs)
+    der = add_unsigned_attribute(der, REVOCATION_REFS, revocation_refs)
+    write('ltv.p7m', der)
+
+
+# a one page PDF with a signature dictionary, /Contents filled in by sign(signed bytes)
+def pdf(sub_filter, sign):
+    placeholder = 8192
+    kind = b'DocTimeStamp' if sub_filter == b'ETSI.RFC3161' else b'Sig'
+    objects = [
+        b'<< /Type /Catalog /Pages 2 0 R /AcroForm << /Fields [4 0 R] /SigFlags 3 >> >>',
+        b'<< /Type /Pages /Kids [3 0 R] /Count 1 >>',
+        b'<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Annots [4 0 R] >>',
+        b'<< /Type /Annot /Subtype /Widget /FT /Sig /Rect [0 0 0 0] /T (Signature1) /V 5 0 R /P 3 0 R >>',
+        b'<< /Type /' + kind + b' /Filter /Adobe.PPKLite /SubFilter /' + sub_filter
+        + b' /ByteRange [0000000000 0000000000 0000000000 0000000000] /Contents <' + b'0' * placeholder
+        + b"> /Name (Alice \\(test\\)) /Reason <FEFF0074006500730074> /M (D:20261019120000+00'00') >>",
+    ]
+    out = b'%PDF-1.7\n%\xe2\xe3\xcf\xd3\n'
+    offsets = []
+    for i, body in enumerate(objects):
+        offsets.append(len(out))
+        out += b'%d 0 obj\n' % (i + 1) + body + b'\nendobj\n'
+    xref = len(out)
+    out += b'xref\n0 %d\n0000000000 65535 f \n' % (len(objects) + 1) + b''.join(b'%010d 00000 n \n' % o for o in offsets)
+    out += b'trailer\n<< /Size %d /Root 1 0 R >>\nstartxref\n%d\n%%%%EOF\n' % (len(objects) + 1, xref)
+    start = out.index(b'/Contents <') + len(b'/Contents ')
+    end = start + placeholder + 2
+    byte_range = b'[%010d %010d %010d %010d]' % (0, start, end, len(out) - end)
+    at = out.index(b'[0000000000 0000000000 0000000000 0000000000]')
+    out = out[:at] + byte_range + out[at + len(byte_range):]
+    contents = sign(out[:start] + out[end:]).hex().encode()
+    return out[:start + 1] + contents + b'0' * (placeholder - len(contents)) + out[start + 1 + placeholder:]
+
+
+# alice signs data with openssl cms and the options given
+def cms_sign(data, *options):
+    return cms_sign_as('alice', data, *options)
+
+
+def cms_sign_as(signer, data, *options):
+    write('sign.bin', data)
+    openssl('cms', '-sign', '-binary', *options, '-in', 'sign.bin', '-signer', signer + '.crt', '-inkey', signer + '.key',
+            '-certfile', 'int.crt', '-outform', 'DER', '-out', 'sign.p7m')
+    der = read('sign.p7m')
+    os.remove(path('sign.bin'))
+    os.remove(path('sign.p7m'))
+    return der
+
+
+# a PAdES signature, a legacy adbe.pkcs7.sha1 one and a document time-stamp
+def pades():
+    documents = [
+        ('signed.pdf', b'ETSI.CAdES.detached', lambda data: cms_sign(data, '-cades')),
+        ('sha1.pdf', b'adbe.pkcs7.sha1', lambda data: cms_sign(hashlib.sha1(data).digest(), '-nodetach')),
+        ('docts.pdf', b'ETSI.RFC3161', lambda data: time_stamp(data, 'tsa')),
+    ]
+    for name, sub_filter, sign in documents:
+        if missing(name):
+            write(name, pdf(sub_filter, sign))
+
+
+# signed.p7m countersigned by ec as a whole: as id-data, and as the bare SignedData under eContentType signedData
+def nested():
+    if missing('nested.p7m'):
+        write('nested.p7m', cms_sign_as('ec', read('signed.p7m'), '-nodetach'))
+    if missing('nested_econtent.p7m'):
+        inner = serialize(parse(read('signed.p7m'))[0][1][1][1][0])
+        write('nested_econtent.p7m', cms_sign_as('ec', inner, '-nodetach', '-econtent_type', '1.2.840.113549.1.7.2'))
+
+
+# e-mails from alice signed by openssl smime, clear signed (multipart/signed) and opaque (pkcs7-mime)
+def smime():
+    for name, options in [('smime.eml', []), ('smime_opaque.eml', ['-nodetach'])]:
+        if missing(name):
+            openssl('smime', '-sign', *options, '-in', 'msg.txt', '-signer', 'alice.crt', '-inkey', 'alice.key', '-certfile', 'int.crt',
+                    '-from', 'Alice <alice@example.com>', '-to', 'bob@example.com', '-subject', 'hello', '-out', name)
+
+
+# a PE32+ image with one 0x400 byte section and no signature
+def pe_image():
+    dos = bytearray(64)
+    dos[0:2] = b'MZ'
+    struct.pack_into('<I', dos, 0x3c, 0x40)
+    coff = struct.pack('<HHIIIHH', 0x8664, 1, 0, 0, 0, 240, 0x22)
+    optional = bytearray(240)
+    struct.pack_into('<H', optional, 0, 0x20b)
+    struct.pack_into('<I', optional, 60, 0x200)  # SizeOfHeaders
+    struct.pack_into('<I', optional, 64, 0x1234)  # CheckSum
+    struct.pack_into('<I', optional, 108, 16)  # NumberOfRvaAndSizes
+    section = b'.text\0\0\0' + struct.pack('<IIIIIIHHI', 0x400, 0x1000, 0x400, 0x200, 0, 0, 0, 0, 0x60000020)
+    headers = dos + b'PE\0\0' + coff + optional + section
+    return headers + bytes(0x200 - len(headers)) + bytes(range(256)) * 4
+
+
+# Authenticode: the SignedData of an SpcIndirectDataContent over the image hash, in the certificate table
+def authenticode(image, signer):
+    security_directory = 0x40 + 24 + 112 + 4 * 8
+    checksum = 0x40 + 24 + 64
+    image_hash = hashlib.sha256(image[:checksum] + image[checksum + 4:security_directory] + image[security_directory + 8:]).digest()
+    sha256 = tlv(0x30, oid('2.16.840.1.101.3.4.2.1') + b'\x05\x00')
+    indirect = tlv(0x30, oid('1.3.6.1.4.1.311.2.1.15') + tlv(0x30, tlv(0x03, b'\0'))) + tlv(0x30, sha256 + tlv(0x04, image_hash))
+    der = cms_sign_as(signer, indirect, '-nodetach', '-econtent_type', '1.3.6.1.4.1.311.2.1.4')
+    # the eContent is the SpcIndirectDataContent SEQUENCE itself, not an OCTET STRING around it
+    tree = parse(der)[0]
+    econtent = tree[1][1][1][0][1][2][1][1]
+    econtent[1][0] = [0x30, econtent[1][0][1]]
+    der = serialize(tree)
+    win_certificate = struct.pack('<IHH', 8 + len(der), 0x200, 2) + der
+    win_certificate += bytes(-len(win_certificate) % 8)
+    signed = bytearray(image)
+    struct.pack_into('<II', signed, security_directory, len(image), len(win_certificate))
+    return bytes(signed) + win_certificate
+
+
+# a PE signed with a code signing certificate
+def code_signing():
+    cert('codesign', '/CN=Acme Code Signing/O=Acme', 'codesign', 'int')
+    if missing('signed.exe'):
+        write('signed.exe', authenticode(pe_image(), 'codesign'))
+
+
+# manifest lines are at most 72 bytes, continued by a leading space
+def manifest_section(*attributes):
+    out = b''
+    for key, value in attributes:
+        line = ('%s: %s' % (key, value)).encode()
+        out += line[:72] + b'\r\n'
+        for i in range(72, len(line), 71):
+            out += b' ' + line[i:i + 71] + b'\r\n'
+    return out + b'\r\n'
+
+
+# a JAR signed by alice, META-INF/ALICE.RSA over ALICE.SF over MANIFEST.MF; then the same archive with an entry
+# added after signing, and with a signed entry changed
+def jar():
+    if not missing('signed.jar', 'unsigned_entry.jar', 'tampered.jar'):
+        return
+    digest = lambda data: base64.b64encode(hashlib.sha256(data).digest()).decode()
+    entries = [('hello.txt', read('msg.txt'), zipfile.ZIP_DEFLATED),
+               ('data/a-directory-name-long-enough-to-continue-the-manifest-line/bytes.bin', bytes(range(256)), zipfile.ZIP_STORED)]
+    main = manifest_section(('Manifest-Version', '1.0'), ('Created-By', 'make.py'))
+    sections = [manifest_section(('Name', name), ('SHA-256-Digest', digest(data))) for name, data, _ in entries]
+    manifest = main + b''.join(sections)
+    signature_file = manifest_section(('Signature-Version', '1.0'), ('SHA-256-Digest-Manifest', digest(manifest)),
+                                      ('SHA-256-Digest-Manifest-Main-Attributes', digest(main)))
+    signature_file += b''.join(manifest_section(('Name', name), ('SHA-256-Digest', digest(section)))
+                               for (name, _, _), section in zip(entries, sections))
+    block = cms_sign(signature_file)
+    archives = [('signed.jar', entries), ('unsigned_entry.jar', entries + [('extra.txt', b'added later\n', zipfile.ZIP_DEFLATED)]),
+                ('tampered.jar', [(entries[0][0], b'hello forged\n', entries[0][2])] + entries[1:])]
+    for name, contents in archives:
+        with zipfile.ZipFile(path(name), 'w') as archive:
+            archive.writestr('META-INF/MANIFEST.MF', manifest, zipfile.ZIP_DEFLATED)
+            archive.writestr('META-INF/ALICE.SF', signature_file, zipfile.ZIP_DEFLATED)
+            archive.writestr('META-INF/ALICE.RSA', block, zipfile.ZIP_DEFLATED)
+            for entry, data, method in contents:
+                archive.writestr(entry, data, method)
+
+
+# PFX files: alice with the intermediate in PBES2 and a SHA-256 MAC (the openssl default), alice in triple DES
+# with a SHA-1 MAC, and ec with the legacy RC2 certificate bag and an empty password
+def pkcs12():
+    files = [('alice.p12', 'alice', ['-certfile', 'int.crt', '-name', 'alice', '-passout', 'pass:secret']),
+             ('alice_3des.p12', 'alice', ['-certpbe', 'PBE-SHA1-3DES', '-keypbe', 'PBE-SHA1-3DES', '-macalg', 'sha1',
+                                          '-passout', 'pass:secret']),
+             ('ec_legacy.p12', 'ec', ['-legacy', '-passout', 'pass:'])]
+    for name, owner, options in files:
+        if missing(name):
+            openssl('pkcs12', '-export', '-in', owner + '.crt', '-inkey', owner + '.key', *options, '-out', name)
+
+
+# trust stores: a PEM bundle, OpenSSL TRUSTED CERTIFICATE files, a p7b and a hashed directory with the root CRL
+def anchors():
+    if missing('roots.pem'):
+        write('roots.pem', read('root.crt') + read('root2.crt'))
+    for name, option, purpose in [('root_no_email.pem', '-addreject', 'emailProtection'),
+                                  ('root_code_signing.pem', '-addtrust', 'codeSigning')]:
+        if missing(name):
+            openssl('x509', '-in', 'root.crt', option, purpose, '-trustout', '-out', name)
+    if missing('roots.p7b'):
+        openssl('crl2pkcs7', '-nocrl', '-certfile', 'root.crt', '-certfile', 'root2.crt', '-outform', 'DER', '-out', 'roots.p7b')
+    if missing('hashed'):
+        os.makedirs(path('hashed'))
+        subject_hash = subprocess.run(['openssl', 'x509', '-in', path('root.crt'), '-noout', '-hash'], check=True,
+                                      capture_output=True, text=True).stdout.strip()
+        shutil.copy(path('root.crt'), path('hashed/%s.0' % subject_hash))
+        openssl('crl', '-in', 'root.crl', '-inform', 'DER', '-out', 'hashed/%s.r0' % subject_hash)
+
+
+TSL_SERVICE = '''
+      <tsl:TSPService>
+        <tsl:ServiceInformation>
+          <tsl:ServiceTypeIdentifier>http://uri.etsi.org/TrstSvc/Svctype/{type}</tsl:ServiceTypeIdentifier>
+          <tsl:ServiceName><tsl:Name xml:lang="it">{name} (it)</tsl:Name><tsl:Name xml:lang="en">{name}</tsl:Name></tsl:ServiceName>
+          <tsl:ServiceDigitalIdentity><tsl:DigitalId><tsl:X509Certificate>{cert}</tsl:X509Certificate></tsl:DigitalId></tsl:ServiceDigitalIdentity>
+          <tsl:ServiceStatus>http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/{status}</tsl:ServiceStatus>
+          <tsl:StatusStartingTime>{start}</tsl:StatusStartingTime>
+        </tsl:ServiceInformation>
+        <tsl:ServiceHistory>
+          <tsl:ServiceHistoryInstance>
+            <tsl:ServiceTypeIdentifier>http://uri.etsi.org/TrstSvc/Svctype/{type}</tsl:ServiceTypeIdentifier>
+            <tsl:ServiceName><tsl:Name xml:lang="en">{name}</tsl:Name></tsl:ServiceName>
+            <tsl:ServiceDigitalIdentity><tsl:DigitalId><tsl:X509Certificate>{history_cert}</tsl:X509Certificate></tsl:DigitalId></tsl:ServiceDigitalIdentity>
+            <tsl:ServiceStatus>http://uri.etsi.org/TrstSvc/TrustedList/Svcstatus/granted</tsl:ServiceStatus>
+            <tsl:StatusStartingTime>2020-01-01T00:00:00Z</tsl:StatusStartingTime>
+          </tsl:ServiceHistoryInstance>
+        </tsl:ServiceHistory>
+      </tsl:TSPService>'''
+
+
+# an unsigned trusted list of Acme Trust with the services given
+def trusted_list(name, services):
+    if not missing(name):
+        return
+    xml = '''<?xml version="1.0" encoding="UTF-8"?>
+<tsl:TrustServiceStatusList xmlns:tsl="http://uri.etsi.org/02231/v2#" TSLTag="http://uri.etsi.org/19612/TSLTag">
+  <tsl:SchemeInformation>
+    <tsl:TSLVersionIdentifier>5</tsl:TSLVersionIdentifier>
+    <tsl:TSLSequenceNumber>42</tsl:TSLSequenceNumber>
+    <tsl:SchemeTerritory>XX</tsl:SchemeTerritory>
+    <tsl:ListIssueDateTime>2026-10-01T00:00:00Z</tsl:ListIssueDateTime>
+    <tsl:NextUpdate><tsl:dateTime>2027-04-01T00:00:00Z</tsl:dateTime></tsl:NextUpdate>
+  </tsl:SchemeInformation>
+  <tsl:TrustServiceProviderList>
+    <tsl:TrustServiceProvider>
+      <tsl:TSPInformation><tsl:TSPName><tsl:Name xml:lang="en">Acme Trust</tsl:Name></tsl:TSPName></tsl:TSPInformation>
+      <tsl:TSPServices>{services}
+      </tsl:TSPServices>
+    </tsl:TrustServiceProvider>
+  </tsl:TrustServiceProviderList>
+</tsl:TrustServiceStatusList>
+'''.format(services=''.join(TSL_SERVICE.format(**service) for service in services))
+    write(name, xml.encode())
+
+
+# tsl.xml: the root CA withdrawn from mid 2027; another CA that was the intermediate key until 2028, then
+# root2; a time-stamping service, never an anchor. tsl_withdrawn.xml: the root CA withdrawn from December 2026,
+# after the signature time-stamp of cades_t.p7m
+def trusted_lists():
+    b64 = lambda name: base64.b64encode(pem_der(name)).decode()
+    root = dict(type='CA/QC', name='Acme Root CA', cert=b64('root.crt'), status='withdrawn', start='2027-06-01T00:00:00Z',
+                history_cert=b64('root.crt'))
+    trusted_list('tsl.xml', [
+        root,
+        dict(type='CA/QC', name='Acme Issuing CA', cert=b64('root2.crt'), status='granted', start='2028-01-01T00:00:00Z',
+             history_cert=b64('int.crt')),
+        dict(type='TSA/QTST', name='Acme TSA', cert=b64('tsa.crt'), status='granted', start='2020-01-01T00:00:00Z',
+             history_cert=b64('tsa.crt')),
+    ])
+    trusted_list('tsl_withdrawn.xml', [dict(root, start='2026-12-01T00:00:00Z')])
+
+
+# a qualified certificate with every decoded statement and one that is not, and a CAdES signature with it
+def qualified():
+    statement = lambda dotted, info=b'': tlv(0x30, oid(dotted) + info)
+    statements = tlv(0x30, b''.join([
+        statement('0.4.0.1862.1.1'),
+        statement('0.4.0.1862.1.4'),
+        statement('0.4.0.1862.1.6', tlv(0x30, oid('0.4.0.1862.1.6.1'))),
+        statement('0.4.0.1862.1.5', tlv(0x30, tlv(0x30, tlv(0x16, b'https://example.com/pds') + tlv(0x13, b'en')))),
+        statement('0.4.0.1862.1.3', tlv(0x02, bytes([15]))),
+        statement('0.4.0.1862.1.2', tlv(0x30, tlv(0x13, b'EUR') + tlv(0x02, bytes([5])) + tlv(0x02, bytes([4])))),
+        statement('1.3.6.1.5.5.7.11.2', tlv(0x30, oid('0.4.0.194121.1.1'))),
+    ]))
+    cert('qualified', '/CN=Quentin Qualified/O=Acme', 'qualified', 'int', extensions='[qualified]\n'
+         'basicConstraints=CA:FALSE\nkeyUsage=critical,nonRepudiation\n1.3.6.1.5.5.7.1.3=DER:' + statements.hex() + '\n')
+    if missing('qualified.p7m'):
+        write('qualified.p7m', cms_sign_as('qualified', read('msg.txt'), '-nodetach', '-cades'))
+
+
+# a CA restricted to example.com names and outside 10.0.0.0/8, under policy 1.3.6.1.4.1.99999.1 that it maps
+# to .2 for its subjects; constrained.p7m by a subject inside the constraints, unconstrained.p7m by one outside
+def constraints():
+    cert('nc', '/CN=Constrained CA/O=Acme', 'nc', 'root', 'P-256', extensions='[nc]\n'
+         'basicConstraints=critical,CA:TRUE\nkeyUsage=critical,keyCertSign,cRLSign\n'
+         'nameConstraints=critical,permitted;email:example.com,permitted;DNS:example.com,excluded;IP:10.0.0.0/255.0.0.0\n'
+         'certificatePolicies=1.3.6.1.4.1.99999.1\npolicyMappings=critical,1.3.6.1.4.1.99999.1:1.3.6.1.4.1.99999.2\n')
+    leaf = '[{}]\nbasicConstraints=CA:FALSE\nkeyUsage=critical,digitalSignature\nsubjectAltName={}\n' \
+           'certificatePolicies=1.3.6.1.4.1.99999.2\n'
+    cert('carol', '/CN=Carol/O=Acme', 'carol', 'nc', 'P-256', extensions=leaf.format('carol', 'email:carol@example.com,DNS:www.example.com'))
+    cert('mallory', '/CN=Mallory/O=Acme', 'mallory', 'nc', 'P-256', extensions=leaf.format('mallory', 'email:mallory@example.org,IP:10.1.2.3'))
+    for name, signer in [('constrained.p7m', 'carol'), ('unconstrained.p7m', 'mallory')]:
+        if missing(name):
+            openssl('cms', '-sign', '-binary', '-nodetach', '-in', 'msg.txt', '-signer', signer + '.crt', '-inkey', signer + '.key',
+                    '-certfile', 'nc.crt', '-outform', 'DER', '-out', name)
+
+
+pki()
+enveloped()
+signed()
+revocation()
+timestamps()
+renewal()
+baseline()
+long_term()
+pades()
+nested()
+smime()
+code_signing()
+jar()
+pkcs12()
+anchors()
+trusted_lists()
+qualified()
+constraints()